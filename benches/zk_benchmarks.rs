@@ -1,16 +1,191 @@
 /// Benchmarks for zk-rust-api
 ///
 /// Run with: cargo bench
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+/// Compare sequential and parallel tree builds with: cargo bench --features parallel
+/// Compare binary and quaternary membership proofs with: cargo bench -- membership_prove
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{self, Circuit, ConstraintSystem},
+};
+use std::hint::black_box;
+use zk_rust_api::services::circuit_cost::measure;
+use zk_rust_api::services::merkle_circuit::MerkleConfig;
+use zk_rust_api::services::quaternary_circuit::QuaternaryConfig;
+use zk_rust_api::services::quaternary_tree::{hash_children, place_children};
+use zk_rust_api::{poseidon_commit, CircuitKeys, Fp, MerkleTree, PoseidonHasher, TreeHasher};
 
-fn benchmark_placeholder(c: &mut Criterion) {
-    c.bench_function("placeholder", |b| {
-        b.iter(|| {
-            // TODO: Add actual benchmarks once core functionality is implemented
-            black_box(42)
-        })
-    });
+/// Tree sizes (log2 of the number of leaves) the build benchmarks run at.
+const LOG_SIZES: [u32; 4] = [14, 16, 18, 20];
+
+/// Group sizes (log2 of the number of leaves) the prover benchmarks run at.
+/// Quaternary trees of the same size are half as deep, so sizes are even.
+const PROVE_LOG_SIZES: [u32; 4] = [8, 12, 16, 20];
+
+/// Secret of the member whose membership is proven.
+const SECRET: u64 = 42;
+
+fn leaves(count: usize) -> Vec<Fp> {
+    (1..=count as u64).map(Fp::from).collect()
+}
+
+fn benchmark_tree_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_tree_build");
+    //a 2^20 build takes seconds, so keep the sample count low
+    group.sample_size(10);
+
+    for log_size in LOG_SIZES {
+        let leaves = leaves(1 << log_size);
+        group.bench_with_input(BenchmarkId::new("sequential", log_size), &leaves, |b, leaves| {
+            b.iter(|| MerkleTree::new_sequential(black_box(leaves.clone())))
+        });
+        #[cfg(feature = "parallel")]
+        group.bench_with_input(BenchmarkId::new("parallel", log_size), &leaves, |b, leaves| {
+            b.iter(|| MerkleTree::new(black_box(leaves.clone())))
+        });
+    }
+    group.finish();
+}
+
+/// Membership in a binary tree of any depth, laid out exactly like MerkleCircuit.
+#[derive(Clone)]
+struct BinaryMembership {
+    secret: Value<Fp>,
+    siblings: Vec<Value<Fp>>,
+    directions: Vec<Value<Fp>>,
+}
+
+impl BinaryMembership {
+    /// The leftmost member of a tree of the given depth, with its root.
+    /// Siblings are arbitrary; only the number of levels affects the prover.
+    fn leftmost(depth: usize) -> (Self, Fp) {
+        let hasher = PoseidonHasher::new();
+        let siblings: Vec<Fp> = (1..=depth as u64).map(Fp::from).collect();
+        let root = siblings
+            .iter()
+            .fold(poseidon_commit(SECRET), |cur, &sibling| hasher.hash_pair(cur, sibling));
+        let circuit = Self {
+            secret: Value::known(Fp::from(SECRET)),
+            siblings: siblings.into_iter().map(Value::known).collect(),
+            directions: vec![Value::known(Fp::zero()); depth],
+        };
+        (circuit, root)
+    }
+}
+
+impl Circuit<Fp> for BinaryMembership {
+    type Config = MerkleConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            secret: Value::unknown(),
+            siblings: vec![Value::unknown(); self.siblings.len()],
+            directions: vec![Value::unknown(); self.directions.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        MerkleConfig::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        config.synthesize_membership(&mut layouter, self.secret, &self.siblings, &self.directions)
+    }
+}
+
+/// Membership in a quaternary tree of any depth, laid out exactly like QuaternaryMerkleCircuit.
+#[derive(Clone)]
+struct QuaternaryMembership {
+    secret: Value<Fp>,
+    siblings: Vec<[Value<Fp>; 3]>,
+    positions: Vec<Value<usize>>,
+}
+
+impl QuaternaryMembership {
+    /// The leftmost member of a tree of the given depth, with its root.
+    fn leftmost(depth: usize) -> (Self, Fp) {
+        let siblings: Vec<[Fp; 3]> = (1..=depth as u64)
+            .map(|level| [1, 2, 3].map(|j| Fp::from(level * 4 + j)))
+            .collect();
+        let root = siblings
+            .iter()
+            .fold(poseidon_commit(SECRET), |cur, &siblings| {
+                hash_children(place_children(cur, siblings, 0))
+            });
+        let circuit = Self {
+            secret: Value::known(Fp::from(SECRET)),
+            siblings: siblings.into_iter().map(|s| s.map(Value::known)).collect(),
+            positions: vec![Value::known(0); depth],
+        };
+        (circuit, root)
+    }
+}
+
+impl Circuit<Fp> for QuaternaryMembership {
+    type Config = QuaternaryConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            secret: Value::unknown(),
+            siblings: vec![[Value::unknown(); 3]; self.siblings.len()],
+            positions: vec![Value::unknown(); self.positions.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        QuaternaryConfig::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        config.synthesize_membership(&mut layouter, self.secret, &self.siblings, &self.positions)
+    }
+}
+
+/// Generates keys at the circuit's minimal k, outside the timed loop.
+fn setup<C: Circuit<Fp>>(name: &str, depth: usize, circuit: &C) -> CircuitKeys<C> {
+    let k = measure(name, depth as u32, circuit, 1).unwrap().min_k;
+    CircuitKeys::setup(k, circuit).unwrap()
+}
+
+fn benchmark_membership_prove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("membership_prove");
+    group.sample_size(10);
+
+    for log_size in PROVE_LOG_SIZES {
+        let depth = log_size as usize;
+        let (binary, root) = BinaryMembership::leftmost(depth);
+        let keys = setup("binary", depth, &binary);
+        let instances = [vec![root]];
+        group.bench_function(BenchmarkId::new("binary", log_size), |b| {
+            b.iter(|| {
+                keys.prove(black_box(std::slice::from_ref(&binary)), &instances)
+                    .unwrap()
+            })
+        });
+
+        let depth = depth / 2;
+        let (quaternary, root) = QuaternaryMembership::leftmost(depth);
+        let keys = setup("quaternary", depth, &quaternary);
+        let instances = [vec![root]];
+        group.bench_function(BenchmarkId::new("quaternary", log_size), |b| {
+            b.iter(|| {
+                keys.prove(black_box(std::slice::from_ref(&quaternary)), &instances)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
 }
 
-criterion_group!(benches, benchmark_placeholder);
+criterion_group!(benches, benchmark_tree_build, benchmark_membership_prove);
 criterion_main!(benches);
@@ -1,10 +1,15 @@
 /// Basic usage example for zk-rust-api
 ///
-/// This example demonstrates the fundamental operations of the library.
+/// This example demonstrates the fundamental operations of the library: building a tree of
+/// Poseidon commitments, proving membership of one of them without revealing which, and
+/// verifying the proof from its wire format.
 ///
-/// Run with: cargo run --example basic_usage
+/// Run with: cargo run --release --example basic_usage
 use tracing::info;
-use tracing_subscriber;
+use zk_rust_api::{
+    CircuitDefinition, MembershipWitness, MerkleCircuit, MerkleKeys, MerkleTree, ProofEnvelope,
+    CIRCUIT_ID, DEPTH, K,
+};
 
 fn main() {
     // Initialize logging
@@ -12,11 +17,43 @@ fn main() {
 
     info!("Starting zk-rust-api basic example");
 
-    // TODO: Add actual ZK proof generation and verification examples
-    // once the core API is implemented
+    //each member computes Poseidon(secret) locally and registers only the commitment
+    let secrets = [42u64, 99, 7, 13, 55, 77, 100, 200];
+    let commitments: Vec<_> = secrets
+        .iter()
+        .map(|&s| zk_rust_api::poseidon_commit(s))
+        .collect();
+    let tree = MerkleTree::new(commitments);
+    assert_eq!(tree.depth(), DEPTH, "the membership circuit is built for 2^DEPTH leaves");
+    info!("Built tree with {} leaves", tree.num_leaves());
 
-    println!("Hello from zk-rust-api!");
-    println!("This is a placeholder for actual ZK operations.");
+    //key generation is deterministic and only depends on the circuit
+    let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).expect("key generation failed");
+
+    //the prover knows secret 7; the circuit hides which leaf it is
+    let (circuit, public_inputs) = MerkleCircuit::default()
+        .build(&tree, MembershipWitness { secret: 7 })
+        .expect("7 is a member");
+    let proof = keys
+        .prove(&[circuit], std::slice::from_ref(&public_inputs))
+        .expect("proving failed");
+    info!("Created proof of {} bytes", proof.len());
+
+    //ship the proof as a versioned envelope and verify it on the other side
+    let envelope = ProofEnvelope::new(CIRCUIT_ID, DEPTH as u32, K, &public_inputs, proof);
+    let received = ProofEnvelope::from_json(&envelope.to_json()).expect("invalid envelope");
+    received
+        .expect_circuit(CIRCUIT_ID, DEPTH as u32, K)
+        .expect("envelope is for another circuit");
+    let valid = keys.verify(&received.proof, &[received.public_inputs_fp()]);
+
+    println!("membership proof valid: {}", valid);
+    assert!(valid);
+
+    //a secret that was never registered cannot be proven
+    assert!(MerkleCircuit::default()
+        .build(&tree, MembershipWitness { secret: 1 })
+        .is_err());
 
     info!("Example completed successfully");
 }
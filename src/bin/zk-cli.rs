@@ -0,0 +1,380 @@
+//! Command-line tool for committing, building trees, proving and verifying without the server.
+//!
+//! Run with: cargo run --release --bin zk-cli -- <command>
+//!
+//! Typical flow:
+//! ```text
+//! zk-cli commit 42                                  # hex commitment to register
+//! zk-cli tree build --leaves leaves.txt --index 0 --out path.json
+//! zk-cli prove --path path.json --secret 42 --out proof.json
+//! zk-cli vk --out vk.json                           # --depth must match prove's
+//! zk-cli verify --proof proof.json --vk vk.json
+//! zk-cli circuit-cost                               # k, columns, proof size per circuit
+//! ```
+use clap::{Parser, Subcommand};
+use halo2_proofs::circuit::Value;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use zk_rust_api::services::merkle_tree::{pad_root, zero_hashes};
+use zk_rust_api::services::merkle_tree_service::{
+    parse_commitment_list, MerklePathResponse, MerkleTreeService,
+};
+use zk_rust_api::services::zk_service::ZKService;
+use zk_rust_api::{
+    fp_to_hex, membership_k, poseidon_commit, poseidon_commit_attribute, CircuitVerifier, Fp,
+    FpHex, MerkleCircuit, MerkleKeys, MerkleTree, PoseidonHasher, ProofEnvelope, CIRCUIT_ID, DEPTH,
+    MAX_DEPTH,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "zk-cli",
+    version,
+    about = "Offline commitments, Merkle trees and ZK proofs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the hex commitment Poseidon(secret, attribute) to register in the tree
+    Commit {
+        secret: u64,
+        /// Attribute bound into the commitment (for range proofs); 0 for plain membership
+        #[arg(long, default_value_t = 0)]
+        attribute: u64,
+    },
+    /// Builds Merkle trees from commitment files
+    Tree {
+        #[command(subcommand)]
+        command: TreeCommand,
+    },
+    /// Proves membership of Poseidon(secret) using a path file from `tree build --index`
+    Prove {
+        /// Path file written by `tree build --index` or fetched from `GET /tree/path/{commitment}`
+        #[arg(long)]
+        path: PathBuf,
+        /// The private secret; it is never written to disk
+        #[arg(long)]
+        secret: u64,
+        /// Circuit depth; must be at least the path's depth and match the server's tree.depth
+        #[arg(long, default_value_t = DEPTH)]
+        depth: usize,
+        /// Where to write the ProofEnvelope JSON (stdout if omitted)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Writes the fingerprint of the membership circuit's verifying key
+    Vk {
+        /// Circuit depth the proofs to verify were made at
+        #[arg(long, default_value_t = DEPTH)]
+        depth: usize,
+        /// Where to write the verifying key JSON (stdout if omitted)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Verifies a ProofEnvelope, checking the verifying key against the file written by `vk`
+    Verify {
+        /// ProofEnvelope JSON written by `prove`
+        #[arg(long)]
+        proof: PathBuf,
+        /// Verifying key JSON written by `vk`
+        #[arg(long)]
+        vk: PathBuf,
+    },
+    /// Prints the minimal k, columns, gates, degree and proof size of each served circuit and the
+    /// membership circuit at each depth up to 20
+    CircuitCost,
+}
+
+#[derive(Subcommand)]
+enum TreeCommand {
+    /// Builds a tree and prints its root, or the path of one leaf with `--index`
+    Build {
+        /// File with one hex commitment per line; blank lines and `#` comments are ignored
+        #[arg(long)]
+        leaves: PathBuf,
+        /// Leaf whose path to output instead of the tree summary
+        #[arg(long)]
+        index: Option<usize>,
+        /// Where to write the JSON output (stdout if omitted)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// Summary printed by `tree build`.
+#[derive(Serialize)]
+struct TreeSummary {
+    root: FpHex,
+    depth: usize,
+    num_leaves: usize,
+}
+
+/// The verifying key a proof must verify against, written by `vk`.
+/// halo2_proofs 0.3 cannot serialize the key itself, so `verify` derives it again and checks
+/// it against the fingerprint.
+#[derive(Serialize, Deserialize)]
+struct VkFile {
+    circuit_id: String,
+    depth: u32,
+    k: u32,
+    /// BLAKE2b-256 of the verifying key, from `CircuitVerifier::vk_fingerprint`
+    fingerprint: String,
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Commit { secret, attribute } => {
+            println!("{}", fp_to_hex(&poseidon_commit_attribute(secret, attribute)));
+            Ok(())
+        },
+        Command::Tree {
+            command: TreeCommand::Build { leaves, index, out },
+        } => tree_build(&leaves, index, out.as_deref()),
+        Command::Prove {
+            path,
+            secret,
+            depth,
+            out,
+        } => prove(&path, secret, depth, out.as_deref()),
+        Command::Vk { depth, out } => write_vk(depth, out.as_deref()),
+        Command::Verify { proof, vk } => verify(&proof, &vk),
+        Command::CircuitCost => circuit_cost(),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// Builds a tree from a commitments file and outputs its summary or one leaf's path.
+fn tree_build(
+    leaves: &Path,
+    index: Option<usize>,
+    out: Option<&Path>,
+) -> std::result::Result<(), String> {
+    let commitments = read_commitments(leaves)?;
+    if commitments.is_empty() {
+        return Err(format!("{}: no commitments", leaves.display()));
+    }
+    let tree = MerkleTree::new(commitments);
+
+    let json = match index {
+        None => serde_json::to_string_pretty(&TreeSummary {
+            root: FpHex(tree.root()),
+            depth: tree.depth(),
+            num_leaves: tree.num_leaves(),
+        }),
+        Some(index) => {
+            let proof = tree
+                .generate_proof(index)
+                .ok_or_else(|| format!("leaf index {} is out of range", index))?;
+            serde_json::to_string_pretty(&MerklePathResponse::from_proof(index, &proof))
+        },
+    }
+    .map_err(|e| e.to_string())?;
+    write_output(out, &json)
+}
+
+/// Proves knowledge of a secret whose commitment is the leaf of the given path.
+/// A path shallower than `circuit_depth` is padded with zero subtrees, and the proof is made
+/// against the padded root.
+fn prove(
+    path: &Path,
+    secret: u64,
+    circuit_depth: usize,
+    out: Option<&Path>,
+) -> std::result::Result<(), String> {
+    check_depth(circuit_depth)?;
+    let file: MerklePathResponse = read_json(path)?;
+    let leaf = file.leaf.0;
+    if poseidon_commit(secret) != leaf {
+        return Err("Poseidon(secret) does not match the leaf in the path file".to_string());
+    }
+    let depth = file.siblings.len();
+    if depth > circuit_depth {
+        return Err(format!(
+            "path has {} levels but the circuit has depth {}; pass a larger --depth",
+            depth, circuit_depth
+        ));
+    }
+    if file.directions.len() != depth {
+        return Err(format!(
+            "path has {} siblings but {} directions",
+            depth,
+            file.directions.len()
+        ));
+    }
+
+    let hasher = PoseidonHasher::new();
+    let zeros = zero_hashes(&hasher, circuit_depth);
+    let root = pad_root(&hasher, &zeros, file.root.0, depth);
+    let siblings: Vec<Value<Fp>> = file
+        .siblings
+        .iter()
+        .map(|s| s.0)
+        .chain(zeros[depth..circuit_depth].iter().copied())
+        .map(Value::known)
+        .collect();
+    let mut directions: Vec<Value<Fp>> = file
+        .directions
+        .iter()
+        .map(|d| match d {
+            0 => Ok(Value::known(Fp::zero())),
+            1 => Ok(Value::known(Fp::one())),
+            d => Err(format!("direction must be 0 or 1, found {}", d)),
+        })
+        .collect::<std::result::Result<_, _>>()?;
+    directions.resize(circuit_depth, Value::known(Fp::zero()));
+    let circuit = MerkleCircuit {
+        secret: Value::known(Fp::from(secret)),
+        siblings,
+        directions,
+    };
+
+    let keys = membership_keys(circuit_depth)?;
+    let proof = keys
+        .prove(&[circuit], &[vec![root]])
+        .map_err(|e| format!("proving failed: {:?}", e))?;
+    let k = membership_k(circuit_depth);
+    let envelope = ProofEnvelope::new(CIRCUIT_ID, circuit_depth as u32, k, &[root], proof);
+    write_output(out, &envelope.to_json())
+}
+
+/// Writes the fingerprint of the membership circuit's verifying key at the given depth.
+fn write_vk(depth: usize, out: Option<&Path>) -> std::result::Result<(), String> {
+    check_depth(depth)?;
+    let verifier = membership_verifier(depth)?;
+    let vk = VkFile {
+        circuit_id: CIRCUIT_ID.to_string(),
+        depth: depth as u32,
+        k: membership_k(depth),
+        fingerprint: verifier.vk_fingerprint(),
+    };
+    write_output(out, &serde_json::to_string_pretty(&vk).map_err(|e| e.to_string())?)
+}
+
+/// Verifies a proof envelope; fails (non-zero exit) if the proof is invalid.
+fn verify(proof: &Path, vk: &Path) -> std::result::Result<(), String> {
+    let envelope = ProofEnvelope::from_json(&read_file(proof)?).map_err(|e| e.to_string())?;
+    let vk: VkFile = read_json(vk)?;
+    envelope
+        .expect_circuit(&vk.circuit_id, vk.depth, vk.k)
+        .map_err(|e| e.to_string())?;
+    let depth = vk.depth as usize;
+    check_depth(depth)?;
+    envelope
+        .expect_circuit(CIRCUIT_ID, vk.depth, membership_k(depth))
+        .map_err(|e| e.to_string())?;
+
+    //the fingerprint catches a binary whose circuit differs from the one the key file was
+    //written for, and an edited key file
+    let verifier = membership_verifier(depth)?;
+    if verifier.vk_fingerprint() != vk.fingerprint {
+        return Err("verifying key fingerprint does not match".to_string());
+    }
+
+    if verifier.verify(&envelope.proof, &[envelope.public_inputs_fp()]) {
+        println!("valid");
+        Ok(())
+    } else {
+        Err("invalid proof".to_string())
+    }
+}
+
+/// Prints the minimal k, columns, gates, degree and proof size of each served circuit and of
+/// the membership circuit at each supported depth.
+fn circuit_cost() -> std::result::Result<(), String> {
+    let zk_service = ZKService::new(Arc::new(MerkleTreeService::new()));
+    let report = zk_service
+        .cost_report()
+        .map_err(|e| format!("failed to measure circuits: {}", e))?;
+
+    println!(
+        "{:<22} {:>5} {:>5} {:>7} {:>6} {:>6} {:>8} {:>9} {:>5} {:>6} {:>11}",
+        "circuit",
+        "depth",
+        "min_k",
+        "rows",
+        "advice",
+        "fixed",
+        "instance",
+        "selectors",
+        "gates",
+        "degree",
+        "proof bytes"
+    );
+    for cost in report.registered.iter().chain(&report.membership_by_depth) {
+        println!(
+            "{:<22} {:>5} {:>5} {:>7} {:>6} {:>6} {:>8} {:>9} {:>5} {:>6} {:>11}",
+            cost.circuit_id,
+            cost.depth,
+            cost.min_k,
+            cost.rows,
+            cost.advice_columns,
+            cost.fixed_columns,
+            cost.instance_columns,
+            cost.selectors,
+            cost.gates,
+            cost.max_degree,
+            cost.proof_size_bytes
+        );
+    }
+    Ok(())
+}
+
+fn check_depth(depth: usize) -> std::result::Result<(), String> {
+    if (1..=MAX_DEPTH).contains(&depth) {
+        Ok(())
+    } else {
+        Err(format!("depth must be between 1 and {}, found {}", MAX_DEPTH, depth))
+    }
+}
+
+fn membership_keys(depth: usize) -> std::result::Result<MerkleKeys, String> {
+    MerkleKeys::setup(membership_k(depth), &MerkleCircuit::new(depth))
+        .map_err(|e| format!("key generation failed: {:?}", e))
+}
+
+/// Derives only the membership circuit's verifying key, which is cheaper than full keygen.
+fn membership_verifier(
+    depth: usize,
+) -> std::result::Result<CircuitVerifier<MerkleCircuit>, String> {
+    CircuitVerifier::setup(membership_k(depth), &MerkleCircuit::new(depth))
+        .map_err(|e| format!("key generation failed: {:?}", e))
+}
+
+/// Reads one hex commitment per line, skipping blank lines and `#` comments.
+fn read_commitments(path: &Path) -> std::result::Result<Vec<Fp>, String> {
+    parse_commitment_list(&read_file(path)?)
+        .map_err(|line| format!("{}:{}: invalid commitment", path.display(), line))
+}
+
+fn read_file(path: &Path) -> std::result::Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> std::result::Result<T, String> {
+    serde_json::from_str(&read_file(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write_output(out: Option<&Path>, contents: &str) -> std::result::Result<(), String> {
+    match out {
+        Some(path) => fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => {
+            println!("{}", contents);
+            Ok(())
+        },
+    }
+}
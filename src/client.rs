@@ -0,0 +1,420 @@
+//! Typed async HTTP client for the zk-rust-api server.
+//!
+//! Enabled with the `client` feature. Requests and responses use the same structs as the
+//! server, so a Rust service never has to hand-write the JSON.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), zk_rust_api::client::ClientError> {
+//! use zk_rust_api::{client::ZkApiClient, poseidon_commit};
+//!
+//! let client = ZkApiClient::new("http://localhost:3000");
+//! client.register(&poseidon_commit(42)).await?;
+//! let envelope = client.prove(42).await?;
+//! assert!(client.verify(&envelope).await?.valid);
+//! # Ok(())
+//! # }
+//! ```
+
+use halo2_proofs::pasta::Fp;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+
+use crate::controllers::merkle_tree_controller::AddToTreeRequest;
+use crate::controllers::zk_controller::{BatchProofRequest, ZKProofRequest};
+use crate::error::ApiErrorBody;
+use crate::services::circuit_registry::CircuitInfo;
+use crate::services::merkle_tree_service::{
+    ConsistencyResponse, FpHex, ImportRequest, ImportResponse, LeavesResponse, MerklePathResponse,
+    MultiProofRequest, MultiProofResponse, PublicKeyResponse, RegisterRequest, SignedTreeHead,
+    TreeInfoResponse, TreeResponse, TreeVisualizationResponse,
+};
+use crate::services::proof_envelope::ProofEnvelope;
+use crate::services::zk_service::{BatchProofResponse, ZKProofResponse, ZKVerifyResponse};
+
+/// Error returned by ZkApiClient.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or the response could not be read
+    Http(reqwest::Error),
+    /// The server answered with an error status and body
+    Api { status: u16, body: ApiErrorBody },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "HTTP error: {}", e),
+            ClientError::Api { status, body } => {
+                write!(f, "API error {} {}: {}", status, body.code, body.message)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+/// Client for one zk-rust-api server.
+#[derive(Debug, Clone)]
+pub struct ZkApiClient {
+    base_url: String,
+    http: reqwest::Client,
+    /// Bearer token sent to admin endpoints
+    admin_token: Option<String>,
+}
+
+impl ZkApiClient {
+    /// Creates a client for the server at `base_url` (e.g. `http://localhost:3000`).
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Creates a client that sends requests through a preconfigured reqwest client
+    /// (timeouts, proxies, TLS settings).
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+            admin_token: None,
+        }
+    }
+
+    /// Sends `token` (the server's `admin.token`) to admin endpoints such as
+    /// `import_commitments`.
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// Registers a commitment; the secret never leaves the caller.
+    pub async fn register(&self, commitment: &Fp) -> Result<TreeResponse, ClientError> {
+        let request = RegisterRequest {
+            commitment: FpHex(*commitment),
+            idempotent: false,
+        };
+        self.post("/register", &request).await
+    }
+
+    /// Registers a commitment, or returns its existing index if it is already registered,
+    /// so a registration can be retried after a lost response.
+    pub async fn register_idempotent(&self, commitment: &Fp) -> Result<TreeResponse, ClientError> {
+        let request = RegisterRequest {
+            commitment: FpHex(*commitment),
+            idempotent: true,
+        };
+        self.post("/register", &request).await
+    }
+
+    /// Appends a set of commitments in one request; either all are added or none.
+    /// Needs `with_admin_token`.
+    pub async fn import_commitments(
+        &self,
+        commitments: &[Fp],
+    ) -> Result<ImportResponse, ClientError> {
+        self.import(commitments, false).await
+    }
+
+    /// Imports commitments, skipping those that are already registered, so an import can be
+    /// retried after a lost response. Needs `with_admin_token`.
+    pub async fn import_commitments_idempotent(
+        &self,
+        commitments: &[Fp],
+    ) -> Result<ImportResponse, ClientError> {
+        self.import(commitments, true).await
+    }
+
+    /// Adds a raw value to the tree (legacy / debug use).
+    pub async fn add_to_tree(&self, value: u64) -> Result<TreeResponse, ClientError> {
+        self.post("/tree", &AddToTreeRequest { value }).await
+    }
+
+    /// Fetches the tree's root, depth and leaf counts.
+    pub async fn tree_info(&self) -> Result<TreeInfoResponse, ClientError> {
+        self.get("/tree").await
+    }
+
+    /// Fetches up to `limit` registered leaves starting at `offset`.
+    pub async fn leaves(&self, offset: usize, limit: usize) -> Result<LeavesResponse, ClientError> {
+        self.get(&format!("/tree/leaves?offset={}&limit={}", offset, limit))
+            .await
+    }
+
+    /// Fetches one compact inclusion proof for several registered leaves.
+    pub async fn multiproof(&self, indices: &[usize]) -> Result<MultiProofResponse, ClientError> {
+        let request = MultiProofRequest {
+            indices: indices.to_vec(),
+        };
+        self.post("/tree/multiproof", &request).await
+    }
+
+    /// Fetches the current signed tree head. Check it with `head.verify(&public_key)` against a
+    /// pinned key.
+    pub async fn tree_head(&self) -> Result<SignedTreeHead, ClientError> {
+        self.get("/tree/head").await
+    }
+
+    /// Fetches the public key tree heads are signed with.
+    pub async fn signing_key(&self) -> Result<PublicKeyResponse, ClientError> {
+        self.get("/tree/signing-key").await
+    }
+
+    /// Fetches a proof that the tree of `from` leaves is a prefix of the tree of `to` leaves.
+    /// Check it with `response.proof().verify(&old_root, &new_root)` against roots recorded
+    /// independently of the server.
+    pub async fn consistency(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> Result<ConsistencyResponse, ClientError> {
+        self.get(&format!("/tree/consistency?from={}&to={}", from, to))
+            .await
+    }
+
+    /// Fetches the Merkle path of a registered commitment.
+    pub async fn proof_path(&self, commitment: &Fp) -> Result<MerklePathResponse, ClientError> {
+        self.get(&format!("/tree/path/{}", FpHex(*commitment)))
+            .await
+    }
+
+    /// Renders the tree and returns the image URL.
+    pub async fn visualize(&self) -> Result<TreeVisualizationResponse, ClientError> {
+        self.get("/tree/visualize").await
+    }
+
+    /// Checks membership of Poseidon(secret) with the server's mock prover.
+    pub async fn zk_proof(&self, secret: u64) -> Result<ZKProofResponse, ClientError> {
+        self.post("/zk", &ZKProofRequest { secret }).await
+    }
+
+    /// Creates a real membership proof for a secret.
+    pub async fn prove(&self, secret: u64) -> Result<ProofEnvelope, ClientError> {
+        self.post("/zk/prove", &ZKProofRequest { secret }).await
+    }
+
+    /// Verifies a membership proof envelope.
+    pub async fn verify(&self, envelope: &ProofEnvelope) -> Result<ZKVerifyResponse, ClientError> {
+        self.post("/zk/verify", envelope).await
+    }
+
+    /// Verifies a membership proof envelope and also requires its root to be a current or
+    /// recent root of the tree.
+    pub async fn verify_known_root(
+        &self,
+        envelope: &ProofEnvelope,
+    ) -> Result<ZKVerifyResponse, ClientError> {
+        self.post("/zk/verify?require_known_root=true", envelope)
+            .await
+    }
+
+    /// Lists the circuits the server can prove and verify.
+    pub async fn circuits(&self) -> Result<Vec<CircuitInfo>, ClientError> {
+        self.get("/zk/circuits").await
+    }
+
+    /// Creates a proof for any served circuit from its witness.
+    pub async fn prove_circuit<W: Serialize>(
+        &self,
+        circuit_id: &str,
+        witness: &W,
+    ) -> Result<ProofEnvelope, ClientError> {
+        self.post(&format!("/zk/{}/prove", circuit_id), witness)
+            .await
+    }
+
+    /// Verifies a proof envelope for any served circuit.
+    pub async fn verify_circuit(
+        &self,
+        circuit_id: &str,
+        envelope: &ProofEnvelope,
+    ) -> Result<ZKVerifyResponse, ClientError> {
+        self.post(&format!("/zk/{}/verify", circuit_id), envelope)
+            .await
+    }
+
+    /// Proves membership for many secrets in one batch proof.
+    pub async fn prove_batch(&self, secrets: &[u64]) -> Result<BatchProofResponse, ClientError> {
+        let request = BatchProofRequest {
+            secrets: secrets.to_vec(),
+        };
+        self.post("/zk/batch", &request).await
+    }
+
+    /// Verifies a batch proof.
+    pub async fn verify_batch(
+        &self,
+        proof: &BatchProofResponse,
+    ) -> Result<ZKVerifyResponse, ClientError> {
+        self.post("/zk/batch/verify", proof).await
+    }
+
+    async fn import(
+        &self,
+        commitments: &[Fp],
+        idempotent: bool,
+    ) -> Result<ImportResponse, ClientError> {
+        let body = ImportRequest {
+            commitments: commitments.iter().copied().map(FpHex).collect(),
+            idempotent,
+        };
+        let mut request = self.http.post(self.url("/admin/import")).json(&body);
+        if let Some(token) = &self.admin_token {
+            request = request.bearer_auth(token);
+        }
+        Self::parse(request.send().await?).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        let response = self.http.get(self.url(path)).send().await?;
+        Self::parse(response).await
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        let response = self.http.post(self.url(path)).json(body).send().await?;
+        Self::parse(response).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Decodes a success body, or turns an error status into ClientError::Api.
+    /// Error bodies that are not ApiErrorBody JSON (e.g. from a proxy) are kept as the message.
+    async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ClientError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+
+        let text = response.text().await?;
+        let body = serde_json::from_str(&text).unwrap_or(ApiErrorBody {
+            code: "unknown".to_string(),
+            message: text,
+            details: None,
+        });
+        Err(ClientError::Api {
+            status: status.as_u16(),
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::server::{build_router, setup_container};
+    use crate::services::merkle_tree_service::poseidon_commit;
+
+    /// Serves the full router on a local port and returns a client for it.
+    async fn spawn_server(name: &str) -> ZkApiClient {
+        let dir =
+            std::env::temp_dir().join(format!("zk-api-client-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.storage.path = dir.clone();
+        config.signing.key_file = dir.join("tree-head.key");
+        config.admin.token = Some("s3cret".to_string());
+
+        let container = setup_container(&config).unwrap();
+        let app = build_router(&container, &config);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ZkApiClient::new(url)
+    }
+
+    #[tokio::test]
+    async fn test_register_and_read_the_tree() {
+        let client = spawn_server("register").await;
+
+        let first = client.register(&poseidon_commit(42)).await.unwrap();
+        let second = client.register(&poseidon_commit(7)).await.unwrap();
+        assert_eq!((first.index, second.index), (0, 1));
+
+        let info = client.tree_info().await.unwrap();
+        assert_eq!(info.num_leaves, 2);
+        assert_eq!(info.root, second.data);
+        let key = client.signing_key().await.unwrap();
+        assert!(second.head.verify(&key.verifying_key().unwrap()));
+
+        let again = client
+            .register_idempotent(&poseidon_commit(42))
+            .await
+            .unwrap();
+        assert_eq!(again.index, 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_sends_the_admin_token() {
+        let client = spawn_server("import").await;
+        let members = [poseidon_commit(1), poseidon_commit(2)];
+
+        match client.import_commitments(&members).await {
+            Err(ClientError::Api { status, body }) => {
+                assert_eq!(status, 401);
+                assert_eq!(body.code, "unauthorized");
+            },
+            other => panic!("expected a 401, got {:?}", other),
+        }
+
+        let admin = client.with_admin_token("s3cret");
+        let response = admin.import_commitments(&members).await.unwrap();
+        assert_eq!(response.imported, 2);
+
+        //a retry with one more member appends only the new one
+        let retry = [members[0], members[1], poseidon_commit(3)];
+        let response = admin.import_commitments_idempotent(&retry).await.unwrap();
+        assert_eq!((response.imported, response.num_members), (1, 3));
+    }
+
+    #[tokio::test]
+    async fn test_prove_and_verify() {
+        let client = spawn_server("prove").await;
+        client.register(&poseidon_commit(42)).await.unwrap();
+        client.register(&poseidon_commit(7)).await.unwrap();
+
+        let envelope = client.prove(42).await.unwrap();
+        assert!(client.verify(&envelope).await.unwrap().valid);
+        assert!(client.verify_known_root(&envelope).await.unwrap().valid);
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_are_decoded() {
+        let client = spawn_server("errors").await;
+        client.register(&poseidon_commit(42)).await.unwrap();
+
+        match client.register(&poseidon_commit(42)).await {
+            Err(ClientError::Api { status, body }) => {
+                assert_eq!(status, 409);
+                assert_eq!(body.code, "duplicate_commitment");
+            },
+            other => panic!("expected a 409, got {:?}", other),
+        }
+        match client.prove(99).await {
+            Err(ClientError::Api { status, body }) => {
+                assert_eq!(status, 404);
+                assert_eq!(body.code, "commitment_not_in_tree");
+            },
+            other => panic!("expected a 404, got {:?}", other),
+        }
+
+        //a body that is not an ApiErrorBody (here axum's empty 404) keeps the status
+        let lost = ZkApiClient::new(format!("{}/no-such-prefix", client.base_url));
+        match lost.tree_info().await {
+            Err(ClientError::Api { status, body }) => {
+                assert_eq!(status, 404);
+                assert_eq!(body.code, "unknown");
+            },
+            other => panic!("expected a 404, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,559 @@
+//! Server configuration, loaded from a TOML file and overridden by environment variables.
+//!
+//! The file is read from `$ZK_API_CONFIG`, or `config.toml` in the working directory if it
+//! exists; every setting has a default, so the file is optional. See `config.example.toml`.
+//!
+//! | Variable                    | Setting                       |
+//! |-----------------------------|-------------------------------|
+//! | `ZK_API_BIND`               | `server.bind`                 |
+//! | `ZK_API_CORS_ORIGINS`       | `server.cors_origins` (comma-separated) |
+//! | `ZK_API_LOG`                | `server.log_filter`           |
+//! | `ZK_API_STORAGE_PATH`       | `storage.path`                |
+//! | `ZK_API_TREE_DEPTH`         | `tree.depth`                  |
+//! | `ZK_API_IMPORT_FILE`        | `tree.import_file`            |
+//! | `ZK_API_ROOT_HISTORY_SIZE`  | `tree.root_history_size`      |
+//! | `ZK_API_MAX_BATCH_SIZE`     | `proving.max_batch_size`      |
+//! | `ZK_API_SIGNING_KEY_FILE`   | `signing.key_file`            |
+//! | `ZK_API_ADMIN_TOKEN`        | `admin.token`                 |
+
+use ed25519_dalek::SigningKey;
+use ff::PrimeField;
+use halo2_proofs::pasta::Fp;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::services::merkle_circuit::{DEPTH, MAX_DEPTH};
+use crate::services::merkle_tree_service::{fp_to_hex, parse_commitment_list};
+use crate::services::tree_head::parse_signing_key;
+
+/// Environment variable naming the config file.
+pub const CONFIG_PATH_VAR: &str = "ZK_API_CONFIG";
+/// Config file used when `ZK_API_CONFIG` is not set, if it exists.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Complete server configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub tree: TreeConfig,
+    pub proving: ProvingConfig,
+    pub signing: SigningConfig,
+    pub admin: AdminConfig,
+}
+
+/// HTTP listener settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address and port to listen on
+    pub bind: SocketAddr,
+    /// Origins allowed by CORS; `["*"]` allows any origin
+    pub cors_origins: Vec<String>,
+    /// tracing filter used when `RUST_LOG` is not set
+    pub log_filter: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            cors_origins: vec!["*".to_string()],
+            log_filter: "rust_api=debug,tower_http=debug".to_string(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Returns true if CORS should allow any origin.
+    pub fn cors_allows_any(&self) -> bool {
+        self.cors_origins.iter().any(|o| o == "*")
+    }
+}
+
+/// Where the server writes files (tree visualizations), served at `/static`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub path: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("static"),
+        }
+    }
+}
+
+/// Merkle tree settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TreeConfig {
+    /// Maximum depth; the tree holds at most 2^depth leaves and rejects further registrations.
+    /// Between 1 and MAX_DEPTH. The Merkle circuits are built for this depth, so a deeper tree
+    /// holds more members but takes longer to prove for (see `GET /zk/circuit-info/cost`).
+    /// Changing it changes the circuits' keys and the padded roots proofs are made against,
+    /// so proofs made before the change no longer verify.
+    pub depth: usize,
+    /// File of hex commitments (one per line, `#` comments) registered at startup.
+    /// The tree starts empty if unset.
+    pub import_file: Option<PathBuf>,
+    /// Number of recent roots (including the current one) that proofs may be verified against
+    pub root_history_size: usize,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self {
+            depth: DEPTH,
+            import_file: None,
+            root_history_size: 32,
+        }
+    }
+}
+
+impl TreeConfig {
+    /// Number of leaves a tree of the configured depth can hold.
+    pub fn capacity(&self) -> usize {
+        1usize << self.depth
+    }
+}
+
+/// Limits on proving work per request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvingConfig {
+    /// Maximum number of secrets in one batch proof
+    pub max_batch_size: usize,
+}
+
+impl Default for ProvingConfig {
+    fn default() -> Self {
+        Self { max_batch_size: 64 }
+    }
+}
+
+/// Key the server signs tree heads with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SigningConfig {
+    /// File holding the ed25519 key's 32-byte seed as 64 hex chars.
+    /// Created with a new key on first start if it does not exist, so heads stay verifiable
+    /// against the same key across restarts.
+    pub key_file: PathBuf,
+}
+
+impl Default for SigningConfig {
+    fn default() -> Self {
+        Self {
+            key_file: PathBuf::from("tree-head.key"),
+        }
+    }
+}
+
+/// Access to the admin endpoints (`POST /admin/import`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token admin requests must send as `Authorization: Bearer <token>`.
+    /// The admin endpoints are not mounted if unset.
+    pub token: Option<String>,
+}
+
+/// Error loading or validating the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file could not be read
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The config file is not valid TOML or has unknown or mistyped settings
+    Parse(toml::de::Error),
+    /// An environment variable could not be parsed
+    Env {
+        var: &'static str,
+        value: String,
+        reason: String,
+    },
+    /// A setting has an unusable value
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => write!(f, "{}: {}", path.display(), source),
+            ConfigError::Parse(e) => write!(f, "invalid config file: {}", e),
+            ConfigError::Env { var, value, reason } => {
+                write!(f, "invalid {}={:?}: {}", var, value, reason)
+            },
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config file (if any), applies environment overrides and validates the result.
+    pub fn load() -> std::result::Result<Self, ConfigError> {
+        let mut config = match std::env::var(CONFIG_PATH_VAR) {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            },
+            Err(_) => Self::default(),
+        };
+        config.apply_env(std::env::vars())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads a TOML config file; settings it omits keep their defaults.
+    pub fn from_file(path: &Path) -> std::result::Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml(&contents)
+    }
+
+    /// Parses TOML config; settings it omits keep their defaults.
+    pub fn from_toml(contents: &str) -> std::result::Result<Self, ConfigError> {
+        toml::from_str(contents).map_err(ConfigError::Parse)
+    }
+
+    /// Overrides settings from `ZK_API_*` variables. Takes the variables as an iterator so
+    /// tests do not have to modify the process environment.
+    pub fn apply_env(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> std::result::Result<(), ConfigError> {
+        for (var, value) in vars {
+            match var.as_str() {
+                "ZK_API_BIND" => self.server.bind = parse_env("ZK_API_BIND", &value)?,
+                "ZK_API_CORS_ORIGINS" => self.server.cors_origins = split_list(&value),
+                "ZK_API_LOG" => self.server.log_filter = value,
+                "ZK_API_STORAGE_PATH" => self.storage.path = PathBuf::from(value),
+                "ZK_API_TREE_DEPTH" => self.tree.depth = parse_env("ZK_API_TREE_DEPTH", &value)?,
+                "ZK_API_IMPORT_FILE" => {
+                    self.tree.import_file = Some(value).filter(|v| !v.is_empty()).map(PathBuf::from)
+                },
+                "ZK_API_ROOT_HISTORY_SIZE" => {
+                    self.tree.root_history_size = parse_env("ZK_API_ROOT_HISTORY_SIZE", &value)?
+                },
+                "ZK_API_MAX_BATCH_SIZE" => {
+                    self.proving.max_batch_size = parse_env("ZK_API_MAX_BATCH_SIZE", &value)?
+                },
+                "ZK_API_SIGNING_KEY_FILE" => self.signing.key_file = PathBuf::from(value),
+                "ZK_API_ADMIN_TOKEN" => self.admin.token = Some(value),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects settings the server cannot run with.
+    pub fn validate(&self) -> std::result::Result<(), ConfigError> {
+        if !(1..=MAX_DEPTH).contains(&self.tree.depth) {
+            return Err(ConfigError::Invalid(format!(
+                "tree.depth must be between 1 and {}, found {}",
+                MAX_DEPTH, self.tree.depth
+            )));
+        }
+        if self.tree.root_history_size == 0 {
+            return Err(ConfigError::Invalid(
+                "tree.root_history_size must be at least 1".to_string(),
+            ));
+        }
+        if self.proving.max_batch_size == 0 {
+            return Err(ConfigError::Invalid(
+                "proving.max_batch_size must be at least 1".to_string(),
+            ));
+        }
+        if self.signing.key_file.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid("signing.key_file must not be empty".to_string()));
+        }
+        if self
+            .admin
+            .token
+            .as_deref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            return Err(ConfigError::Invalid(
+                "admin.token must not be empty; leave it unset to disable admin endpoints"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads the commitments of `tree.import_file`, or none if it is unset.
+    /// Fails if the file is unreadable, has an invalid line, repeats a commitment, contains
+    /// the zero commitment or does not fit in the tree.
+    pub fn load_initial_members(&self) -> std::result::Result<Vec<Fp>, ConfigError> {
+        let Some(path) = &self.tree.import_file else {
+            return Ok(Vec::new());
+        };
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let members = parse_commitment_list(&contents).map_err(|line| {
+            ConfigError::Invalid(format!("{}:{}: invalid commitment", path.display(), line))
+        })?;
+        if members.len() > self.tree.capacity() {
+            return Err(ConfigError::Invalid(format!(
+                "{}: {} commitments do not fit in a tree of depth {}",
+                path.display(),
+                members.len(),
+                self.tree.depth
+            )));
+        }
+        if members.contains(&Fp::zero()) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: the zero commitment is reserved for padding",
+                path.display()
+            )));
+        }
+        let mut seen = HashSet::with_capacity(members.len());
+        if let Some(duplicate) = members.iter().find(|m| !seen.insert(m.to_repr())) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: commitment {} appears more than once",
+                path.display(),
+                fp_to_hex(duplicate)
+            )));
+        }
+        Ok(members)
+    }
+
+    /// Reads the tree head signing key from `signing.key_file`.
+    /// If the file does not exist, a new key is generated and written there (readable only by
+    /// the owner on unix), so later starts reload the same key.
+    pub fn load_signing_key(&self) -> std::result::Result<SigningKey, ConfigError> {
+        let path = &self.signing.key_file;
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = SigningKey::generate(&mut OsRng);
+                write_signing_key(path, &key).map_err(|source| ConfigError::Read {
+                    path: path.clone(),
+                    source,
+                })?;
+                tracing::info!("generated tree head signing key at {}", path.display());
+                return Ok(key);
+            },
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: path.clone(),
+                    source,
+                })
+            },
+        };
+        parse_signing_key(&contents).ok_or_else(|| {
+            ConfigError::Invalid(format!(
+                "{}: expected a 32-byte ed25519 seed as 64 hex chars",
+                path.display()
+            ))
+        })
+    }
+}
+
+/// Writes a signing key's seed as hex, failing rather than replacing an existing file.
+fn write_signing_key(path: &Path, key: &SigningKey) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", hex::encode(key.to_bytes()))
+}
+
+fn parse_env<T>(var: &'static str, value: &str) -> std::result::Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::Env {
+        var,
+        value: value.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Splits a comma-separated list, dropping empty entries so `""` means an empty list.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_file_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_file_overrides_only_given_settings() {
+        let config = Config::from_toml(
+            r#"
+            [server]
+            bind = "127.0.0.1:8080"
+            cors_origins = ["https://app.example.com"]
+
+            [tree]
+            depth = 3
+            import_file = "members.txt"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.bind, "127.0.0.1:8080".parse().unwrap());
+        assert!(!config.server.cors_allows_any());
+        assert_eq!(config.tree.depth, 3);
+        assert_eq!(config.tree.import_file, Some(PathBuf::from("members.txt")));
+        assert_eq!(config.tree.root_history_size, TreeConfig::default().root_history_size);
+        assert_eq!(config.storage, StorageConfig::default());
+    }
+
+    #[test]
+    fn test_unknown_settings_are_rejected() {
+        let err = Config::from_toml("[tree]\ndepht = 3\n").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(_)));
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::from_toml("[tree]\ndepth = 3\n").unwrap();
+        config
+            .apply_env(env(&[
+                ("ZK_API_BIND", "0.0.0.0:9000"),
+                ("ZK_API_CORS_ORIGINS", "https://a.example, https://b.example"),
+                ("ZK_API_TREE_DEPTH", "10"),
+                ("ZK_API_IMPORT_FILE", "/etc/zk/members.txt"),
+                ("ZK_API_MAX_BATCH_SIZE", "8"),
+                ("ZK_API_ADMIN_TOKEN", "s3cret"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.server.bind.port(), 9000);
+        assert_eq!(config.server.cors_origins, vec!["https://a.example", "https://b.example"]);
+        assert_eq!(config.tree.depth, 10);
+        assert_eq!(config.tree.import_file, Some(PathBuf::from("/etc/zk/members.txt")));
+        assert_eq!(config.proving.max_batch_size, 8);
+        assert_eq!(config.admin.token.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn test_invalid_env_names_the_variable() {
+        let mut config = Config::default();
+        let err = config
+            .apply_env(env(&[("ZK_API_TREE_DEPTH", "three")]))
+            .unwrap_err();
+        assert!(err.to_string().contains("ZK_API_TREE_DEPTH"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_initial_members() {
+        assert!(Config::default().load_initial_members().unwrap().is_empty());
+
+        let path = std::env::temp_dir().join(format!("zk-api-members-{}.txt", std::process::id()));
+        let members = [Fp::from(1u64), Fp::from(2u64), Fp::from(3u64)];
+        let contents: Vec<String> = members.iter().map(fp_to_hex).collect();
+        std::fs::write(&path, format!("# members\n{}\n", contents.join("\n"))).unwrap();
+
+        let mut config = Config::default();
+        config.tree.import_file = Some(path.clone());
+        assert_eq!(config.load_initial_members().unwrap(), members);
+
+        //three members do not fit in a depth-1 tree
+        config.tree.depth = 1;
+        assert!(config.load_initial_members().is_err());
+
+        config.tree.depth = 4;
+        std::fs::write(&path, format!("{}\n{}\n", contents[0], contents[0])).unwrap();
+        let err = config.load_initial_members().unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{}", err);
+
+        std::fs::write(&path, format!("{}\n{}\n", contents[0], fp_to_hex(&Fp::zero()))).unwrap();
+        let err = config.load_initial_members().unwrap_err();
+        assert!(err.to_string().contains("zero commitment"), "{}", err);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_signing_key() {
+        let path = std::env::temp_dir().join(format!("zk-api-key-{}.hex", std::process::id()));
+        std::fs::write(&path, format!("{}\n", hex::encode([5u8; 32]))).unwrap();
+        let mut config = Config::default();
+        config.signing.key_file = path.clone();
+        assert_eq!(config.load_signing_key().unwrap(), SigningKey::from_bytes(&[5u8; 32]));
+
+        std::fs::write(&path, "not a key").unwrap();
+        assert!(matches!(config.load_signing_key(), Err(ConfigError::Invalid(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_signing_key_is_generated_once() {
+        let path = std::env::temp_dir().join(format!("zk-api-new-key-{}.hex", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = Config::default();
+        config.signing.key_file = path.clone();
+
+        let generated = config.load_signing_key().unwrap();
+        assert!(path.exists(), "the generated key must be persisted");
+        assert_eq!(config.load_signing_key().unwrap(), generated, "restarts reuse the key");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_unusable_settings() {
+        let mut config = Config::default();
+        config.tree.root_history_size = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.tree.depth = MAX_DEPTH + 1;
+        assert!(config.validate().is_err());
+        config.tree.depth = 0;
+        assert!(config.validate().is_err());
+        config.tree.depth = 16;
+        assert!(config.validate().is_ok());
+        assert!(Config::default().validate().is_ok());
+
+        let mut config = Config::default();
+        config.signing.key_file = PathBuf::new();
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.admin.token = Some(" ".to_string());
+        assert!(config.validate().is_err());
+    }
+}
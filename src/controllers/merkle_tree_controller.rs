@@ -1,41 +1,260 @@
+use crate::error::ApiError;
+use crate::extract::{ApiJson, ApiPath, ApiQuery};
 use crate::services::merkle_tree_service::{
-    parse_fp_hex, MerkleTreeService, RegisterRequest, TreeResponse,
+    parse_fp_hex, ConsistencyQuery, ConsistencyResponse, ImportResponse, LeavesQuery,
+    LeavesResponse, MerklePathResponse, MerkleTreeService, MultiProofRequest, MultiProofResponse,
+    PublicKeyResponse, SignedTreeHead, TreeInfoResponse, TreeResponse, TreeVisualizationResponse,
 };
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
 use rust_api::prelude::*;
 use std::sync::Arc;
 
+/// One server-sent event, or the error from serializing its data.
+type EventResult = std::result::Result<Event, axum::Error>;
+
 /// Request body for adding a value to the tree.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AddToTreeRequest {
     /// The value to add as a leaf in the tree
     pub value: u64,
 }
 
+/// Wire form of `RegisterRequest`. The commitment stays a string until the handler parses it,
+/// so a bad commitment is a 400 `invalid_commitment` rather than a body decoding error.
+#[derive(Debug, Deserialize)]
+pub struct RegisterBody {
+    pub commitment: String,
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+/// Wire form of `ImportRequest`, parsed by the handler for the same reason as `RegisterBody`.
+#[derive(Debug, Deserialize)]
+pub struct ImportBody {
+    pub commitments: Vec<String>,
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
 /// Registers a new commitment in the Merkle tree.
 /// The client computes `commitment = Poseidon(secret)` locally and sends only the commitment.
 /// The server never learns the secret.
 ///
 /// # Request Body
 /// ```json
-/// { "commitment": "<64-char hex Fp>" }
+/// { "commitment": "<64-char hex Fp>", "idempotent": false }
 /// ```
 ///
 /// # Response
-/// Returns a TreeResponse containing the new root hash, or 400 on invalid commitment.
+/// Returns a TreeResponse containing the leaf index, the new root hash and a signed tree
+/// head. A zero commitment is rejected with 400 `zero_commitment`. A commitment that is
+/// already registered is rejected with 409 `duplicate_commitment`, or with `idempotent` set
+/// returns its existing index and the current root. A commitment that is not a hex field
+/// element is rejected with 400 `invalid_commitment`.
 #[post("/register")]
 pub async fn register(
     State(service): State<Arc<MerkleTreeService>>,
-    Json(request): Json<RegisterRequest>,
-) -> impl IntoResponse {
-    match parse_fp_hex(&request.commitment) {
-        Some(commitment) => {
-            (StatusCode::OK, Json(service.register_commitment(commitment))).into_response()
-        },
-        None => (StatusCode::BAD_REQUEST, "invalid commitment: expected 64-char hex (32 bytes)")
-            .into_response(),
+    ApiJson(request): ApiJson<RegisterBody>,
+) -> std::result::Result<Json<TreeResponse>, ApiError> {
+    let commitment = parse_fp_hex(&request.commitment).map_err(|e| {
+        ApiError::bad_request("invalid_commitment", format!("invalid commitment: {}", e))
+    })?;
+    if request.idempotent {
+        service.register_commitment_idempotent(commitment).map(Json)
+    } else {
+        service.register_commitment(commitment).map(Json)
+    }
+}
+
+/// Appends a set of commitments to the tree, e.g. to load the initial member set.
+/// Only mounted when `admin.token` is configured; requests without
+/// `Authorization: Bearer <token>` are rejected with 401 `unauthorized` first.
+/// Every commitment is parsed before any is appended, so a bad entry imports nothing.
+///
+/// # Request Body
+/// ```json
+/// { "commitments": ["<64-char hex Fp>", "..."], "idempotent": false }
+/// ```
+///
+/// # Response
+/// Returns an ImportResponse (`imported`, `num_members`, `root`), 400 `invalid_commitment`
+/// naming the position of the first bad entry, 400 `zero_commitment`, 409
+/// `duplicate_commitment` if a commitment is already registered or repeated, or 422
+/// `tree_full`. With `idempotent` set, already registered commitments are skipped instead
+/// and `imported` counts only the new ones.
+#[post("/admin/import")]
+pub async fn import_commitments(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiJson(request): ApiJson<ImportBody>,
+) -> std::result::Result<Json<ImportResponse>, ApiError> {
+    let commitments = request
+        .commitments
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            parse_fp_hex(c).map_err(|e| {
+                ApiError::bad_request(
+                    "invalid_commitment",
+                    format!("invalid commitment at position {}: {}", i, e),
+                )
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if request.idempotent {
+        service
+            .import_commitments_idempotent(&commitments)
+            .map(Json)
+    } else {
+        service.import_commitments(&commitments).map(Json)
     }
 }
 
+/// Returns the tree's metadata.
+///
+/// # Response
+/// Returns a TreeInfoResponse (`root`, `depth`, `num_leaves`, `padded_leaves`, `capacity`).
+#[get("/tree")]
+pub async fn get_tree(
+    State(service): State<Arc<MerkleTreeService>>,
+) -> std::result::Result<Json<TreeInfoResponse>, ApiError> {
+    service.tree_info().map(Json)
+}
+
+/// Lists registered leaves in tree order, for mirroring the membership set.
+/// Query parameters: `offset` (default 0) and `limit` (default and maximum 1000).
+///
+/// # Response
+/// Returns a LeavesResponse (`root`, `offset`, `total`, hex `leaves`).
+#[get("/tree/leaves")]
+pub async fn get_tree_leaves(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiQuery(query): ApiQuery<LeavesQuery>,
+) -> std::result::Result<Json<LeavesResponse>, ApiError> {
+    service.leaves(&query).map(Json)
+}
+
+/// Returns the current tree head signed with the server's key, a checkpoint clients can show
+/// to third parties.
+///
+/// # Response
+/// Returns a SignedTreeHead (`size`, `root`, `timestamp`, `signature`).
+#[get("/tree/head")]
+pub async fn get_tree_head(
+    State(service): State<Arc<MerkleTreeService>>,
+) -> std::result::Result<Json<SignedTreeHead>, ApiError> {
+    service.signed_tree_head().map(Json)
+}
+
+/// Returns the public key tree heads are signed with.
+/// Clients should pin it out of band rather than trust it on every request.
+///
+/// # Response
+/// Returns a PublicKeyResponse (`algorithm`, hex `public_key`).
+#[get("/tree/signing-key")]
+pub async fn get_signing_key(
+    State(service): State<Arc<MerkleTreeService>>,
+) -> Json<PublicKeyResponse> {
+    Json(service.public_key())
+}
+
+/// Proves that the tree of `from` leaves is a prefix of the tree of `to` leaves, so auditors
+/// can check that no member was rewritten or removed between two roots they recorded.
+/// Query parameters: `from` and `to` (default: the current size).
+///
+/// # Response
+/// Returns a ConsistencyResponse (`from`, `to`, `from_root`, `to_root`, `leaf`, `siblings`)
+/// or 400 `invalid_range`.
+#[get("/tree/consistency")]
+pub async fn get_tree_consistency(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiQuery(query): ApiQuery<ConsistencyQuery>,
+) -> std::result::Result<Json<ConsistencyResponse>, ApiError> {
+    service.consistency_proof(&query).map(Json)
+}
+
+/// Query parameters for the tree event feed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FeedQuery {
+    /// Index of the first leaf to stream; defaults to the next leaf to be inserted
+    pub from: Option<usize>,
+}
+
+/// Streams leaf insertions as server-sent events.
+/// Each `leaf` event has the leaf index as its id and a LeafEvent (`index`, `commitment`,
+/// `root`) as JSON data. Leaves from `from` onwards that are already in the tree are sent
+/// first. Without `from`, a reconnecting EventSource resumes after its `Last-Event-ID`.
+///
+/// # Response
+/// An event stream. A client that falls too far behind receives one `lagged` event with
+/// `{ "resume_from": <index> }` and the stream ends; it should reconnect from that index.
+#[get("/tree/events")]
+pub async fn get_tree_events(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiQuery(query): ApiQuery<FeedQuery>,
+    headers: HeaderMap,
+) -> std::result::Result<Sse<impl Stream<Item = EventResult>>, ApiError> {
+    let last_seen = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<usize>().ok());
+    let from = match (query.from, last_seen) {
+        (Some(from), _) => from,
+        (None, Some(last)) => last.saturating_add(1),
+        (None, None) => service.tree_info()?.num_leaves,
+    };
+
+    let subscription = service.subscribe(from)?;
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = match subscription.next().await? {
+            Ok(leaf) => Event::default()
+                .event("leaf")
+                .id(leaf.index.to_string())
+                .json_data(&leaf),
+            Err(lagged) => Event::default().event("lagged").json_data(lagged),
+        };
+        Some((event, subscription))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Returns the Merkle path of a registered commitment against the current root.
+/// Clients use it to build membership proofs offline.
+///
+/// # Response
+/// Returns a MerklePathResponse (`index`, `leaf`, `root`, hex `siblings`, `directions`),
+/// 400 `invalid_commitment`, or 404 `commitment_not_in_tree`.
+#[get("/tree/path/{commitment}")]
+pub async fn get_tree_path(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiPath(commitment): ApiPath<String>,
+) -> std::result::Result<Json<MerklePathResponse>, ApiError> {
+    let commitment = parse_fp_hex(&commitment).map_err(|e| {
+        ApiError::bad_request("invalid_commitment", format!("invalid commitment: {}", e))
+    })?;
+    service.proof_path(commitment).map(Json)
+}
+
+/// Proves that several registered leaves are in the tree with one compact proof, for
+/// auditors checking many members at once.
+///
+/// # Request Body
+/// ```json
+/// { "indices": [0, 17, 42] }
+/// ```
+///
+/// # Response
+/// Returns a MultiProofResponse (`root`, `depth`, `indices`, `leaves`, `hashes`) or 400
+/// `invalid_indices`.
+#[post("/tree/multiproof")]
+pub async fn post_tree_multiproof(
+    State(service): State<Arc<MerkleTreeService>>,
+    ApiJson(request): ApiJson<MultiProofRequest>,
+) -> std::result::Result<Json<MultiProofResponse>, ApiError> {
+    service.multiproof(&request).map(Json)
+}
+
 /// Adds a new value to the Merkle tree and returns the new root hash.
 /// Uses dependency injection to access the MerkleTreeService.
 ///
@@ -51,10 +270,9 @@ pub async fn register(
 #[post("/tree")]
 pub async fn add_to_tree(
     State(service): State<Arc<MerkleTreeService>>,
-    Json(request): Json<AddToTreeRequest>,
-) -> Json<TreeResponse> {
-    let response = service.add_to_tree(request.value);
-    Json(response)
+    ApiJson(request): ApiJson<AddToTreeRequest>,
+) -> std::result::Result<Json<TreeResponse>, ApiError> {
+    service.add_to_tree(request.value).map(Json)
 }
 
 /// Generates a visualization of the current Merkle tree and returns the image URL.
@@ -63,10 +281,10 @@ pub async fn add_to_tree(
 /// # Response
 /// Returns a TreeVisualizationResponse containing the URL to the generated image.
 /// The image shows the tree structure with nodes and hash values (truncated).
+/// Returns 500 `visualization_failed` if the image cannot be written.
 #[get("/tree/visualize")]
-pub async fn visualize_tree(State(service): State<Arc<MerkleTreeService>>) -> impl IntoResponse {
-    match service.visualize_tree() {
-        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-    }
+pub async fn visualize_tree(
+    State(service): State<Arc<MerkleTreeService>>,
+) -> std::result::Result<Json<TreeVisualizationResponse>, ApiError> {
+    service.visualize_tree().map(Json)
 }
@@ -1,14 +1,48 @@
-use crate::services::zk_service::{ZKProofResponse, ZKService};
+use crate::error::ApiError;
+use crate::extract::{ApiJson, ApiPath, ApiQuery};
+use crate::services::circuit_cost::CostReport;
+use crate::services::circuit_registry::CircuitInfo;
+use crate::services::proof_envelope::ProofEnvelope;
+use crate::services::zk_service::{
+    BatchProofResponse, ZKProofResponse, ZKService, ZKVerifyResponse,
+};
 use rust_api::prelude::*;
 use std::sync::Arc;
 
 /// Request body for the ZK proof endpoint.
 /// The secret is the private value whose Poseidon commitment must be in the tree.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct ZKProofRequest {
     pub secret: u64,
 }
 
+/// Request body for the batch proof endpoint.
+/// Every secret's Poseidon commitment must be in the tree.
+#[derive(Serialize, Deserialize)]
+pub struct BatchProofRequest {
+    pub secrets: Vec<u64>,
+}
+
+/// Query parameters for the verify endpoints.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VerifyQuery {
+    /// Also reject proofs whose root is not a current or recent root of the tree
+    /// (422 `unknown_root`); by default only the proof itself is checked
+    #[serde(default)]
+    pub require_known_root: bool,
+}
+
+/// Runs key generation or proving on the blocking thread pool, so a long proof does not
+/// stall the async worker threads serving other requests.
+/// Fails with 500 `proving_task_failed` if the task panics.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> std::result::Result<T, ApiError> + Send + 'static,
+) -> std::result::Result<T, ApiError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::internal("proving_task_failed", e.to_string()))?
+}
+
 /// Proves knowledge of a secret whose Poseidon commitment is in the Merkle tree.
 /// The secret is used as a private ZK witness and is never stored or logged.
 ///
@@ -19,10 +53,169 @@ pub struct ZKProofRequest {
 ///
 /// # Response
 /// Returns `{ "proof": true }` if Poseidon(secret) is in the tree and the ZK circuit verifies.
+/// Otherwise returns an error body `{ "code", "message", "details" }` with:
+/// - 404 `commitment_not_in_tree`
+/// - 422 `depth_unsupported` (details: `tree_depth` and `circuit_depth`)
+/// - 500 `synthesis` or `verification_failed` (details: the failing constraints)
 #[post("/zk")]
 pub async fn post_zk(
     State(service): State<Arc<ZKService>>,
-    Json(request): Json<ZKProofRequest>,
-) -> Json<ZKProofResponse> {
-    Json(service.zk_proof(request.secret))
+    ApiJson(request): ApiJson<ZKProofRequest>,
+) -> std::result::Result<Json<ZKProofResponse>, ApiError> {
+    run_blocking(move || service.zk_proof(request.secret))
+        .await
+        .map(Json)
+}
+
+/// Creates a real membership proof for a secret and returns it as a versioned ProofEnvelope.
+/// The secret is used as a private ZK witness and is never stored or logged.
+///
+/// # Request Body
+/// ```json
+/// { "secret": 42 }
+/// ```
+///
+/// # Response
+/// Returns a ProofEnvelope (format version, circuit id, depth, k, public inputs, hex proof),
+/// or an error body: 404 `commitment_not_in_tree`, or 422 `depth_unsupported`.
+#[post("/zk/prove")]
+pub async fn post_zk_prove(
+    State(service): State<Arc<ZKService>>,
+    ApiJson(request): ApiJson<ZKProofRequest>,
+) -> std::result::Result<Json<ProofEnvelope>, ApiError> {
+    run_blocking(move || service.prove(request.secret))
+        .await
+        .map(Json)
+}
+
+/// Verifies a ProofEnvelope returned by `/zk/prove`.
+///
+/// Query parameter: `require_known_root` (default false) additionally rejects proofs against
+/// a root outside the tree's root history.
+///
+/// # Request Body
+/// The ProofEnvelope exactly as returned by `/zk/prove`. Unknown fields are rejected.
+///
+/// # Response
+/// Returns `{ "valid": true }` if the proof verifies, 400 if the envelope is malformed or
+/// was produced for a different circuit version or parameters, or 422 `unknown_root`.
+#[post("/zk/verify")]
+pub async fn post_zk_verify(
+    State(service): State<Arc<ZKService>>,
+    ApiQuery(query): ApiQuery<VerifyQuery>,
+    ApiJson(envelope): ApiJson<ProofEnvelope>,
+) -> std::result::Result<Json<ZKVerifyResponse>, ApiError> {
+    service
+        .verify(&envelope, query.require_known_root)
+        .map(Json)
+}
+
+/// Lists every circuit that can be proven and verified through `/zk/{circuit_id}/...`.
+///
+/// # Response
+/// Returns an array of `{ "id", "k", "depth", "public_inputs" }` describing each circuit.
+#[get("/zk/circuits")]
+pub async fn get_zk_circuits(State(service): State<Arc<ZKService>>) -> Json<Vec<CircuitInfo>> {
+    Json(service.circuits())
+}
+
+/// Reports circuit costs for capacity planning: minimal k, advice/fixed/instance columns,
+/// selectors, gates, constraint degree and proof size for every served circuit, and for the
+/// membership circuit at each depth up to 20.
+/// The first request runs trial key generations on the blocking thread pool and may take a
+/// few seconds; later requests are served from a cache.
+///
+/// # Response
+/// Returns `{ "registered": [...], "membership_by_depth": [...] }`, or 500 if a circuit
+/// cannot be measured.
+#[get("/zk/circuit-info/cost")]
+pub async fn get_zk_circuit_cost(
+    State(service): State<Arc<ZKService>>,
+) -> std::result::Result<Json<CostReport>, ApiError> {
+    tokio::task::spawn_blocking(move || service.cost_report())
+        .await
+        .map_err(|e| ApiError::internal("cost_measurement_failed", e.to_string()))?
+        .map(Json)
+}
+
+/// Creates a real proof for any registered circuit and returns it as a ProofEnvelope.
+/// The witness is used privately and is never stored or logged.
+///
+/// # Request Body
+/// The circuit's witness, e.g. for `merkle-range-v1`:
+/// ```json
+/// { "secret": 42, "attribute": 30, "min": 18, "max": 65 }
+/// ```
+///
+/// # Response
+/// Returns a ProofEnvelope, or an error body: 404 `unknown_circuit`, 400 `invalid_witness` if
+/// the witness is malformed, or 404/422 if it cannot be proven against the current tree.
+#[post("/zk/{circuit_id}/prove")]
+pub async fn post_zk_circuit_prove(
+    State(service): State<Arc<ZKService>>,
+    ApiPath(circuit_id): ApiPath<String>,
+    ApiJson(witness): ApiJson<serde_json::Value>,
+) -> std::result::Result<Json<ProofEnvelope>, ApiError> {
+    run_blocking(move || service.prove_circuit(&circuit_id, witness))
+        .await
+        .map(Json)
+}
+
+/// Verifies a ProofEnvelope returned by `/zk/{circuit_id}/prove`.
+/// Query parameter: `require_known_root`, as for `/zk/verify`.
+///
+/// # Response
+/// Returns `{ "valid": true }` if the proof verifies, 404 if the circuit is unknown, 400 if
+/// the envelope is malformed or was produced for a different circuit version or parameters,
+/// or 422 `unknown_root`.
+#[post("/zk/{circuit_id}/verify")]
+pub async fn post_zk_circuit_verify(
+    State(service): State<Arc<ZKService>>,
+    ApiPath(circuit_id): ApiPath<String>,
+    ApiQuery(query): ApiQuery<VerifyQuery>,
+    ApiJson(envelope): ApiJson<ProofEnvelope>,
+) -> std::result::Result<Json<ZKVerifyResponse>, ApiError> {
+    service
+        .verify_circuit(&circuit_id, &envelope, query.require_known_root)
+        .map(Json)
+}
+
+/// Proves membership for many secrets in a single batch proof.
+/// The secrets are used as private ZK witnesses and are never stored or logged.
+///
+/// # Request Body
+/// ```json
+/// { "secrets": [42, 99, 200] }
+/// ```
+///
+/// # Response
+/// Returns a BatchProofResponse (`root`, `count`, hex `proof`), 400 `empty_batch`,
+/// or 404/422 if any secret is not a provable member (the message names its position).
+#[post("/zk/batch")]
+pub async fn post_zk_batch(
+    State(service): State<Arc<ZKService>>,
+    ApiJson(request): ApiJson<BatchProofRequest>,
+) -> std::result::Result<Json<BatchProofResponse>, ApiError> {
+    run_blocking(move || service.prove_batch(&request.secrets))
+        .await
+        .map(Json)
+}
+
+/// Verifies a batch proof returned by `/zk/batch`.
+/// Query parameter: `require_known_root`, as for `/zk/verify`.
+///
+/// # Request Body
+/// The BatchProofResponse exactly as returned by `/zk/batch`.
+///
+/// # Response
+/// Returns `{ "valid": true }` if the batch proof verifies, or 400 on malformed input.
+#[post("/zk/batch/verify")]
+pub async fn post_zk_batch_verify(
+    State(service): State<Arc<ZKService>>,
+    ApiQuery(query): ApiQuery<VerifyQuery>,
+    ApiJson(request): ApiJson<BatchProofResponse>,
+) -> std::result::Result<Json<ZKVerifyResponse>, ApiError> {
+    service
+        .verify_batch(&request, query.require_known_root)
+        .map(Json)
 }
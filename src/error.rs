@@ -0,0 +1,182 @@
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use rust_api::prelude::*;
+use std::fmt;
+use std::sync::PoisonError;
+
+use crate::services::proof_envelope::EnvelopeError;
+use crate::services::zk_error::ZkProofError;
+
+/// JSON body of every error response.
+///
+/// ```json
+/// { "code": "commitment_not_in_tree", "message": "commitment is not in the tree" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiErrorBody {
+    /// Stable, machine-readable error code in snake_case
+    pub code: String,
+    /// Human-readable description; may change between releases
+    pub message: String,
+    /// Structured context for the error, if any (e.g. failing constraints)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Error type returned by every service and controller.
+/// Converts into an HTTP response with the matching status code and an ApiErrorBody.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    /// Creates an error with the given status, code and message and no details.
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// 400: the request is malformed.
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    /// 404: the requested resource does not exist.
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    /// 500: the server failed to handle a valid request.
+    pub fn internal(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, code, message)
+    }
+
+    /// Returns the JSON body sent to clients.
+    pub fn body(&self) -> ApiErrorBody {
+        ApiErrorBody {
+            code: self.code.to_string(),
+            message: self.message.clone(),
+            details: self.details.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body())).into_response()
+    }
+}
+
+impl From<ZkProofError> for ApiError {
+    fn from(e: ZkProofError) -> Self {
+        let details = match &e {
+            ZkProofError::DepthUnsupported {
+                tree_depth,
+                circuit_depth,
+            } => Some(serde_json::json!({
+                "tree_depth": tree_depth,
+                "circuit_depth": circuit_depth,
+            })),
+            ZkProofError::VerificationFailed { failures } => {
+                Some(serde_json::json!({ "failures": failures }))
+            },
+            _ => None,
+        };
+        Self {
+            status: e.status_code(),
+            code: e.code(),
+            message: e.to_string(),
+            details,
+        }
+    }
+}
+
+impl From<EnvelopeError> for ApiError {
+    fn from(e: EnvelopeError) -> Self {
+        let code = match e {
+            EnvelopeError::UnsupportedVersion(_) => "unsupported_envelope_version",
+            EnvelopeError::CircuitMismatch { .. } => "circuit_mismatch",
+            _ => "invalid_envelope",
+        };
+        Self::bad_request(code, e.to_string())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match rejection {
+            JsonRejection::JsonSyntaxError(_) => "malformed_json",
+            JsonRejection::MissingJsonContentType(_) => "unsupported_media_type",
+            _ => "invalid_body",
+        };
+        Self::new(rejection.status(), code, rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid_path", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+impl<T> From<PoisonError<T>> for ApiError {
+    fn from(_: PoisonError<T>) -> Self {
+        //a panic while holding the lock may have left the state half-updated
+        Self::internal("state_unavailable", "shared state is unavailable after a failed update")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_omits_empty_details() {
+        let body = ApiError::bad_request("invalid_commitment", "bad hex").body();
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json, serde_json::json!({ "code": "invalid_commitment", "message": "bad hex" }));
+    }
+
+    #[test]
+    fn test_zk_proof_error_conversion() {
+        let err = ApiError::from(ZkProofError::DepthUnsupported {
+            tree_depth: 4,
+            circuit_depth: 3,
+        });
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.code, "depth_unsupported");
+        assert_eq!(err.details, Some(serde_json::json!({ "tree_depth": 4, "circuit_depth": 3 })));
+
+        let err = ApiError::from(ZkProofError::CommitmentNotInTree);
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        assert_eq!(err.code, "commitment_not_in_tree");
+    }
+
+    #[test]
+    fn test_envelope_error_conversion() {
+        let err = ApiError::from(EnvelopeError::EmptyProof);
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.code, "invalid_envelope");
+    }
+}
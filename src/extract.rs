@@ -0,0 +1,119 @@
+//! Request extractors whose rejections are ApiErrors.
+//!
+//! axum's `Json`, `Path` and `Query` reject malformed requests with a plain-text body. These
+//! wrappers extract the same values but reject with the `{ "code", "message" }` JSON body
+//! every other error uses, so clients only have to decode one error shape.
+
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::request::Parts;
+use rust_api::prelude::*;
+
+use crate::error::ApiError;
+
+/// `Json` body extractor that rejects with an ApiError.
+pub struct ApiJson<T>(pub T);
+
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = axum::extract::rejection::JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> std::result::Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+/// `Path` parameter extractor that rejects with an ApiError.
+pub struct ApiPath<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ApiPath<T>
+where
+    Path<T>: FromRequestParts<S, Rejection = axum::extract::rejection::PathRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiPath(value))
+    }
+}
+
+/// `Query` string extractor that rejects with an ApiError.
+pub struct ApiQuery<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ApiQuery<T>
+where
+    Query<T>: FromRequestParts<S, Rejection = axum::extract::rejection::QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use futures_util::FutureExt;
+
+    #[derive(Debug, Deserialize)]
+    struct Secret {
+        #[allow(dead_code)]
+        secret: u64,
+    }
+
+    fn json_request(body: &'static str) -> Request {
+        Request::builder()
+            .method("POST")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn extract(req: Request) -> std::result::Result<ApiJson<Secret>, ApiError> {
+        ApiJson::<Secret>::from_request(req, &())
+            .now_or_never()
+            .expect("the body is already in memory")
+    }
+
+    #[test]
+    fn test_malformed_body_is_a_json_error() {
+        let err = extract(json_request("{ \"secret\": ")).err().unwrap();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.code, "malformed_json");
+
+        let response = err.into_response();
+        assert_eq!(response.headers().get("content-type").unwrap(), "application/json");
+    }
+
+    #[test]
+    fn test_mistyped_body_is_a_json_error() {
+        let err = extract(json_request("{ \"secret\": \"42\" }"))
+            .err()
+            .unwrap();
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.code, "invalid_body");
+
+        let req = Request::builder()
+            .method("POST")
+            .body(Body::from("{ \"secret\": 42 }"))
+            .unwrap();
+        assert_eq!(extract(req).err().unwrap().code, "unsupported_media_type");
+
+        assert!(extract(json_request("{ \"secret\": 42 }")).is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+//! Zero-knowledge membership proofs over Poseidon Merkle trees, built with Halo2.
+//!
+//! The crate can be used as a library (tree, commitment, circuit and proving APIs) or run as
+//! an HTTP server (`src/main.rs`), which is a thin layer over the same services.
+//!
+//! # Example
+//! ```
+//! use zk_rust_api::{poseidon_commit, MerkleTree};
+//!
+//! //members register Poseidon(secret); the secret itself never leaves the client
+//! let commitments: Vec<_> = [42u64, 99, 7, 13].iter().map(|&s| poseidon_commit(s)).collect();
+//! let tree = MerkleTree::new(commitments);
+//!
+//! let proof = tree.generate_proof(0).unwrap();
+//! assert_eq!(proof.root, tree.root());
+//! assert_eq!(proof.siblings.len(), tree.depth());
+//! ```
+//!
+//! See `examples/basic_usage.rs` for generating and verifying a real Halo2 proof.
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod config;
+pub mod controllers;
+pub mod error;
+pub mod extract;
+pub mod server;
+pub mod services;
+
+pub use config::Config;
+pub use error::{ApiError, ApiErrorBody};
+pub use halo2_proofs::pasta::Fp;
+pub use services::batch::{prove_batch, verify_batch, verify_batches, BatchProof};
+pub use services::circuit_registry::{CircuitDefinition, CircuitInfo, CircuitRegistry};
+pub use services::fp_hex::{FpHex, FpHexError};
+pub use services::merkle_circuit::{
+    merkle_path_witness, MembershipWitness, MerkleCircuit, CIRCUIT_ID, DEPTH, MAX_DEPTH,
+};
+pub use services::merkle_range_circuit::{
+    merkle_range_k, MerkleRangeCircuit, RangeWitness, MERKLE_RANGE_CIRCUIT_ID, MERKLE_RANGE_K,
+};
+pub use services::merkle_tree::{ConsistencyProof, LeafValue, MerkleProof, MerkleTree, MultiProof};
+pub use services::merkle_tree_service::{
+    fp_to_hex, parse_fp_hex, poseidon_commit, poseidon_commit_attribute,
+};
+pub use services::proof_envelope::{EnvelopeError, ProofEnvelope, ENVELOPE_VERSION};
+pub use services::prover::{membership_k, CircuitKeys, CircuitVerifier, MerkleKeys, K};
+pub use services::quaternary_circuit::{
+    QuaternaryMerkleCircuit, QUATERNARY_CIRCUIT_ID, QUATERNARY_DEPTH,
+};
+pub use services::quaternary_tree::{QuaternaryProof, QuaternaryTree};
+pub use services::range_check::{RangeCircuit, ValueRangeWitness, RANGE_CIRCUIT_ID, RANGE_K};
+pub use services::tree_hasher::{PoseidonHasher, Sha256Hasher, TreeHasher};
+pub use services::tree_head::SignedTreeHead;
+pub use services::zk_error::ZkProofError;
@@ -1,97 +1,62 @@
 use rust_api::prelude::*;
-use std::sync::Arc;
-use tower_http::services::ServeDir;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod controllers;
-mod services;
-
-// Import controller handlers and their macro-generated path constants
-use crate::services::health_service::HealthService;
-use crate::services::merkle_tree_service::MerkleTreeService;
-use crate::services::zk_service::ZKService;
-use controllers::health_controller::{__health_check_route, health_check};
-use controllers::merkle_tree_controller::{
-    __add_to_tree_route, __register_route, __visualize_tree_route, add_to_tree, register,
-    visualize_tree,
-};
-use controllers::zk_controller::{__post_zk_route, post_zk};
-
-/// Root endpoint handler that returns a welcome message.
-#[get("/")]
-async fn root() -> &'static str {
-    "Welcome to RustAPI!"
-}
+use zk_rust_api::config::Config;
+use zk_rust_api::server::{build_router, setup_container_with};
 
 /// Main entry point for the rust_api REST API server.
 /// Demonstrates FastAPI-style routing with decorator macros and dependency injection.
 #[tokio::main]
 async fn main() {
-    initialize_tracing();
-    let container = setup_container();
-    let app = build_router(&container);
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("failed to load configuration: {}", e);
+            std::process::exit(1);
+        },
+    };
+
+    initialize_tracing(&config.server.log_filter);
+    if let Err(e) = std::fs::create_dir_all(&config.storage.path) {
+        eprintln!("failed to create {}: {}", config.storage.path.display(), e);
+        std::process::exit(1);
+    }
+
+    let members = match config.load_initial_members() {
+        Ok(members) => members,
+        Err(e) => {
+            eprintln!("failed to load initial members: {}", e);
+            std::process::exit(1);
+        },
+    };
+    let signing_key = match config.load_signing_key() {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("failed to load the tree head signing key: {}", e);
+            std::process::exit(1);
+        },
+    };
+
+    let container = setup_container_with(&config, members, signing_key);
+    let app = build_router(&container, &config);
 
     // Start the server using RustAPI framework
     RustAPI::new(app)
-        .port(3000) // Configurable port (default is 3000)
+        .host(config.server.bind.ip().to_string())
+        .port(config.server.bind.port())
         .serve()
         .await
         .expect("Failed to start server");
 }
 
-/// Initializes the tracing subscriber for logging
-fn initialize_tracing() {
+/// Initializes the tracing subscriber for logging.
+/// `RUST_LOG` takes precedence over the configured filter.
+fn initialize_tracing(default_filter: &str) {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "rust_api=debug,tower_http=debug".into()),
+                .unwrap_or_else(|_| default_filter.into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
 }
-
-/// Sets up the DI container with all services
-fn setup_container() -> Container {
-    let mut container = Container::new();
-
-    // Register services
-    container.register_factory(HealthService::new);
-    container.register_factory(MerkleTreeService::new);
-
-    container
-}
-
-/// Builds the application router using FastAPI-style route decorators
-fn build_router(container: &Container) -> Router {
-    // Resolve services from container
-    let health_service = container.resolve::<HealthService>().unwrap();
-    let tree_service = container.resolve::<MerkleTreeService>().unwrap();
-
-    // ZKService depends on MerkleTreeService, so we create it manually
-    let zk_service = Arc::new(ZKService::new(tree_service.clone()));
-
-    // Build separate routers for each service with their own state
-    let health_router = Router::new()
-        .route(__health_check_route, routing::get(health_check))
-        .with_state(health_service);
-
-    let zk_router = Router::new()
-        .route(__post_zk_route, routing::post(post_zk))
-        .with_state(zk_service);
-
-    let tree_router = Router::new()
-        .route(__register_route, routing::post(register))
-        .route(__add_to_tree_route, routing::post(add_to_tree))
-        .route(__visualize_tree_route, routing::get(visualize_tree))
-        .with_state(tree_service);
-
-    // Merge all routers together
-    router::build()
-        .route(__root_route, routing::get(root))
-        .merge(health_router)
-        .merge(zk_router)
-        .merge(tree_router)
-        .nest_service("/static", ServeDir::new("static"))
-        .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::permissive())
-}
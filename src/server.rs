@@ -0,0 +1,233 @@
+use axum::extract::Request;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use ed25519_dalek::SigningKey;
+use halo2_proofs::pasta::Fp;
+use rust_api::prelude::*;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::services::ServeDir;
+
+use crate::config::{Config, ConfigError, ServerConfig};
+use crate::error::ApiError;
+
+// Import controller handlers and their macro-generated path constants
+use crate::controllers::health_controller::{__health_check_route, health_check};
+use crate::controllers::merkle_tree_controller::{
+    __add_to_tree_route, __get_signing_key_route, __get_tree_consistency_route,
+    __get_tree_events_route, __get_tree_head_route, __get_tree_leaves_route, __get_tree_path_route,
+    __get_tree_route, __import_commitments_route, __post_tree_multiproof_route, __register_route,
+    __visualize_tree_route, add_to_tree, get_signing_key, get_tree, get_tree_consistency,
+    get_tree_events, get_tree_head, get_tree_leaves, get_tree_path, import_commitments,
+    post_tree_multiproof, register, visualize_tree,
+};
+use crate::controllers::zk_controller::{
+    __get_zk_circuit_cost_route, __get_zk_circuits_route, __post_zk_batch_route,
+    __post_zk_batch_verify_route, __post_zk_circuit_prove_route, __post_zk_circuit_verify_route,
+    __post_zk_prove_route, __post_zk_route, __post_zk_verify_route, get_zk_circuit_cost,
+    get_zk_circuits, post_zk, post_zk_batch, post_zk_batch_verify, post_zk_circuit_prove,
+    post_zk_circuit_verify, post_zk_prove, post_zk_verify,
+};
+use crate::services::health_service::HealthService;
+use crate::services::merkle_tree_service::MerkleTreeService;
+use crate::services::zk_service::ZKService;
+
+/// Root endpoint handler that returns a welcome message.
+#[get("/")]
+async fn root() -> &'static str {
+    "Welcome to RustAPI!"
+}
+
+/// Sets up the DI container with all services.
+/// Fails if `tree.import_file` cannot be loaded or `signing.key_file` can neither be loaded
+/// nor created.
+pub fn setup_container(config: &Config) -> std::result::Result<Container, ConfigError> {
+    let members = config.load_initial_members()?;
+    let signing_key = config.load_signing_key()?;
+    Ok(setup_container_with(config, members, signing_key))
+}
+
+/// Sets up the DI container like `setup_container`, with initial members and a signing key
+/// the caller has already loaded, so it can report each failure on its own.
+pub fn setup_container_with(
+    config: &Config,
+    members: Vec<Fp>,
+    signing_key: SigningKey,
+) -> Container {
+    let mut container = Container::new();
+
+    // Register services
+    container.register_factory(HealthService::new);
+    let tree_config = config.clone();
+    container.register_factory(move || {
+        MerkleTreeService::with_members(&tree_config, members.clone())
+            .with_signing_key(signing_key.clone())
+    });
+
+    container
+}
+
+/// Builds the application router using FastAPI-style route decorators
+pub fn build_router(container: &Container, config: &Config) -> Router {
+    // Resolve services from container
+    let health_service = container.resolve::<HealthService>().unwrap();
+    let tree_service = container.resolve::<MerkleTreeService>().unwrap();
+
+    // ZKService depends on MerkleTreeService, so we create it manually
+    let zk_service = Arc::new(ZKService::with_config(tree_service.clone(), &config.proving));
+
+    // Build separate routers for each service with their own state
+    let health_router = Router::new()
+        .route(__health_check_route, routing::get(health_check))
+        .with_state(health_service);
+
+    let zk_router = Router::new()
+        .route(__post_zk_route, routing::post(post_zk))
+        .route(__post_zk_prove_route, routing::post(post_zk_prove))
+        .route(__post_zk_verify_route, routing::post(post_zk_verify))
+        .route(__get_zk_circuits_route, routing::get(get_zk_circuits))
+        .route(__get_zk_circuit_cost_route, routing::get(get_zk_circuit_cost))
+        .route(__post_zk_circuit_prove_route, routing::post(post_zk_circuit_prove))
+        .route(__post_zk_circuit_verify_route, routing::post(post_zk_circuit_verify))
+        .route(__post_zk_batch_route, routing::post(post_zk_batch))
+        .route(__post_zk_batch_verify_route, routing::post(post_zk_batch_verify))
+        .with_state(zk_service);
+
+    let tree_router = Router::new()
+        .route(__register_route, routing::post(register))
+        .route(__add_to_tree_route, routing::post(add_to_tree))
+        .route(__get_tree_route, routing::get(get_tree))
+        .route(__get_tree_leaves_route, routing::get(get_tree_leaves))
+        .route(__get_tree_events_route, routing::get(get_tree_events))
+        .route(__get_tree_consistency_route, routing::get(get_tree_consistency))
+        .route(__get_tree_head_route, routing::get(get_tree_head))
+        .route(__post_tree_multiproof_route, routing::post(post_tree_multiproof))
+        .route(__get_signing_key_route, routing::get(get_signing_key))
+        .route(__visualize_tree_route, routing::get(visualize_tree))
+        .route(__get_tree_path_route, routing::get(get_tree_path))
+        .with_state(tree_service.clone());
+
+    // Admin endpoints are only mounted when a token is configured to guard them
+    let admin_router = match &config.admin.token {
+        Some(token) => Router::new()
+            .route(__import_commitments_route, routing::post(import_commitments))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::<str>::from(token.as_str()),
+                require_admin_token,
+            ))
+            .with_state(tree_service),
+        None => Router::new(),
+    };
+
+    // Merge all routers together
+    router::build()
+        .route(__root_route, routing::get(root))
+        .merge(health_router)
+        .merge(zk_router)
+        .merge(tree_router)
+        .merge(admin_router)
+        .nest_service("/static", ServeDir::new(&config.storage.path))
+        .layer(TraceLayer::new_for_http())
+        .layer(cors_layer(&config.server))
+}
+
+/// Rejects requests without `Authorization: Bearer <admin.token>` with 401 `unauthorized`.
+async fn require_admin_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> std::result::Result<Response, ApiError> {
+    let presented = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(presented) if constant_time_eq(presented.as_bytes(), token.as_bytes()) => {
+            Ok(next.run(request).await)
+        },
+        _ => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "admin endpoints require Authorization: Bearer <admin token>",
+        )),
+    }
+}
+
+/// Compares two byte strings in time that depends only on their lengths, so response times
+/// do not reveal how much of the token a guess got right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Allows any origin if `cors_origins` contains `*`, otherwise only the listed origins.
+fn cors_layer(config: &ServerConfig) -> CorsLayer {
+    if config.cors_allows_any() {
+        return CorsLayer::permissive();
+    }
+
+    let origins = config.cors_origins.clone();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origins.iter().any(|o| o.as_bytes() == origin.as_bytes())
+        }))
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    /// Builds the router with `admin.token` set to `token`.
+    fn app(name: &str, token: Option<&str>) -> Router {
+        let dir =
+            std::env::temp_dir().join(format!("zk-api-server-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.storage.path = dir.clone();
+        config.signing.key_file = dir.join("tree-head.key");
+        config.admin.token = token.map(str::to_string);
+        build_router(&setup_container(&config).unwrap(), &config)
+    }
+
+    fn import(authorization: Option<&str>) -> Request {
+        let commitment = crate::fp_to_hex(&crate::poseidon_commit(42));
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/admin/import")
+            .header("content-type", "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header("authorization", authorization);
+        }
+        request
+            .body(Body::from(format!("{{ \"commitments\": [\"{}\"] }}", commitment)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_import_requires_the_token() {
+        let app = app("admin", Some("s3cret"));
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cret")] {
+            let response = app.clone().oneshot(import(authorization)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{:?}", authorization);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: crate::ApiErrorBody = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body.code, "unauthorized");
+        }
+
+        let response = app.oneshot(import(Some("Bearer s3cret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_import_is_not_mounted_without_a_token() {
+        let response = app("no-admin", None).oneshot(import(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
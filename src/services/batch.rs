@@ -0,0 +1,175 @@
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::{self, BatchVerifier},
+};
+
+use super::merkle_circuit::MerkleCircuit;
+use super::prover::MerkleKeys;
+
+/// A single proof that `count` membership circuits all verify against the same root.
+///
+/// This is batched proving, not recursive aggregation or accumulation: Halo2 proves several
+/// instances of one circuit in a single transcript, so the instances share the permutation,
+/// lookup, vanishing and multiopen arguments, but the proof still grows with `count` (one set
+/// of advice commitments per instance) and verifying it still costs a full IPA check.
+/// Aggregation into a constant-size proof needs recursion, which halo2_proofs 0.3 does not
+/// provide; see "Proof aggregation" in docs/ARCHITECTURE.md.
+#[derive(Clone, Debug)]
+pub struct BatchProof {
+    /// The Merkle root every membership proof in the batch was made against
+    pub root: Fp,
+    /// Number of membership proofs in the batch
+    pub count: usize,
+    /// Serialized Halo2 proof bytes
+    pub proof: Vec<u8>,
+}
+
+impl BatchProof {
+    /// Returns the public inputs of the batch proof: the root, once per instance.
+    fn instances(&self) -> Vec<Vec<Fp>> {
+        vec![vec![self.root]; self.count]
+    }
+}
+
+/// Proves many MerkleCircuit membership witnesses in a single proof.
+///
+/// # Arguments
+/// * `keys` - Proving keys for MerkleCircuit
+/// * `circuits` - One fully-witnessed circuit per member being proven
+/// * `root` - The Merkle root all circuits prove membership against
+///
+/// # Returns
+/// A BatchProof covering every circuit, or an error if the batch is empty or proving fails
+pub fn prove_batch(
+    keys: &MerkleKeys,
+    circuits: &[MerkleCircuit],
+    root: Fp,
+) -> std::result::Result<BatchProof, plonk::Error> {
+    if circuits.is_empty() {
+        return Err(plonk::Error::InvalidInstances);
+    }
+
+    let instances = vec![vec![root]; circuits.len()];
+    let proof = keys.prove(circuits, &instances)?;
+
+    Ok(BatchProof {
+        root,
+        count: circuits.len(),
+        proof,
+    })
+}
+
+/// Verifies a single batch proof.
+///
+/// # Returns
+/// true if every membership proof in the batch is valid against `batch.root`
+pub fn verify_batch(keys: &MerkleKeys, batch: &BatchProof) -> bool {
+    batch.count > 0 && keys.verify(&batch.proof, &batch.instances())
+}
+
+/// Batch-verifies many batch proofs (e.g. one per epoch) with Halo2's BatchVerifier.
+///
+/// Each proof's final IPA check is deferred into a multi-scalar multiplication and the MSMs
+/// are combined with random coefficients and checked together. This saves work at
+/// verification time only; nothing is accumulated across calls.
+///
+/// # Returns
+/// true only if every proof in `batches` is valid
+pub fn verify_batches(keys: &MerkleKeys, batches: &[BatchProof]) -> bool {
+    if batches.iter().any(|b| b.count == 0) {
+        return false;
+    }
+
+    let mut verifier = BatchVerifier::<EqAffine>::new();
+    for batch in batches {
+        let instances = batch
+            .instances()
+            .into_iter()
+            .map(|column| vec![column])
+            .collect();
+        verifier.add_proof(instances, batch.proof.clone());
+    }
+    verifier.finalize(keys.params(), keys.vk())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::merkle_tree::MerkleTree;
+    use crate::services::merkle_tree_service::poseidon_commit;
+    use crate::services::prover::K;
+    use halo2_proofs::circuit::Value;
+
+    /// Builds an 8-leaf tree of commitments and a fully-witnessed circuit for each given index.
+    fn setup_tree(indices: &[usize]) -> (Fp, Vec<MerkleCircuit>) {
+        let secrets: Vec<u64> = (1..=8).collect();
+        let tree = MerkleTree::new(secrets.iter().map(|&s| poseidon_commit(s)).collect());
+
+        let circuits = indices
+            .iter()
+            .map(|&idx| {
+                let proof = tree.generate_proof(idx).unwrap();
+                MerkleCircuit {
+                    secret: Value::known(Fp::from(secrets[idx])),
+                    siblings: proof.siblings.into_iter().map(Value::known).collect(),
+                    directions: proof.directions.into_iter().map(Value::known).collect(),
+                }
+            })
+            .collect();
+
+        (tree.root(), circuits)
+    }
+
+    #[test]
+    fn test_prove_and_verify_batch() {
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let (root, circuits) = setup_tree(&[0, 3, 7]);
+
+        let batch = prove_batch(&keys, &circuits, root).unwrap();
+
+        assert_eq!(batch.count, 3);
+        assert!(verify_batch(&keys, &batch), "batch proof should verify");
+    }
+
+    #[test]
+    fn test_batch_proof_rejects_wrong_root() {
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let (root, circuits) = setup_tree(&[1, 2]);
+
+        let mut batch = prove_batch(&keys, &circuits, root).unwrap();
+        batch.root = Fp::from(12345u64);
+
+        assert!(!verify_batch(&keys, &batch), "proof must not verify for another root");
+    }
+
+    #[test]
+    fn test_batch_proof_rejects_wrong_count() {
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let (root, circuits) = setup_tree(&[1, 2]);
+
+        let mut batch = prove_batch(&keys, &circuits, root).unwrap();
+        batch.count = 1;
+
+        assert!(!verify_batch(&keys, &batch), "count is bound to the proof");
+    }
+
+    #[test]
+    fn test_prove_empty_batch_is_error() {
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        assert!(prove_batch(&keys, &[], Fp::zero()).is_err());
+    }
+
+    #[test]
+    fn test_verify_batches() {
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let (root, circuits) = setup_tree(&[0, 1, 2, 3]);
+
+        let epoch1 = prove_batch(&keys, &circuits[..2], root).unwrap();
+        let epoch2 = prove_batch(&keys, &circuits[2..], root).unwrap();
+        assert!(verify_batches(&keys, &[epoch1.clone(), epoch2.clone()]));
+
+        let mut tampered = epoch2;
+        tampered.root = Fp::from(1u64);
+        assert!(!verify_batches(&keys, &[epoch1, tampered]));
+    }
+}
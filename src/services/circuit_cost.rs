@@ -0,0 +1,238 @@
+use halo2_proofs::{
+    dev::CircuitCost,
+    pasta::{Eq, EqAffine, Fp},
+    plonk::{self, keygen_vk, Circuit, ConstraintSystem},
+    poly::commitment::Params,
+};
+use rust_api::prelude::*;
+
+use super::merkle_circuit::{MerkleCircuit, CIRCUIT_ID, MAX_DEPTH};
+
+/// Deepest tree covered by the per-depth report, the deepest the circuits can be built for.
+pub const MAX_REPORT_DEPTH: u32 = MAX_DEPTH as u32;
+
+/// Largest k tried when searching for the minimal k of a circuit.
+const MAX_K: u32 = 20;
+
+/// Size and shape of a circuit at its minimal k.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitCostReport {
+    pub circuit_id: String,
+    /// Merkle depth the circuit was measured at
+    pub depth: u32,
+    /// Smallest k for which keygen succeeds (the circuit fits in 2^k rows)
+    pub min_k: u32,
+    /// Total rows at min_k, i.e. 2^min_k
+    pub rows: usize,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    /// Selectors, which are compressed into additional fixed columns at keygen
+    pub selectors: usize,
+    /// Custom gates, each made of one or more constraints
+    pub gates: usize,
+    /// Maximum degree of the constraint system (gates, lookups and permutation)
+    pub max_degree: usize,
+    /// Estimated size in bytes of a single proof at min_k
+    pub proof_size_bytes: usize,
+}
+
+/// Cost of the membership circuit at each depth, alongside the cost of every served circuit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostReport {
+    /// Every registered circuit, at the depth and parameters it is served with
+    pub registered: Vec<CircuitCostReport>,
+    /// MerkleCircuit's layout measured at depths 1..=MAX_REPORT_DEPTH
+    pub membership_by_depth: Vec<CircuitCostReport>,
+}
+
+/// Column, selector and gate counts of a configured constraint system.
+struct Shape {
+    advice_columns: usize,
+    fixed_columns: usize,
+    instance_columns: usize,
+    selectors: usize,
+    gates: usize,
+}
+
+impl Shape {
+    /// Reads the counts from the constraint system's Debug output.
+    /// halo2_proofs 0.3 keeps these fields crate-private and its derived Debug is the only
+    /// public view of them; `test_shape_counts_a_known_circuit` fails if that output changes.
+    fn of(cs: &ConstraintSystem<Fp>) -> Self {
+        let debug = format!("{:?}", cs);
+        let count = |field: &str| {
+            debug
+                .split(&format!("{}: ", field))
+                .nth(1)
+                .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+                .and_then(|digits| digits.parse().ok())
+                .unwrap_or_else(|| panic!("ConstraintSystem Debug output has no {}", field))
+        };
+        Shape {
+            advice_columns: count("num_advice_columns"),
+            fixed_columns: count("num_fixed_columns"),
+            instance_columns: count("num_instance_columns"),
+            selectors: count("num_selectors"),
+            gates: debug.matches("Gate { name: ").count(),
+        }
+    }
+}
+
+/// Finds the smallest k in `start_k..=MAX_K` for which the circuit fits.
+/// Keygen fails with NotEnoughRowsAvailable when the layout (including blinding rows and
+/// lookup tables) does not fit in 2^k rows.
+fn min_k<C: Circuit<Fp>>(circuit: &C, start_k: u32) -> std::result::Result<u32, plonk::Error> {
+    for k in start_k..=MAX_K {
+        let params: Params<EqAffine> = Params::new(k);
+        match keygen_vk(&params, circuit) {
+            Ok(_) => return Ok(k),
+            Err(plonk::Error::NotEnoughRowsAvailable { .. }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(plonk::Error::NotEnoughRowsAvailable { current_k: MAX_K })
+}
+
+/// Measures a circuit: its minimal k, column and gate counts, degree and proof size.
+///
+/// # Arguments
+/// * `circuit_id` - Identifier recorded in the report
+/// * `depth` - Merkle depth the circuit is instantiated with
+/// * `circuit` - The circuit to measure; witnesses may be unknown
+/// * `start_k` - Smallest k to try; pass a known lower bound to skip trial keygens
+pub fn measure<C: Circuit<Fp>>(
+    circuit_id: &str,
+    depth: u32,
+    circuit: &C,
+    start_k: u32,
+) -> std::result::Result<CircuitCostReport, plonk::Error> {
+    let mut cs = ConstraintSystem::<Fp>::default();
+    C::configure(&mut cs);
+    let shape = Shape::of(&cs);
+
+    let min_k = min_k(circuit, start_k)?;
+    let cost = CircuitCost::<Eq, C>::measure(min_k, circuit);
+
+    Ok(CircuitCostReport {
+        circuit_id: circuit_id.to_string(),
+        depth,
+        min_k,
+        rows: 1 << min_k,
+        advice_columns: shape.advice_columns,
+        fixed_columns: shape.fixed_columns,
+        instance_columns: shape.instance_columns,
+        selectors: shape.selectors,
+        gates: shape.gates,
+        max_degree: cs.degree(),
+        proof_size_bytes: cost.proof_size(1).into(),
+    })
+}
+
+/// Measures the membership circuit at every depth in `depths`.
+/// Depths are measured in increasing order so each search starts at the previous minimal k.
+pub fn membership_cost_by_depth(
+    depths: std::ops::RangeInclusive<u32>,
+) -> std::result::Result<Vec<CircuitCostReport>, plonk::Error> {
+    let mut start_k = 1;
+    depths
+        .map(|depth| {
+            let circuit = MerkleCircuit::new(depth as usize);
+            let report = measure(CIRCUIT_ID, depth, &circuit, start_k)?;
+            start_k = report.min_k;
+            Ok(report)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::merkle_range_circuit::{merkle_range_k, MerkleRangeCircuit};
+    use super::super::prover::membership_k;
+    use super::*;
+    use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+    use halo2_proofs::poly::Rotation;
+
+    /// Two advice, one fixed and one instance column, one selector and two gates.
+    #[derive(Clone, Default)]
+    struct KnownShape;
+
+    impl Circuit<Fp> for KnownShape {
+        type Config = ();
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let [a, b] = [meta.advice_column(), meta.advice_column()];
+            meta.fixed_column();
+            meta.instance_column();
+            let s = meta.selector();
+            meta.create_gate("equal", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![s * (a - b)]
+            });
+            meta.create_gate("square", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::next());
+                vec![s * (a.clone() * a - b)]
+            });
+        }
+
+        fn synthesize(
+            &self,
+            _config: Self::Config,
+            _layouter: impl Layouter<Fp>,
+        ) -> std::result::Result<(), plonk::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shape_counts_a_known_circuit() {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        KnownShape::configure(&mut cs);
+        let shape = Shape::of(&cs);
+        assert_eq!(
+            (
+                shape.advice_columns,
+                shape.fixed_columns,
+                shape.instance_columns,
+                shape.selectors,
+                shape.gates
+            ),
+            (2, 1, 1, 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_min_k_grows_with_depth() {
+        let reports = membership_cost_by_depth(1..=4).unwrap();
+        assert_eq!(reports.len(), 4);
+        for pair in reports.windows(2) {
+            assert!(pair[0].min_k <= pair[1].min_k);
+            assert_eq!(pair[0].advice_columns, pair[1].advice_columns);
+            assert_eq!(pair[0].gates, pair[1].gates);
+        }
+        assert!(reports.iter().all(|r| r.proof_size_bytes > 0));
+    }
+
+    #[test]
+    fn test_served_k_fits_merkle_circuits() {
+        for depth in [1, 3, 6] {
+            let report = measure(CIRCUIT_ID, depth as u32, &MerkleCircuit::new(depth), 1).unwrap();
+            let k = membership_k(depth);
+            assert!(report.min_k <= k, "depth {}: k={} but needs {}", depth, k, report.min_k);
+
+            let range = MerkleRangeCircuit::new(depth);
+            let report = measure("merkle-range", depth as u32, &range, 1).unwrap();
+            let k = merkle_range_k(depth);
+            assert!(report.min_k <= k, "depth {}: k={} but needs {}", depth, k, report.min_k);
+        }
+    }
+}
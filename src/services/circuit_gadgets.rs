@@ -0,0 +1,101 @@
+use halo2_gadgets::poseidon::{
+    primitives::{ConstantLength, P128Pow5T3},
+    Hash, Pow5Chip, Pow5Config,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::Fp,
+    plonk::{self, Advice, Column, ConstraintSystem, Fixed},
+};
+
+/// The columns a width-`T` Pow5 Poseidon chip runs on.
+/// A chip of a smaller width can run on the first columns of each array.
+#[derive(Clone, Copy, Debug)]
+pub struct PoseidonColumns<const T: usize> {
+    pub state: [Column<Advice>; T],
+    pub partial_sbox: Column<Advice>,
+    pub rc_a: [Column<Fixed>; T],
+    pub rc_b: [Column<Fixed>; T],
+}
+
+impl<const T: usize> PoseidonColumns<T> {
+    /// Allocates the state and round constant columns, enables equality on the advice columns
+    /// so values can be copied in and out of the hash, and marks the fixed columns as constant
+    /// columns.
+    pub fn configure(meta: &mut ConstraintSystem<Fp>) -> Self {
+        let state = std::array::from_fn(|_| meta.advice_column());
+        let partial_sbox = meta.advice_column();
+        meta.enable_equality(partial_sbox);
+        for col in &state {
+            meta.enable_equality(*col);
+        }
+
+        let rc_a: [_; T] = std::array::from_fn(|_| meta.fixed_column());
+        let rc_b: [_; T] = std::array::from_fn(|_| meta.fixed_column());
+        for col in rc_a.iter().chain(&rc_b) {
+            meta.enable_constant(*col);
+        }
+
+        PoseidonColumns {
+            state,
+            partial_sbox,
+            rc_a,
+            rc_b,
+        }
+    }
+}
+
+/// Assigns a private witness value in row 0 of its own region in `column`.
+pub fn assign_private(
+    layouter: &mut impl Layouter<Fp>,
+    column: Column<Advice>,
+    name: &'static str,
+    value: Value<Fp>,
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    layouter.assign_region(
+        || format!("assign {}", name),
+        |mut region| region.assign_advice(|| name, column, 0, || value),
+    )
+}
+
+/// Assigns a fixed constant in row 0 of its own region in `column`.
+/// The cell is constrained to equal `value`, so the prover cannot substitute another value.
+pub fn assign_constant(
+    layouter: &mut impl Layouter<Fp>,
+    column: Column<Advice>,
+    name: &'static str,
+    value: Fp,
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    layouter.assign_region(
+        || format!("assign {}", name),
+        |mut region| region.assign_advice_from_constant(|| name, column, 0, value),
+    )
+}
+
+/// Computes the width-3 Poseidon(inputs[0], inputs[1]) inside the circuit, as used for
+/// commitments and binary tree nodes.
+pub fn hash_pair(
+    layouter: &mut impl Layouter<Fp>,
+    poseidon: &Pow5Config<Fp, 3, 2>,
+    name: &str,
+    inputs: [AssignedCell<Fp, Fp>; 2],
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    let hasher = Hash::<_, _, P128Pow5T3, ConstantLength<2>, 3, 2>::init(
+        Pow5Chip::<Fp, 3, 2>::construct(poseidon.clone()),
+        layouter.namespace(|| format!("init hasher {}", name)),
+    )?;
+    hasher.hash(layouter.namespace(|| format!("hash {}", name)), inputs)
+}
+
+/// Derives the commitment Poseidon(secret, 0) from a private secret inside the circuit.
+/// The zero pad is a constrained constant, so the commitment matches `poseidon_commit`.
+pub fn commitment(
+    layouter: &mut impl Layouter<Fp>,
+    advice: Column<Advice>,
+    poseidon: &Pow5Config<Fp, 3, 2>,
+    secret: Value<Fp>,
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    let secret_cell = assign_private(layouter, advice, "secret", secret)?;
+    let zero_cell = assign_constant(layouter, advice, "zero pad", Fp::zero())?;
+    hash_pair(layouter, poseidon, "commitment", [secret_cell, zero_cell])
+}
@@ -0,0 +1,240 @@
+use halo2_proofs::{pasta::Fp, plonk::Circuit};
+use rust_api::prelude::*;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::error::ApiError;
+
+use super::circuit_cost::{measure, CircuitCostReport};
+use super::merkle_tree::MerkleTree;
+use super::merkle_tree_service::MerkleTreeService;
+use super::proof_envelope::ProofEnvelope;
+use super::prover::CircuitKeys;
+use super::zk_error::ZkProofError;
+
+/// A circuit that can be served by ZKService through the CircuitRegistry.
+///
+/// Implementing this trait (and registering an instance in `ZKService::with_config`) is all
+/// that is needed to expose a new circuit at `/zk/{circuit_id}/prove` and
+/// `/zk/{circuit_id}/verify`; no controller changes are required. The registered instance
+/// has no witnesses and fixes the circuit's parameters, such as its Merkle depth.
+pub trait CircuitDefinition: Circuit<Fp> + Sized + Send + Sync + 'static {
+    /// Private inputs supplied by the prover in the JSON body of a prove request
+    type Witness: DeserializeOwned;
+
+    /// Stable identifier, used in routes and recorded in proof envelopes
+    const ID: &'static str;
+    /// Names of the public inputs, in instance row order. An input named "root" is the tree
+    /// root the proof was made against.
+    const PUBLIC_INPUTS: &'static [&'static str];
+
+    /// log2 of the number of circuit rows
+    fn k(&self) -> u32;
+
+    /// Merkle depth the circuit is instantiated with, or 0 if it does not involve the tree
+    fn depth(&self) -> u32;
+
+    /// Builds a fully-witnessed circuit with the same parameters as `self` and its public
+    /// inputs from the prover's witness and the current tree.
+    ///
+    /// # Returns
+    /// The circuit and its public inputs (matching PUBLIC_INPUTS), or an error describing why
+    /// the witness cannot produce a valid proof
+    fn build(
+        &self,
+        tree: &MerkleTree,
+        witness: Self::Witness,
+    ) -> std::result::Result<(Self, Vec<Fp>), ZkProofError>;
+}
+
+/// Describes a registered circuit to clients.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CircuitInfo {
+    pub id: String,
+    pub k: u32,
+    pub depth: u32,
+    pub public_inputs: Vec<String>,
+}
+
+/// Object-safe view of a registered circuit, so circuits of different types can share a registry.
+pub trait ServedCircuit: Send + Sync {
+    /// Returns the circuit's identifier, parameters and public-input schema.
+    fn info(&self) -> CircuitInfo;
+
+    /// Parses a JSON witness, builds the circuit against the current tree and proves it.
+    fn prove(
+        &self,
+        tree_service: &MerkleTreeService,
+        witness: serde_json::Value,
+    ) -> std::result::Result<ProofEnvelope, ApiError>;
+
+    /// Position of the tree root among the public inputs, or None if the circuit does not
+    /// prove anything about the tree.
+    fn root_input(&self) -> Option<usize>;
+
+    /// Validates an envelope and returns its public inputs, rejecting envelopes produced for
+    /// another circuit or parameters.
+    fn public_inputs(&self, envelope: &ProofEnvelope) -> std::result::Result<Vec<Fp>, ApiError>;
+
+    /// Verifies an envelope's proof against the public inputs returned by `public_inputs`.
+    fn verify(
+        &self,
+        envelope: &ProofEnvelope,
+        public_inputs: Vec<Fp>,
+    ) -> std::result::Result<bool, ApiError>;
+
+    /// Measures the circuit's minimal k, columns, degree and proof size.
+    fn cost(&self) -> std::result::Result<CircuitCostReport, ApiError>;
+}
+
+/// Registry entry for a circuit. Owns the circuit's unwitnessed instance and its keys,
+/// generated on first use since keygen is expensive.
+pub struct CircuitEntry<C: CircuitDefinition> {
+    circuit: C,
+    keys: OnceLock<CircuitKeys<C>>,
+}
+
+impl<C: CircuitDefinition> CircuitEntry<C> {
+    /// Creates an entry serving `circuit`; its witnesses are never used.
+    pub fn new(circuit: C) -> Self {
+        Self {
+            circuit: circuit.without_witnesses(),
+            keys: OnceLock::new(),
+        }
+    }
+
+    /// Returns the unwitnessed circuit, which fixes the parameters proofs are made with.
+    pub fn circuit(&self) -> &C {
+        &self.circuit
+    }
+
+    /// Returns the circuit's keys, generating them on first use.
+    /// Fails with 500 `key_generation_failed` if keygen fails; a later call retries it.
+    pub fn keys(&self) -> std::result::Result<&CircuitKeys<C>, ApiError> {
+        if let Some(keys) = self.keys.get() {
+            return Ok(keys);
+        }
+        let keys = CircuitKeys::setup(self.circuit.k(), &self.circuit).map_err(|e| {
+            ApiError::internal("key_generation_failed", format!("{}: {:?}", C::ID, e))
+        })?;
+        Ok(self.keys.get_or_init(|| keys))
+    }
+}
+
+impl<C: CircuitDefinition + Default> Default for CircuitEntry<C> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+impl<C: CircuitDefinition> ServedCircuit for CircuitEntry<C> {
+    fn info(&self) -> CircuitInfo {
+        CircuitInfo {
+            id: C::ID.to_string(),
+            k: self.circuit.k(),
+            depth: self.circuit.depth(),
+            public_inputs: C::PUBLIC_INPUTS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn prove(
+        &self,
+        tree_service: &MerkleTreeService,
+        witness: serde_json::Value,
+    ) -> std::result::Result<ProofEnvelope, ApiError> {
+        let witness: C::Witness = serde_json::from_value(witness).map_err(|e| {
+            ApiError::bad_request("invalid_witness", format!("invalid witness: {}", e))
+        })?;
+
+        //only hold the tree lock while building the witness, not while proving
+        let (circuit, public_inputs) =
+            tree_service.with_tree(|tree| self.circuit.build(tree, witness))??;
+
+        let proof = self
+            .keys()?
+            .prove(&[circuit], std::slice::from_ref(&public_inputs))
+            .map_err(ZkProofError::from)?;
+
+        Ok(ProofEnvelope::new(
+            C::ID,
+            self.circuit.depth(),
+            self.circuit.k(),
+            &public_inputs,
+            proof,
+        ))
+    }
+
+    fn root_input(&self) -> Option<usize> {
+        C::PUBLIC_INPUTS.iter().position(|&name| name == "root")
+    }
+
+    fn public_inputs(&self, envelope: &ProofEnvelope) -> std::result::Result<Vec<Fp>, ApiError> {
+        envelope.validate()?;
+        envelope.expect_circuit(C::ID, self.circuit.depth(), self.circuit.k())?;
+
+        let public_inputs = envelope.public_inputs_fp();
+        if public_inputs.len() != C::PUBLIC_INPUTS.len() {
+            return Err(ApiError::bad_request(
+                "invalid_envelope",
+                format!(
+                    "expected {} public inputs, found {}",
+                    C::PUBLIC_INPUTS.len(),
+                    public_inputs.len()
+                ),
+            ));
+        }
+        Ok(public_inputs)
+    }
+
+    fn verify(
+        &self,
+        envelope: &ProofEnvelope,
+        public_inputs: Vec<Fp>,
+    ) -> std::result::Result<bool, ApiError> {
+        Ok(self.keys()?.verify(&envelope.proof, &[public_inputs]))
+    }
+
+    fn cost(&self) -> std::result::Result<CircuitCostReport, ApiError> {
+        measure(C::ID, self.circuit.depth(), &self.circuit, 1).map_err(|e| {
+            ApiError::internal("cost_measurement_failed", format!("{}: {:?}", C::ID, e))
+        })
+    }
+}
+
+/// Maps circuit ids to the circuits ZKService can prove and verify.
+#[derive(Default)]
+pub struct CircuitRegistry {
+    circuits: BTreeMap<&'static str, Arc<dyn ServedCircuit>>,
+}
+
+impl CircuitRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a circuit under its `CircuitDefinition::ID`, replacing any previous entry.
+    /// `circuit` fixes the parameters, such as the Merkle depth, it is served with.
+    /// Returns the typed entry so callers can keep direct access to its keys.
+    pub fn register<C: CircuitDefinition>(&mut self, circuit: C) -> Arc<CircuitEntry<C>> {
+        let entry = Arc::new(CircuitEntry::new(circuit));
+        self.circuits.insert(C::ID, entry.clone());
+        entry
+    }
+
+    /// Looks up a circuit by id.
+    pub fn get(&self, circuit_id: &str) -> Option<&Arc<dyn ServedCircuit>> {
+        self.circuits.get(circuit_id)
+    }
+
+    /// Measures every registered circuit, ordered by id.
+    pub fn costs(&self) -> std::result::Result<Vec<CircuitCostReport>, ApiError> {
+        self.circuits.values().map(|c| c.cost()).collect()
+    }
+
+    /// Describes every registered circuit, ordered by id.
+    pub fn list(&self) -> Vec<CircuitInfo> {
+        self.circuits.values().map(|c| c.info()).collect()
+    }
+}
@@ -0,0 +1,240 @@
+use halo2_proofs::pasta::Fp;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Why a string is not a hex-encoded field element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FpHexError {
+    /// The input (after an optional `0x` prefix) is not 64 characters long
+    InvalidLength { found: usize },
+    /// A character that is not a hex digit, at its character position after the prefix
+    InvalidChar { index: usize, found: char },
+    /// The 32 bytes encode an integer that is not below the field modulus
+    NonCanonical,
+}
+
+impl fmt::Display for FpHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FpHexError::InvalidLength { found } => {
+                write!(f, "expected 64 hex chars (32 bytes), found {}", found)
+            },
+            FpHexError::InvalidChar { index, found } => {
+                write!(f, "invalid hex character {:?} at position {}", found, index)
+            },
+            FpHexError::NonCanonical => write!(f, "value is not below the field modulus"),
+        }
+    }
+}
+
+impl std::error::Error for FpHexError {}
+
+/// Parses a hex string into an Fp field element.
+/// Expects 32 bytes (64 hex chars) in little-endian byte order, optionally prefixed with `0x`
+/// or `0X`; upper- and lowercase digits are accepted. Never panics, whatever the input.
+///
+/// ```
+/// use zk_rust_api::{fp_to_hex, parse_fp_hex, services::fp_hex::FpHexError, Fp};
+///
+/// let hex = fp_to_hex(&Fp::from(42u64));
+/// assert_eq!(parse_fp_hex(&format!("0x{}", hex.to_uppercase())), Ok(Fp::from(42u64)));
+/// assert_eq!(parse_fp_hex("abc"), Err(FpHexError::InvalidLength { found: 3 }));
+/// ```
+pub fn parse_fp_hex(input: &str) -> std::result::Result<Fp, FpHexError> {
+    use ff::PrimeField;
+    let digits = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+
+    if let Some((index, found)) = digits
+        .chars()
+        .enumerate()
+        .find(|(_, c)| !c.is_ascii_hexdigit())
+    {
+        return Err(FpHexError::InvalidChar { index, found });
+    }
+    //all chars are ASCII from here on, so the byte length is the char count
+    let mut repr = [0u8; 32];
+    hex::decode_to_slice(digits, &mut repr).map_err(|_| FpHexError::InvalidLength {
+        found: digits.len(),
+    })?;
+    Option::from(Fp::from_repr(repr)).ok_or(FpHexError::NonCanonical)
+}
+
+/// Encodes an Fp field element as 64 hex chars in little-endian byte order.
+/// This is the inverse of `parse_fp_hex`.
+pub fn fp_to_hex(value: &Fp) -> String {
+    use ff::PrimeField;
+    hex::encode(value.to_repr())
+}
+
+/// A field element that serializes as canonical hex (`fp_to_hex`) and deserializes with
+/// `parse_fp_hex`.
+///
+/// Every field element in a request or response body uses this type, so clients only ever see
+/// one encoding: 64 lowercase hex chars of the little-endian representation.
+///
+/// ```
+/// use zk_rust_api::{services::fp_hex::FpHex, Fp};
+///
+/// let json = serde_json::to_string(&FpHex(Fp::from(1u64))).unwrap();
+/// assert_eq!(json, format!("\"01{}\"", "0".repeat(62)));
+/// assert_eq!(serde_json::from_str::<FpHex>(&json).unwrap(), FpHex(Fp::from(1u64)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FpHex(pub Fp);
+
+impl From<Fp> for FpHex {
+    fn from(value: Fp) -> Self {
+        FpHex(value)
+    }
+}
+
+impl From<FpHex> for Fp {
+    fn from(value: FpHex) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for FpHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&fp_to_hex(&self.0))
+    }
+}
+
+impl Serialize for FpHex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&fp_to_hex(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for FpHex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        parse_fp_hex(&hex)
+            .map(FpHex)
+            .map_err(|e| D::Error::custom(format!("invalid field element {:?}: {}", hex, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::merkle_tree_service::poseidon_commit;
+    use ff::PrimeField;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_accepts_prefix_and_uppercase() {
+        let value = poseidon_commit(42);
+        let hex = fp_to_hex(&value);
+        for input in [
+            hex.clone(),
+            format!("0x{}", hex),
+            format!("0X{}", hex),
+            hex.to_uppercase(),
+            format!("0x{}", hex.to_uppercase()),
+        ] {
+            assert_eq!(parse_fp_hex(&input), Ok(value), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_each_error() {
+        let hex = fp_to_hex(&Fp::one());
+        assert_eq!(parse_fp_hex(""), Err(FpHexError::InvalidLength { found: 0 }));
+        assert_eq!(parse_fp_hex(&hex[1..]), Err(FpHexError::InvalidLength { found: 63 }));
+        assert_eq!(
+            parse_fp_hex(&format!("{}00", hex)),
+            Err(FpHexError::InvalidLength { found: 66 })
+        );
+        //only one prefix is stripped
+        assert_eq!(
+            parse_fp_hex(&format!("0x0x{}", &hex[4..])),
+            Err(FpHexError::InvalidChar {
+                index: 1,
+                found: 'x'
+            })
+        );
+        //multi-byte characters are reported, not sliced through
+        assert_eq!(
+            parse_fp_hex(&format!("é{}", &hex[1..])),
+            Err(FpHexError::InvalidChar {
+                index: 0,
+                found: 'é'
+            })
+        );
+        //the modulus itself (p = -1 + 1) is not a canonical encoding
+        let mut modulus = (-Fp::one()).to_repr();
+        modulus[0] += 1;
+        assert_eq!(parse_fp_hex(&hex::encode(modulus)), Err(FpHexError::NonCanonical));
+        assert_eq!(parse_fp_hex(&"f".repeat(64)), Err(FpHexError::NonCanonical));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_never_panics(input in any::<String>()) {
+            let _ = parse_fp_hex(&input);
+        }
+
+        #[test]
+        fn prop_parse_never_panics_on_hex_like_input(input in "(0[xX])?[0-9a-fA-Fé ]{0,70}") {
+            let _ = parse_fp_hex(&input);
+        }
+
+        #[test]
+        fn prop_round_trip(bytes in any::<[u8; 32]>()) {
+            //clear the top bits so every sample is below the modulus
+            let mut repr = bytes;
+            repr[31] &= 0x3f;
+            let value = Fp::from_repr(repr).unwrap();
+            let hex = fp_to_hex(&value);
+            prop_assert_eq!(parse_fp_hex(&hex), Ok(value));
+            prop_assert_eq!(parse_fp_hex(&hex.to_uppercase()), Ok(value));
+            prop_assert_eq!(parse_fp_hex(&format!("0x{}", hex)), Ok(value));
+        }
+
+        #[test]
+        fn prop_parse_accepts_exactly_canonical_bytes(bytes in any::<[u8; 32]>()) {
+            let canonical = bool::from(Fp::from_repr(bytes).is_some());
+            prop_assert_eq!(parse_fp_hex(&hex::encode(bytes)).is_ok(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_matches_parse_fp_hex() {
+        for value in [Fp::zero(), Fp::one(), -Fp::one(), poseidon_commit(42)] {
+            let json = serde_json::to_string(&FpHex(value)).unwrap();
+            assert_eq!(json, format!("\"{}\"", fp_to_hex(&value)));
+
+            let decoded: FpHex = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.0, value);
+            assert_eq!(parse_fp_hex(&decoded.to_string()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_deserialize_accepts_what_parse_fp_hex_accepts() {
+        let value = poseidon_commit(7);
+        let prefixed = format!("\"0x{}\"", fp_to_hex(&value));
+        assert_eq!(serde_json::from_str::<FpHex>(&prefixed).unwrap(), FpHex(value));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_invalid_hex() {
+        let not_hex = format!("\"{}\"", "zz".repeat(32));
+        for bad in ["\"\"", "\"1234\"", "42", not_hex.as_str()] {
+            assert!(serde_json::from_str::<FpHex>(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let value = FpHex(poseidon_commit(99));
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&value, &mut bytes).unwrap();
+        let decoded: FpHex = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, value);
+    }
+}
@@ -12,6 +12,12 @@ pub struct HealthService {
 
 impl Injectable for HealthService {}
 
+impl Default for HealthService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HealthService {
     pub fn new() -> Self {
         Self {
@@ -1,32 +1,128 @@
-use halo2_gadgets::poseidon::{
-    primitives::{ConstantLength, P128Pow5T3},
-    Hash, Pow5Chip, Pow5Config,
-};
+use halo2_gadgets::poseidon::{primitives::P128Pow5T3, Pow5Chip, Pow5Config};
 use halo2_proofs::{
-    circuit::{Layouter, SimpleFloorPlanner, Value},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
     pasta::Fp,
     plonk::{self, Advice, Circuit, Column, ConstraintSystem, Expression, Instance, Selector},
     poly::Rotation,
 };
+use rust_api::prelude::*;
+
+use super::circuit_gadgets::{self, PoseidonColumns};
+use super::circuit_registry::CircuitDefinition;
+use super::merkle_tree::MerkleTree;
+use super::merkle_tree_service::poseidon_commit;
+use super::prover::membership_k;
+use super::zk_error::ZkProofError;
 
+/// Depth the membership circuits are built for by default, and the default `tree.depth`.
 pub const DEPTH: usize = 3;
 
+/// Deepest tree the membership circuits can be built for (2^20 leaves).
+pub const MAX_DEPTH: usize = 20;
+
+/// Stable identifier for this circuit, recorded in proof envelopes.
+/// Bump the suffix whenever the constraint system changes in a way that invalidates old keys.
+/// The depth is recorded next to it, since circuits of different depths have different keys.
+pub const CIRCUIT_ID: &str = "merkle-membership-v2";
+
 #[derive(Clone, Debug)]
 pub struct MerkleCircuit {
     /// Private secret witness — never revealed; the circuit proves Poseidon(secret) == commitment
     pub secret: Value<Fp>,
 
-    /// Merkle path siblings (one per level).
+    /// Merkle path siblings (one per level); their number is the circuit's depth.
     /// The circuit hashes the current value with each sibling in sequence,
     /// moving up the tree until reaching the root.
-    pub siblings: [Value<Fp>; DEPTH],
+    pub siblings: Vec<Value<Fp>>,
 
-    /// Direction bits (0 = cur is left, 1 = cur is right).
+    /// Direction bits (0 = cur is left, 1 = cur is right), one per sibling.
     /// The circuit enforces:
     /// 1. Each direction bit must be binary (0 or 1)
     /// 2. left  = cur * (1 - dir) + sibling * dir
     /// 3. right = cur * dir       + sibling * (1 - dir)
-    pub directions: [Value<Fp>; DEPTH],
+    pub directions: Vec<Value<Fp>>,
+}
+
+impl MerkleCircuit {
+    /// A circuit for trees of up to `depth` levels with all witnesses unknown, suitable for
+    /// key generation.
+    pub fn new(depth: usize) -> Self {
+        Self {
+            secret: Value::unknown(),
+            siblings: vec![Value::unknown(); depth],
+            directions: vec![Value::unknown(); depth],
+        }
+    }
+}
+
+impl Default for MerkleCircuit {
+    /// A circuit of the default DEPTH with all witnesses unknown.
+    fn default() -> Self {
+        Self::new(DEPTH)
+    }
+}
+
+/// Private inputs for a membership proof request.
+#[derive(Debug, Deserialize)]
+pub struct MembershipWitness {
+    /// The private secret whose Poseidon commitment must be in the tree
+    pub secret: u64,
+}
+
+/// A Merkle path as circuit witnesses (siblings, then directions), with the root it leads to.
+pub type PathWitness = (Vec<Value<Fp>>, Vec<Value<Fp>>, Fp);
+
+/// Looks up `leaf` in the tree and returns its path as witnesses for a circuit of `depth`
+/// levels. A shallower tree is padded with zero subtrees, so the path always has `depth`
+/// levels and leads to `tree.root_at_depth(depth)`, the root the proof is made against.
+/// Fails if the leaf is not in the tree or the tree is deeper than `depth`.
+pub fn merkle_path_witness(
+    tree: &MerkleTree,
+    leaf: Fp,
+    depth: usize,
+) -> std::result::Result<PathWitness, ZkProofError> {
+    let index = tree
+        .index_of(&leaf)
+        .ok_or(ZkProofError::CommitmentNotInTree)?;
+    let proof =
+        tree.generate_proof_at_depth(index, depth)
+            .ok_or(ZkProofError::DepthUnsupported {
+                tree_depth: tree.depth(),
+                circuit_depth: depth,
+            })?;
+
+    let known = |values: Vec<Fp>| values.into_iter().map(Value::known).collect();
+    Ok((known(proof.siblings), known(proof.directions), proof.root))
+}
+
+impl CircuitDefinition for MerkleCircuit {
+    type Witness = MembershipWitness;
+
+    const ID: &'static str = CIRCUIT_ID;
+    const PUBLIC_INPUTS: &'static [&'static str] = &["root"];
+
+    fn k(&self) -> u32 {
+        membership_k(self.siblings.len())
+    }
+
+    fn depth(&self) -> u32 {
+        self.siblings.len() as u32
+    }
+
+    fn build(
+        &self,
+        tree: &MerkleTree,
+        witness: Self::Witness,
+    ) -> std::result::Result<(Self, Vec<Fp>), ZkProofError> {
+        let (siblings, directions, root) =
+            merkle_path_witness(tree, poseidon_commit(witness.secret), self.siblings.len())?;
+        let circuit = MerkleCircuit {
+            secret: Value::known(Fp::from(witness.secret)),
+            siblings,
+            directions,
+        };
+        Ok((circuit, vec![root]))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -47,19 +143,10 @@ pub struct MerkleConfig {
     swap_selector: Selector,
 }
 
-impl Circuit<Fp> for MerkleCircuit {
-    type Config = MerkleConfig;
-    type FloorPlanner = SimpleFloorPlanner;
-
-    fn without_witnesses(&self) -> Self {
-        Self {
-            secret: Value::unknown(),
-            siblings: [Value::unknown(); DEPTH],
-            directions: [Value::unknown(); DEPTH],
-        }
-    }
-
-    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+impl MerkleConfig {
+    /// Allocates the columns, Poseidon chip and conditional swap gate shared by every circuit
+    /// that proves a Merkle path.
+    pub fn configure(meta: &mut ConstraintSystem<Fp>) -> Self {
         let advice = meta.advice_column();
         let instance = meta.instance_column();
 
@@ -67,43 +154,15 @@ impl Circuit<Fp> for MerkleCircuit {
         meta.enable_equality(advice);
         meta.enable_equality(instance);
 
-        //poseidon config - needs state columns for hashing
-        let state = [
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-        ];
-        let partial_sbox = meta.advice_column();
-        meta.enable_equality(partial_sbox);
-
-        //enable equality on state columns so we can copy values between regions
-        for col in &state {
-            meta.enable_equality(*col);
-        }
-
-        //round constants need many fixed columns for P128Pow5T3
-        //allocate enough columns to store all round constants
-        let rc_a = [
-            meta.fixed_column(),
-            meta.fixed_column(),
-            meta.fixed_column(),
-        ];
-        let rc_b = [
-            meta.fixed_column(),
-            meta.fixed_column(),
-            meta.fixed_column(),
-        ];
-
-        //mark these columns as constant columns
-        meta.enable_constant(rc_a[0]);
-        meta.enable_constant(rc_a[1]);
-        meta.enable_constant(rc_a[2]);
-        meta.enable_constant(rc_b[0]);
-        meta.enable_constant(rc_b[1]);
-        meta.enable_constant(rc_b[2]);
-
-        let poseidon =
-            Pow5Chip::<Fp, 3, 2>::configure::<P128Pow5T3>(meta, state, partial_sbox, rc_a, rc_b);
+        //poseidon config - needs state and round constant columns for hashing
+        let columns = PoseidonColumns::<3>::configure(meta);
+        let poseidon = Pow5Chip::<Fp, 3, 2>::configure::<P128Pow5T3>(
+            meta,
+            columns.state,
+            columns.partial_sbox,
+            columns.rc_a,
+            columns.rc_b,
+        );
 
         //columns for conditional swap based on direction bit
         let swap_current = meta.advice_column();
@@ -162,91 +221,127 @@ impl Circuit<Fp> for MerkleCircuit {
         }
     }
 
-    fn synthesize(
+    /// Assigns a private witness value in the general-purpose advice column.
+    pub fn assign_private(
         &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<Fp>,
-    ) -> std::result::Result<(), plonk::Error> {
-        //step 1: assign secret as a private witness cell
-        let secret_cell = layouter.assign_region(
-            || "assign secret",
-            |mut region| region.assign_advice(|| "secret", config.advice, 0, || self.secret),
-        )?;
-
-        //step 2: hash Poseidon(secret, 0) inside the circuit to derive the commitment
-        let commitment_hasher = Hash::<_, _, P128Pow5T3, ConstantLength<2>, 3, 2>::init(
-            Pow5Chip::<Fp, 3, 2>::construct(config.poseidon.clone()),
-            layouter.namespace(|| "commitment hasher"),
-        )?;
-
-        //step 3: pad with zero to form the 2-element input [secret, 0]
-        let zero_cell = layouter.assign_region(
-            || "assign zero pad",
+        layouter: &mut impl Layouter<Fp>,
+        name: &'static str,
+        value: Value<Fp>,
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        circuit_gadgets::assign_private(layouter, self.advice, name, value)
+    }
+
+    /// Assigns a fixed constant in the general-purpose advice column.
+    /// The cell is constrained to equal `value`, so the prover cannot substitute another value.
+    pub fn assign_constant(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        name: &'static str,
+        value: Fp,
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        circuit_gadgets::assign_constant(layouter, self.advice, name, value)
+    }
+
+    /// Copies a public input from the given instance row into the general-purpose advice
+    /// column so it can be used in other constraints.
+    pub fn assign_public(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        name: &'static str,
+        row: usize,
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        layouter.assign_region(
+            || format!("assign {}", name),
             |mut region| {
-                region.assign_advice(|| "zero", config.advice, 0, || Value::known(Fp::zero()))
+                region.assign_advice_from_instance(|| name, self.instance, row, self.advice, 0)
             },
-        )?;
+        )
+    }
 
-        //commitment is Poseidon(secret, 0); this becomes the leaf for the Merkle path
-        let mut cur_cell = commitment_hasher
-            .hash(layouter.namespace(|| "compute commitment"), [secret_cell, zero_cell])?;
+    /// Computes Poseidon(inputs[0], inputs[1]) inside the circuit.
+    pub fn hash_pair(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        name: &str,
+        inputs: [AssignedCell<Fp, Fp>; 2],
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        circuit_gadgets::hash_pair(layouter, &self.poseidon, name, inputs)
+    }
+
+    /// Walks the Merkle path from `leaf` up to the root and returns the root cell.
+    /// The path may have any length; the number of levels is the tree depth.
+    ///
+    /// # Arguments
+    /// * `leaf` - The assigned leaf (commitment) cell
+    /// * `siblings` - Sibling nodes, one per level from leaf to root
+    /// * `directions` - Direction bits, one per level (0 = cur is left, 1 = cur is right)
+    pub fn merkle_root(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        leaf: AssignedCell<Fp, Fp>,
+        siblings: &[Value<Fp>],
+        directions: &[Value<Fp>],
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        assert_eq!(siblings.len(), directions.len(), "one direction bit per sibling");
+        let mut cur_cell = leaf;
 
         //iterate through each level of the tree, from leaf to root
-        for i in 0..DEPTH {
+        for i in 0..siblings.len() {
             //perform conditional swap based on direction bit
             //this region assigns all values and enables the swap constraint
             let (left_cell, right_cell) = layouter.assign_region(
                 || format!("conditional swap level {}", i),
                 |mut region| {
                     //enable the swap selector
-                    config.swap_selector.enable(&mut region, 0)?;
+                    self.swap_selector.enable(&mut region, 0)?;
 
-                    //copy the current value into the swap region
-                    let _cur_copy = region.assign_advice(
+                    //copy the current value into the swap region; the copy constraint ties
+                    //this level to the hash computed at the level below
+                    let cur_copy = cur_cell.copy_advice(
                         || format!("cur {}", i),
-                        config.swap_current,
+                        &mut region,
+                        self.swap_current,
                         0,
-                        || cur_cell.value().copied(),
                     )?;
 
                     //assign the sibling value
                     let _sibling = region.assign_advice(
                         || format!("sibling {}", i),
-                        config.swap_sibling,
+                        self.swap_sibling,
                         0,
-                        || self.siblings[i],
+                        || siblings[i],
                     )?;
 
                     //assign the direction bit
                     let _dir = region.assign_advice(
                         || format!("dir {}", i),
-                        config.swap_direction,
+                        self.swap_direction,
                         0,
-                        || self.directions[i],
+                        || directions[i],
                     )?;
 
                     //compute and assign left = cur * (1 - dir) + sibling * dir
-                    let left_val = cur_cell
+                    let left_val = cur_copy
                         .value()
-                        .zip(self.siblings[i])
-                        .zip(self.directions[i])
+                        .zip(siblings[i])
+                        .zip(directions[i])
                         .map(|((c, s), d)| c * (Fp::one() - d) + s * d);
                     let left = region.assign_advice(
                         || format!("left {}", i),
-                        config.swap_left,
+                        self.swap_left,
                         0,
                         || left_val,
                     )?;
 
                     //compute and assign right = cur * dir + sibling * (1 - dir)
-                    let right_val = cur_cell
+                    let right_val = cur_copy
                         .value()
-                        .zip(self.siblings[i])
-                        .zip(self.directions[i])
+                        .zip(siblings[i])
+                        .zip(directions[i])
                         .map(|((c, s), d)| c * d + s * (Fp::one() - d));
                     let right = region.assign_advice(
                         || format!("right {}", i),
-                        config.swap_right,
+                        self.swap_right,
                         0,
                         || right_val,
                     )?;
@@ -260,23 +355,54 @@ impl Circuit<Fp> for MerkleCircuit {
                 },
             )?;
 
-            //initialize the Poseidon hasher for this level
-            let hasher = Hash::<_, _, P128Pow5T3, ConstantLength<2>, 3, 2>::init(
-                Pow5Chip::<Fp, 3, 2>::construct(config.poseidon.clone()),
-                layouter.namespace(|| format!("init hasher {}", i)),
-            )?;
-
             //hash left and right to compute the parent node
             //the direction bit ensures we hash in the correct order
-            cur_cell = hasher.hash(
-                layouter.namespace(|| format!("hash level {}", i)),
-                [left_cell, right_cell],
-            )?;
+            cur_cell =
+                self.hash_pair(layouter, &format!("level {}", i), [left_cell, right_cell])?;
         }
 
+        Ok(cur_cell)
+    }
+
+    /// Lays out the full membership statement: Poseidon(secret, 0) is a leaf on the given path
+    /// and the path hashes to the public root in instance row 0.
+    pub fn synthesize_membership(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        secret: Value<Fp>,
+        siblings: &[Value<Fp>],
+        directions: &[Value<Fp>],
+    ) -> std::result::Result<(), plonk::Error> {
+        //hash Poseidon(secret, 0) inside the circuit to derive the commitment;
+        //this becomes the leaf for the Merkle path
+        let commitment =
+            circuit_gadgets::commitment(layouter, self.advice, &self.poseidon, secret)?;
+
+        //walk the path from the commitment up to the root
+        let root = self.merkle_root(layouter, commitment, siblings, directions)?;
+
         //constrain the final hash (root) to equal the public input
-        layouter.constrain_instance(cur_cell.cell(), config.instance, 0)?;
+        layouter.constrain_instance(root.cell(), self.instance, 0)
+    }
+}
+
+impl Circuit<Fp> for MerkleCircuit {
+    type Config = MerkleConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(self.siblings.len())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        MerkleConfig::configure(meta)
+    }
 
-        Ok(())
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        config.synthesize_membership(&mut layouter, self.secret, &self.siblings, &self.directions)
     }
 }
@@ -0,0 +1,245 @@
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    pasta::Fp,
+    plonk::{self, Circuit, ConstraintSystem},
+};
+use rust_api::prelude::*;
+
+use super::circuit_registry::CircuitDefinition;
+use super::merkle_circuit::{merkle_path_witness, MerkleConfig, DEPTH};
+use super::merkle_tree::MerkleTree;
+use super::merkle_tree_service::poseidon_commit_attribute;
+use super::prover::membership_k;
+use super::range_check::{RangeCheckConfig, RANGE_K};
+use super::zk_error::ZkProofError;
+
+/// Stable identifier for this circuit, recorded in proof envelopes.
+pub const MERKLE_RANGE_CIRCUIT_ID: &str = "merkle-range-v1";
+
+/// Circuit size parameter MerkleRangeCircuit is served with at `depth` levels: one more than
+/// the larger of the membership circuit's and the range check's, so the 256-row limb lookup
+/// table fits alongside the Poseidon regions.
+pub const fn merkle_range_k(depth: usize) -> u32 {
+    let k = membership_k(depth);
+    if k > RANGE_K {
+        k + 1
+    } else {
+        RANGE_K + 1
+    }
+}
+
+/// Circuit size parameter used for MerkleRangeCircuit at the default DEPTH.
+pub const MERKLE_RANGE_K: u32 = merkle_range_k(DEPTH);
+
+/// Proves "I am a member of the tree AND my committed attribute lies in [min, max]"
+/// without revealing the secret, the attribute, or which leaf is mine.
+///
+/// The leaf is the attribute-bound commitment `Poseidon(secret, attribute)`, so the attribute
+/// cannot be chosen at proving time: it is fixed when the commitment is registered.
+///
+/// Public inputs (instance column rows): 0 = root, 1 = min, 2 = max.
+#[derive(Clone, Debug)]
+pub struct MerkleRangeCircuit {
+    /// Private secret witness
+    pub secret: Value<Fp>,
+    /// Private attribute committed alongside the secret (age, balance, ...)
+    pub attribute: Value<Fp>,
+    /// Merkle path siblings (one per level); their number is the circuit's depth
+    pub siblings: Vec<Value<Fp>>,
+    /// Direction bits (0 = cur is left, 1 = cur is right), one per sibling
+    pub directions: Vec<Value<Fp>>,
+}
+
+impl MerkleRangeCircuit {
+    /// A circuit for trees of up to `depth` levels with all witnesses unknown, suitable for
+    /// key generation.
+    pub fn new(depth: usize) -> Self {
+        Self {
+            secret: Value::unknown(),
+            attribute: Value::unknown(),
+            siblings: vec![Value::unknown(); depth],
+            directions: vec![Value::unknown(); depth],
+        }
+    }
+}
+
+impl Default for MerkleRangeCircuit {
+    /// A circuit of the default DEPTH with all witnesses unknown.
+    fn default() -> Self {
+        Self::new(DEPTH)
+    }
+}
+
+/// Private inputs and public bounds for a membership + attribute range proof request.
+/// The commitment registered in the tree must be Poseidon(secret, attribute).
+#[derive(Debug, Deserialize)]
+pub struct RangeWitness {
+    pub secret: u64,
+    pub attribute: u64,
+    /// Inclusive lower bound (public)
+    pub min: u64,
+    /// Inclusive upper bound (public)
+    pub max: u64,
+}
+
+impl CircuitDefinition for MerkleRangeCircuit {
+    type Witness = RangeWitness;
+
+    const ID: &'static str = MERKLE_RANGE_CIRCUIT_ID;
+    const PUBLIC_INPUTS: &'static [&'static str] = &["root", "min", "max"];
+
+    fn k(&self) -> u32 {
+        merkle_range_k(self.siblings.len())
+    }
+
+    fn depth(&self) -> u32 {
+        self.siblings.len() as u32
+    }
+
+    fn build(
+        &self,
+        tree: &MerkleTree,
+        witness: Self::Witness,
+    ) -> std::result::Result<(Self, Vec<Fp>), ZkProofError> {
+        if !(witness.min..=witness.max).contains(&witness.attribute) {
+            return Err(ZkProofError::InvalidWitness {
+                reason: format!("attribute is not within [{}, {}]", witness.min, witness.max),
+            });
+        }
+
+        let commitment = poseidon_commit_attribute(witness.secret, witness.attribute);
+        let (siblings, directions, root) =
+            merkle_path_witness(tree, commitment, self.siblings.len())?;
+        let circuit = MerkleRangeCircuit {
+            secret: Value::known(Fp::from(witness.secret)),
+            attribute: Value::known(Fp::from(witness.attribute)),
+            siblings,
+            directions,
+        };
+        let public_inputs = vec![root, Fp::from(witness.min), Fp::from(witness.max)];
+        Ok((circuit, public_inputs))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MerkleRangeConfig {
+    merkle: MerkleConfig,
+    range: RangeCheckConfig,
+}
+
+impl Circuit<Fp> for MerkleRangeCircuit {
+    type Config = MerkleRangeConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(self.siblings.len())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        //MerkleConfig enables the constant columns the range check needs
+        MerkleRangeConfig {
+            merkle: MerkleConfig::configure(meta),
+            range: RangeCheckConfig::configure(meta),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        let merkle = &config.merkle;
+        config.range.load_table(&mut layouter)?;
+
+        //membership: Poseidon(secret, attribute) is a leaf under the public root
+        let secret = merkle.assign_private(&mut layouter, "secret", self.secret)?;
+        let attribute = merkle.assign_private(&mut layouter, "attribute", self.attribute)?;
+        let commitment =
+            merkle.hash_pair(&mut layouter, "commitment", [secret, attribute.clone()])?;
+        let root =
+            merkle.merkle_root(&mut layouter, commitment, &self.siblings, &self.directions)?;
+        layouter.constrain_instance(root.cell(), merkle.instance, 0)?;
+
+        //range: min <= attribute <= max, with both bounds taken from the public inputs
+        let min = merkle.assign_public(&mut layouter, "min", 1)?;
+        let max = merkle.assign_public(&mut layouter, "max", 2)?;
+        config.range.assert_difference_in_range(
+            &mut layouter,
+            "attribute - min",
+            &attribute,
+            &min,
+        )?;
+        config.range.assert_difference_in_range(
+            &mut layouter,
+            "max - attribute",
+            &max,
+            &attribute,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    /// Member 3 has secret 7 and attribute (age) 30 in an 8-member tree.
+    fn member_circuit(attribute: u64) -> (Fp, MerkleRangeCircuit) {
+        let commitments: Vec<Fp> = (0..8u64)
+            .map(|i| {
+                if i == 3 {
+                    poseidon_commit_attribute(7, 30)
+                } else {
+                    Fp::from(i + 100)
+                }
+            })
+            .collect();
+        let tree = MerkleTree::new(commitments);
+        let proof = tree.generate_proof(3).unwrap();
+
+        let circuit = MerkleRangeCircuit {
+            secret: Value::known(Fp::from(7u64)),
+            attribute: Value::known(Fp::from(attribute)),
+            siblings: proof.siblings.into_iter().map(Value::known).collect(),
+            directions: proof.directions.into_iter().map(Value::known).collect(),
+        };
+        (tree.root(), circuit)
+    }
+
+    fn verifies(circuit: &MerkleRangeCircuit, root: Fp, min: u64, max: u64) -> bool {
+        let instance = vec![root, Fp::from(min), Fp::from(max)];
+        MockProver::run(MERKLE_RANGE_K, circuit, vec![instance])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_member_with_attribute_in_range() {
+        let (root, circuit) = member_circuit(30);
+        assert!(verifies(&circuit, root, 18, 65));
+        assert!(verifies(&circuit, root, 30, 30), "bounds are inclusive");
+    }
+
+    #[test]
+    fn test_member_with_attribute_out_of_range() {
+        let (root, circuit) = member_circuit(30);
+        assert!(!verifies(&circuit, root, 31, 65));
+        assert!(!verifies(&circuit, root, 0, 29));
+    }
+
+    #[test]
+    fn test_attribute_not_matching_commitment() {
+        //claiming a different attribute changes the leaf, so membership fails
+        let (root, circuit) = member_circuit(40);
+        assert!(!verifies(&circuit, root, 18, 65));
+    }
+
+    #[test]
+    fn test_wrong_root() {
+        let (_, circuit) = member_circuit(30);
+        assert!(!verifies(&circuit, Fp::from(1u64), 18, 65));
+    }
+}
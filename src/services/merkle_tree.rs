@@ -1,5 +1,8 @@
-use halo2_gadgets::poseidon::primitives::{ConstantLength, Hash as PoseidonHash, P128Pow5T3};
+use ff::PrimeField;
 use halo2_proofs::pasta::Fp;
+use std::collections::HashMap;
+
+pub use crate::services::tree_hasher::{PoseidonHasher, Sha256Hasher, TreeHasher};
 
 /// Represents a leaf value in the Merkle tree.
 /// Can be either an unhashed value (which will be hashed) or a pre-hashed Fp value.
@@ -23,6 +26,15 @@ impl From<Fp> for LeafValue {
     }
 }
 
+impl From<LeafValue> for Fp {
+    fn from(leaf: LeafValue) -> Self {
+        match leaf {
+            LeafValue::Unhashed(val) => Fp::from(val),
+            LeafValue::Hashed(fp) => fp,
+        }
+    }
+}
+
 /// Represents a Merkle proof for a specific leaf.
 /// Contains the sibling nodes and direction bits needed to reconstruct the path to the root.
 #[derive(Clone, Debug)]
@@ -38,123 +50,438 @@ pub struct MerkleProof {
     pub root: Fp,
 }
 
-/// A Merkle tree implementation using Poseidon hash.
+/// Proof that the tree with `old_size` leaves is a prefix of the tree with `new_size` leaves,
+/// i.e. the larger tree only appended leaves and never rewrote or removed earlier ones.
+///
+/// It is the path of the old tree's last leaf in the new tree. Where that path goes right, the
+/// left sibling covers old leaves only and is shared by both trees; where it goes left, the old
+/// tree has a zero subtree and the new tree has the given sibling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    /// Leaf at index `old_size - 1`; zero when `old_size` is 0
+    pub leaf: Fp,
+    /// Siblings of that leaf in the new tree, from the leaf level up to the root
+    pub siblings: Vec<Fp>,
+}
+
+impl ConsistencyProof {
+    /// Checks that `old_root` is the root of the first `old_size` leaves and `new_root` the
+    /// root of `new_size` leaves that start with them. Every tree extends the empty tree.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let old = MerkleTree::new(vec![10u64, 20, 30]);
+    /// let new = MerkleTree::new(vec![10u64, 20, 30, 40, 50]);
+    /// let proof = new.consistency_proof(3).unwrap();
+    /// assert!(proof.verify(&old.root(), &new.root()));
+    /// assert!(!proof.verify(&new.root(), &new.root()));
+    /// ```
+    pub fn verify(&self, old_root: &Fp, new_root: &Fp) -> bool {
+        self.verify_with(&PoseidonHasher::new(), old_root, new_root)
+    }
+
+    /// Like `verify`, for trees built with `MerkleTree::with_hasher(_, hasher)`.
+    pub fn verify_with<H: TreeHasher>(&self, hasher: &H, old_root: &Fp, new_root: &Fp) -> bool {
+        if self.old_size > self.new_size {
+            return false;
+        }
+        if self.old_size == 0 {
+            return self.siblings.is_empty() && *old_root == Fp::zero();
+        }
+        if self.siblings.len() != depth_for(self.new_size) {
+            return false;
+        }
+        let (old, new) = self.roots(hasher);
+        old == *old_root && new == *new_root
+    }
+
+    /// Root of the old tree implied by the proof.
+    /// Only meaningful once the proof has been checked against a trusted old root.
+    pub fn old_root(&self) -> Fp {
+        self.old_root_with(&PoseidonHasher::new())
+    }
+
+    /// Like `old_root`, for trees built with `MerkleTree::with_hasher(_, hasher)`.
+    pub fn old_root_with<H: TreeHasher>(&self, hasher: &H) -> Fp {
+        if self.old_size == 0 {
+            return Fp::zero();
+        }
+        self.roots(hasher).0
+    }
+
+    /// Folds the path up both trees at once, returning (old root, new root).
+    fn roots<H: TreeHasher>(&self, hasher: &H) -> (Fp, Fp) {
+        let old_depth = depth_for(self.old_size);
+        let mut index = self.old_size - 1;
+        let mut old = self.leaf;
+        let mut new = self.leaf;
+        //root of an all-zero subtree at the current level
+        let mut zero = Fp::zero();
+
+        for (level, &sibling) in self.siblings.iter().enumerate() {
+            if index % 2 == 1 {
+                if level < old_depth {
+                    old = hasher.hash_pair(sibling, old);
+                }
+                new = hasher.hash_pair(sibling, new);
+            } else {
+                if level < old_depth {
+                    old = hasher.hash_pair(old, zero);
+                }
+                new = hasher.hash_pair(new, sibling);
+            }
+            zero = hasher.hash_pair(zero, zero);
+            index /= 2;
+        }
+        (old, new)
+    }
+}
+
+/// Inclusion proof for several leaves at once.
+/// Siblings shared by the paths, or computable from the proven leaves themselves, are sent
+/// once or not at all, so proving k leaves costs far fewer than k * depth hashes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiProof {
+    /// Depth of the tree the leaves are in
+    pub depth: usize,
+    /// Positions of the proven leaves, strictly increasing
+    pub indices: Vec<usize>,
+    /// The proven leaves, in the order of `indices`
+    pub leaves: Vec<Fp>,
+    /// Nodes the verifier cannot compute, level by level from the leaves up and in index
+    /// order within a level
+    pub hashes: Vec<Fp>,
+}
+
+impl MultiProof {
+    /// Checks that every leaf is at its index in the tree with the given root.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let tree = MerkleTree::new((1..=8u64).collect());
+    /// let proof = tree.generate_multiproof(&[1, 2, 3]).unwrap();
+    /// assert!(proof.verify(&tree.root()));
+    /// //leaf 3 is the sibling of leaf 2, so only leaf 0 and the right half's root are sent
+    /// assert_eq!(proof.hashes.len(), 2);
+    /// ```
+    pub fn verify(&self, root: &Fp) -> bool {
+        self.verify_with(&PoseidonHasher::new(), root)
+    }
+
+    /// Like `verify`, for trees built with `MerkleTree::with_hasher(_, hasher)`.
+    pub fn verify_with<H: TreeHasher>(&self, hasher: &H, root: &Fp) -> bool {
+        let width = match 1usize.checked_shl(self.depth as u32) {
+            Some(width) => width,
+            None => return false,
+        };
+        if self.indices.is_empty()
+            || self.indices.len() != self.leaves.len()
+            || self.indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || self.indices.last().is_some_and(|&last| last >= width)
+        {
+            return false;
+        }
+
+        let mut nodes: Vec<(usize, Fp)> = self
+            .indices
+            .iter()
+            .copied()
+            .zip(self.leaves.iter().copied())
+            .collect();
+        let mut hashes = self.hashes.iter().copied();
+
+        for _ in 0..self.depth {
+            let mut parents = Vec::with_capacity(nodes.len());
+            let mut i = 0;
+            while i < nodes.len() {
+                let (index, node) = nodes[i];
+                let parent = match nodes.get(i + 1) {
+                    //both children are known
+                    Some(&(next, right)) if index % 2 == 0 && next == index + 1 => {
+                        i += 1;
+                        hasher.hash_pair(node, right)
+                    },
+                    _ => {
+                        let Some(sibling) = hashes.next() else {
+                            return false;
+                        };
+                        if index % 2 == 0 {
+                            hasher.hash_pair(node, sibling)
+                        } else {
+                            hasher.hash_pair(sibling, node)
+                        }
+                    },
+                };
+                parents.push((index / 2, parent));
+                i += 1;
+            }
+            nodes = parents;
+        }
+
+        hashes.next().is_none() && nodes == [(0, *root)]
+    }
+}
+
+/// Depth of the tree holding `size` leaves, which is padded to the next power of two.
+fn depth_for(size: usize) -> usize {
+    size.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Returns the roots of the all-zero subtrees of heights 0 to `depth` under `hasher`:
+/// `zeros[0]` is the zero leaf and `zeros[l + 1] = hash(zeros[l], zeros[l])`.
+pub fn zero_hashes<H: TreeHasher>(hasher: &H, depth: usize) -> Vec<Fp> {
+    let mut zeros = vec![Fp::zero()];
+    for level in 0..depth {
+        zeros.push(hasher.hash_pair(zeros[level], zeros[level]));
+    }
+    zeros
+}
+
+/// Extends the root of a tree of depth `depth` to the root of a tree of depth
+/// `zeros.len() - 1` holding the same leaves, by hashing it with the zero subtree of each
+/// level in between. The root is always the left child, since the leaves come first.
+///
+/// # Arguments
+/// * `hasher` - The hasher the tree was built with
+/// * `zeros` - `zero_hashes(hasher, target_depth)`
+/// * `root` - Root of the smaller tree
+/// * `depth` - Depth of the smaller tree, at most the target depth
+pub fn pad_root<H: TreeHasher>(hasher: &H, zeros: &[Fp], root: Fp, depth: usize) -> Fp {
+    zeros[depth..zeros.len() - 1]
+        .iter()
+        .fold(root, |node, &zero| hasher.hash_pair(node, zero))
+}
+
+/// Smallest level worth splitting across threads; below it the scheduling costs more than
+/// the hashes it spreads out.
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_LEVEL: usize = 1 << 10;
+
+/// Hashes each pair of nodes of a level's filled prefix into the level above it.
+/// A last node without a right sibling is paired with `zero`, the level's zero subtree.
+/// With the `parallel` feature, large levels are hashed on the rayon thread pool.
+#[cfg_attr(not(feature = "parallel"), allow(unused_variables))]
+fn hash_level<H: TreeHasher>(level: &[Fp], zero: Fp, hasher: &H, parallel: bool) -> Vec<Fp> {
+    let hash = |pair: &[Fp]| hasher.hash_pair(pair[0], pair.get(1).copied().unwrap_or(zero));
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        if parallel && level.len() >= PARALLEL_MIN_LEVEL {
+            return level.par_chunks(2).map(hash).collect();
+        }
+    }
+    level.chunks(2).map(hash).collect()
+}
+
+/// A Merkle tree implementation using Poseidon hash by default, or any other `TreeHasher`.
 /// Supports arbitrary depth (automatically calculated from number of leaves).
-/// Pads with zeros if the number of leaves is not a power of 2.
+/// Pads with zeros if the number of leaves is not a power of 2; the padding is never stored.
 #[derive(Clone, Debug)]
-pub struct MerkleTree {
-    /// All leaves at the bottom level (may include zero-padding)
-    leaves: Vec<Fp>,
-    /// All nodes in the tree, organized by levels (0 = leaves, last = root)
-    /// Each level contains the hashes at that level
-    pub levels: Vec<Vec<Fp>>,
+pub struct MerkleTree<H = PoseidonHasher> {
+    /// The filled prefix of each level, from the leaves (level 0) up to the root. Level `l`
+    /// holds the nodes that cover at least one leaf; every node after them is the zero
+    /// subtree `zeros[l]`. The leaves are stored once and the tree holds fewer than
+    /// 2 * size + depth field elements.
+    levels: Vec<Vec<Fp>>,
+    /// `zeros[l]` is the root of an all-zero subtree of height `l`, for `l` up to `depth`
+    zeros: Vec<Fp>,
+    /// Number of leaves, not counting the padding
+    size: usize,
     /// The depth of the tree (number of levels from leaf to root, not including leaf level)
     depth: usize,
+    /// Position of each non-zero leaf, keyed by its canonical representation (Fp is not
+    /// Hash), if built with `with_index`. A leaf that occurs more than once maps to its first
+    /// position.
+    ///
+    /// Each entry is a 32-byte key and an 8-byte index, and with the hash table's control
+    /// bytes and spare capacity the index costs 47 to 94 bytes per leaf, about as much as the
+    /// 64 bytes per leaf of the levels. Without it, lookups scan the leaves.
+    positions: Option<HashMap<[u8; 32], usize>>,
+    /// Combines two children into their parent
+    hasher: H,
 }
 
 impl MerkleTree {
     /// Creates a new Merkle tree from a list of leaf values.
     /// Leaf values can be either unhashed (u64) or pre-hashed (Fp).
     /// If the number of leaves is not a power of 2, pads with zeros.
+    /// With the `parallel` feature, large levels are hashed across all cores.
     ///
     /// # Arguments
     /// * `leaves` - Vector of leaf values (can be mixed unhashed and hashed)
     ///
     /// # Example
     /// ```
-    /// let tree = MerkleTree::new(vec![10.into(), 20.into(), 30.into()]);
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let tree = MerkleTree::new(vec![10u64, 20, 30]);
+    /// assert_eq!(tree.depth(), 2);
     /// ```
     pub fn new<T: Into<LeafValue>>(leaves: Vec<T>) -> Self {
-        let mut converted_leaves: Vec<Fp> = leaves
+        Self::with_hasher(leaves, PoseidonHasher::new())
+    }
+
+    /// Creates a Merkle tree like `new`, but hashes on the calling thread even when the
+    /// `parallel` feature is enabled, e.g. when already running inside a thread pool.
+    pub fn new_sequential<T: Into<LeafValue>>(leaves: Vec<T>) -> Self {
+        Self::build_from(leaves, PoseidonHasher::new(), false)
+    }
+}
+
+impl<H: TreeHasher> MerkleTree<H> {
+    /// Creates a Merkle tree like `new` whose nodes are combined with `hasher`.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::{services::merkle_tree::Sha256Hasher, MerkleTree};
+    ///
+    /// let tree = MerkleTree::with_hasher(vec![10u64, 20, 30], Sha256Hasher);
+    /// assert_ne!(tree.root(), MerkleTree::new(vec![10u64, 20, 30]).root());
+    /// ```
+    pub fn with_hasher<T: Into<LeafValue>>(leaves: Vec<T>, hasher: H) -> Self {
+        Self::build_from(leaves, hasher, cfg!(feature = "parallel"))
+    }
+
+    fn build_from<T: Into<LeafValue>>(leaves: Vec<T>, hasher: H, parallel: bool) -> Self {
+        let converted_leaves: Vec<Fp> = leaves
             .into_iter()
-            .map(|leaf| match leaf.into() {
-                LeafValue::Unhashed(val) => Fp::from(val),
-                LeafValue::Hashed(fp) => fp,
-            })
+            .map(|leaf| Fp::from(leaf.into()))
             .collect();
+        let size = converted_leaves.len();
+        let depth = depth_for(size);
 
-        // Pad with zeros if not a power of 2
-        let padded_size = converted_leaves.len().next_power_of_two();
-        converted_leaves.resize(padded_size, Fp::zero());
-
-        let depth = (padded_size as f64).log2() as usize;
+        // The padding up to the next power of 2 is represented by the zero subtree hashes
+        let zeros = zero_hashes(&hasher, depth);
 
         let mut tree = MerkleTree {
-            leaves: converted_leaves,
-            levels: Vec::new(),
+            levels: vec![converted_leaves],
+            zeros,
+            size,
             depth,
+            positions: None,
+            hasher,
         };
 
-        tree.build();
+        tree.build_levels(parallel);
         tree
     }
 
-    /// Adds a new leaf to the tree and rebuilds it.
+    /// Adds a new leaf after the last leaf and updates the hashes on its path.
     /// The leaf can be either unhashed (u64) or pre-hashed (Fp).
+    /// When the tree is full it doubles in width first; the new right half is a zero subtree,
+    /// so no existing hash is recomputed.
     ///
     /// # Arguments
     /// * `leaf` - The leaf value to add
     ///
     /// # Example
     /// ```
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let mut tree = MerkleTree::new(vec![10u64, 20, 30]);
     /// tree.add(40u64);
+    /// assert_eq!(tree.num_leaves(), 4);
+    /// assert_eq!(tree.root(), MerkleTree::new(vec![10u64, 20, 30, 40]).root());
     /// ```
     pub fn add<T: Into<LeafValue>>(&mut self, leaf: T) {
-        let fp_leaf = match leaf.into() {
-            LeafValue::Unhashed(val) => Fp::from(val),
-            LeafValue::Hashed(fp) => fp,
-        };
+        let fp_leaf = Fp::from(leaf.into());
 
-        // Remove trailing zeros from padding
-        while self.leaves.last() == Some(&Fp::zero()) && self.leaves.len() > 1 {
-            self.leaves.pop();
+        // The new leaf takes the place of the first padding leaf
+        let index = self.size;
+        if index == self.num_leaves() {
+            self.grow();
         }
+        self.levels[0].push(fp_leaf);
+        self.size += 1;
+        if let (Some(positions), false) = (&mut self.positions, fp_leaf == Fp::zero()) {
+            positions.entry(fp_leaf.to_repr()).or_insert(index);
+        }
+        self.update_path(index);
+    }
 
-        // Add the new leaf
-        self.leaves.push(fp_leaf);
+    /// Indexes the position of every leaf, so `index_of` and `contains` take O(1) instead of
+    /// scanning the leaves, at the memory cost described on the field. The index is kept up
+    /// to date by `add`.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::{Fp, MerkleTree};
+    ///
+    /// let tree = MerkleTree::new(vec![10u64, 20, 30]).with_index();
+    /// assert_eq!(tree.index_of(&Fp::from(30u64)), Some(2));
+    /// ```
+    pub fn with_index(mut self) -> Self {
+        let mut positions = HashMap::with_capacity(self.size);
+        for (position, leaf) in self.levels[0].iter().enumerate() {
+            if *leaf != Fp::zero() {
+                positions.entry(leaf.to_repr()).or_insert(position);
+            }
+        }
+        self.positions = Some(positions);
+        self
+    }
 
-        // Pad to next power of 2
-        let padded_size = self.leaves.len().next_power_of_two();
-        self.leaves.resize(padded_size, Fp::zero());
+    /// Doubles the number of leaves by adding a level above the root; the new right half is
+    /// the zero subtree of the old depth. The new root is set by the next `update_path`.
+    fn grow(&mut self) {
+        let zero = self.zeros[self.depth];
+        self.zeros.push(self.hasher.hash_pair(zero, zero));
+        self.levels.push(Vec::new());
+        self.depth += 1;
+    }
 
-        // Recalculate depth and rebuild
-        self.depth = (padded_size as f64).log2() as usize;
-        self.build();
+    /// Recomputes the ancestors of the leaf at `index`, extending each level's filled prefix
+    /// when the leaf starts a new subtree.
+    fn update_path(&mut self, mut index: usize) {
+        for level in 0..self.depth {
+            let left = index & !1;
+            let parent = self
+                .hasher
+                .hash_pair(self.node(level, left), self.node(level, left + 1));
+            index /= 2;
+            let above = &mut self.levels[level + 1];
+            if index == above.len() {
+                above.push(parent);
+            } else {
+                above[index] = parent;
+            }
+        }
     }
 
     /// Builds the tree by computing all internal nodes from leaves to root.
-    /// Uses Poseidon hash to combine pairs of nodes at each level.
-    fn build(&mut self) {
-        self.levels.clear();
-
+    /// Uses the tree's hasher to combine pairs of nodes at each level.
+    fn build_levels(&mut self, parallel: bool) {
         // Level 0 is the leaves
-        self.levels.push(self.leaves.clone());
+        self.levels.truncate(1);
 
-        // Build each level up to the root
-        let mut current_level = self.leaves.clone();
-
-        for _ in 0..self.depth {
-            let mut next_level = Vec::new();
-
-            // Hash pairs of nodes to create the next level
-            for chunk in current_level.chunks(2) {
-                let left = chunk[0];
-                let right = chunk[1];
-
-                let hash = PoseidonHash::<Fp, P128Pow5T3, ConstantLength<2>, 3, 2>::init()
-                    .hash([left, right]);
-
-                next_level.push(hash);
-            }
-
-            self.levels.push(next_level.clone());
-            current_level = next_level;
+        // Hash pairs of nodes to create each level up to the root
+        for level in 0..self.depth {
+            let next_level =
+                hash_level(&self.levels[level], self.zeros[level], &self.hasher, parallel);
+            self.levels.push(next_level);
         }
     }
 
+    /// Returns the node at `index` of `level` (0 = leaves), including the zero padding.
+    ///
+    /// # Panics
+    /// If `level` is above the root
+    pub fn node(&self, level: usize, index: usize) -> Fp {
+        self.levels[level]
+            .get(index)
+            .copied()
+            .unwrap_or(self.zeros[level])
+    }
+
     /// Returns the root hash of the tree.
     pub fn root(&self) -> Fp {
-        self.levels.last().unwrap()[0]
+        self.node(self.depth, 0)
     }
 
     /// Generates a Merkle proof for the leaf at the given index.
@@ -170,10 +497,14 @@ impl MerkleTree {
     ///
     /// # Example
     /// ```
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let tree = MerkleTree::new(vec![10u64, 20, 30, 40]);
     /// let proof = tree.generate_proof(0).unwrap();
+    /// assert_eq!(proof.root, tree.root());
     /// ```
     pub fn generate_proof(&self, leaf_index: usize) -> Option<MerkleProof> {
-        if leaf_index >= self.leaves.len() {
+        if leaf_index >= self.num_leaves() {
             return None;
         }
 
@@ -196,7 +527,7 @@ impl MerkleTree {
             };
 
             // Get the sibling value
-            let sibling = self.levels[level][sibling_index];
+            let sibling = self.node(level, sibling_index);
             siblings.push(sibling);
 
             // Move to parent index for next level
@@ -204,13 +535,175 @@ impl MerkleTree {
         }
 
         Some(MerkleProof {
-            leaf: self.leaves[leaf_index],
+            leaf: self.node(0, leaf_index),
             siblings,
             directions,
             root: self.root(),
         })
     }
 
+    /// Generates a proof for the leaf at the given index in this tree padded with zero
+    /// subtrees to `depth` levels, as a circuit of that depth expects. The levels above the
+    /// tree's own depth have a zero subtree as sibling and the path on the left; the proof's
+    /// root is `root_at_depth(depth)`.
+    ///
+    /// # Returns
+    /// * `Some(MerkleProof)` with exactly `depth` siblings
+    /// * `None` if the index is out of bounds or the tree is deeper than `depth`
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::MerkleTree;
+    ///
+    /// let tree = MerkleTree::new(vec![10u64, 20]);
+    /// let proof = tree.generate_proof_at_depth(1, 3).unwrap();
+    /// assert_eq!(proof.siblings.len(), 3);
+    /// assert_eq!(proof.root, tree.root_at_depth(3).unwrap());
+    /// ```
+    pub fn generate_proof_at_depth(&self, leaf_index: usize, depth: usize) -> Option<MerkleProof> {
+        if depth < self.depth {
+            return None;
+        }
+        let mut proof = self.generate_proof(leaf_index)?;
+        let zeros = zero_hashes(&self.hasher, depth);
+        proof.siblings.extend_from_slice(&zeros[self.depth..depth]);
+        proof.directions.resize(depth, Fp::zero());
+        proof.root = pad_root(&self.hasher, &zeros, proof.root, self.depth);
+        Some(proof)
+    }
+
+    /// Returns the root of this tree padded with zero subtrees to `depth` levels, or None if
+    /// the tree is deeper than that. At the tree's own depth it is `root()`.
+    pub fn root_at_depth(&self, depth: usize) -> Option<Fp> {
+        if depth < self.depth {
+            return None;
+        }
+        let zeros = zero_hashes(&self.hasher, depth);
+        Some(pad_root(&self.hasher, &zeros, self.root(), self.depth))
+    }
+
+    /// Generates one proof for several leaves, sending each needed sibling only once.
+    ///
+    /// # Arguments
+    /// * `indices` - Leaf indices in any order; duplicates are ignored
+    ///
+    /// # Returns
+    /// * `Some(MultiProof)` with the indices sorted
+    /// * `None` if `indices` is empty or any index is out of bounds
+    pub fn generate_multiproof(&self, indices: &[usize]) -> Option<MultiProof> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() || indices.iter().any(|&i| i >= self.num_leaves()) {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        let mut known = indices.clone();
+        for level in 0..self.depth {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if index % 2 == 0 && known.get(i + 1) == Some(&(index + 1)) {
+                    i += 1;
+                } else {
+                    hashes.push(self.node(level, index ^ 1));
+                }
+                parents.push(index / 2);
+                i += 1;
+            }
+            known = parents;
+        }
+
+        Some(MultiProof {
+            depth: self.depth,
+            leaves: indices.iter().map(|&i| self.node(0, i)).collect(),
+            indices,
+            hashes,
+        })
+    }
+
+    /// Proves that the tree of the first `old_size` leaves is a prefix of this tree.
+    ///
+    /// # Arguments
+    /// * `old_size` - Number of leaves in the earlier tree
+    ///
+    /// # Returns
+    /// * `Some(ConsistencyProof)` from `old_size` to `self.size()`
+    /// * `None` if `old_size` is larger than this tree
+    pub fn consistency_proof(&self, old_size: usize) -> Option<ConsistencyProof> {
+        self.consistency_proof_between(old_size, self.size())
+    }
+
+    /// Proves that the tree of the first `old_size` leaves is a prefix of the tree of the first
+    /// `new_size` leaves, without rebuilding the latter: its nodes are read from the stored
+    /// levels, and only the nodes over leaf `new_size` are hashed again.
+    ///
+    /// # Returns
+    /// * `Some(ConsistencyProof)` from `old_size` to `new_size`
+    /// * `None` unless `old_size <= new_size <= self.size()`
+    pub fn consistency_proof_between(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Option<ConsistencyProof> {
+        if old_size > new_size || new_size > self.size() {
+            return None;
+        }
+        if old_size == 0 {
+            return Some(ConsistencyProof {
+                old_size,
+                new_size,
+                leaf: Fp::zero(),
+                siblings: Vec::new(),
+            });
+        }
+
+        let mut index = old_size - 1;
+        let siblings = (0..depth_for(new_size))
+            .map(|level| {
+                let sibling = self.node_at_size(new_size, level, index ^ 1);
+                index /= 2;
+                sibling
+            })
+            .collect();
+        Some(ConsistencyProof {
+            old_size,
+            new_size,
+            leaf: self.levels[0][old_size - 1],
+            siblings,
+        })
+    }
+
+    /// Returns the node at `index` of `level` in the tree of the first `size` leaves.
+    /// Subtrees entirely before leaf `size` are the stored nodes and subtrees after it are
+    /// zero; only the O(level) nodes over leaf `size` itself are hashed.
+    fn node_at_size(&self, size: usize, level: usize, index: usize) -> Fp {
+        let start = index << level;
+        if size == self.size || start + (1 << level) <= size {
+            self.node(level, index)
+        } else if start >= size {
+            self.zeros[level]
+        } else {
+            self.hasher.hash_pair(
+                self.node_at_size(size, level - 1, 2 * index),
+                self.node_at_size(size, level - 1, 2 * index + 1),
+            )
+        }
+    }
+
+    /// Returns the root the tree had when it held its first `size` leaves, without rebuilding
+    /// it: the old root implied by the consistency proof from `size`.
+    ///
+    /// # Returns
+    /// * `Some(root)` of the first `size` leaves
+    /// * `None` if `size` is larger than this tree
+    pub fn root_at(&self, size: usize) -> Option<Fp> {
+        self.consistency_proof(size)
+            .map(|proof| proof.old_root_with(&self.hasher))
+    }
+
     /// Returns the depth of the tree (number of levels from leaf to root, not including leaf level).
     pub fn depth(&self) -> usize {
         self.depth
@@ -218,18 +711,61 @@ impl MerkleTree {
 
     /// Returns the number of leaves in the tree (including zero-padding).
     pub fn num_leaves(&self) -> usize {
-        self.leaves.len()
+        1 << self.depth
     }
 
-    /// Returns a reference to the leaves (including zero-padding).
+    /// Returns the position of `leaf`, or None if it is not in the tree: in O(1) for a tree
+    /// built `with_index`, otherwise by scanning the leaves.
+    /// Zero is the padding value and is never found. A leaf that occurs more than once is
+    /// found at its first position.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::{Fp, MerkleTree};
+    ///
+    /// let tree = MerkleTree::new(vec![10u64, 20, 30]);
+    /// assert_eq!(tree.index_of(&Fp::from(30u64)), Some(2));
+    /// assert_eq!(tree.index_of(&Fp::zero()), None);
+    /// ```
+    pub fn index_of(&self, leaf: &Fp) -> Option<usize> {
+        match &self.positions {
+            Some(positions) => positions.get(&leaf.to_repr()).copied(),
+            None if *leaf == Fp::zero() => None,
+            None => self.levels[0].iter().position(|l| l == leaf),
+        }
+    }
+
+    /// Returns true if `leaf` is a non-zero leaf of the tree.
+    pub fn contains(&self, leaf: &Fp) -> bool {
+        self.index_of(leaf).is_some()
+    }
+
+    /// Returns the number of leaves, not counting the zero padding after the last one.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the leaves, without the zero padding.
     pub fn leaves(&self) -> &[Fp] {
-        &self.leaves
+        &self.levels[0]
+    }
+
+    /// Returns the hasher that combines nodes.
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Returns the filled prefix of every level, from the leaves (level 0) up to the root.
+    /// Nodes past a level's prefix are zero subtrees; `node` returns them too.
+    pub fn levels(&self) -> &[Vec<Fp>] {
+        &self.levels
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use halo2_gadgets::poseidon::primitives::{ConstantLength, Hash as PoseidonHash, P128Pow5T3};
 
     #[test]
     fn test_tree_creation_power_of_2() {
@@ -247,7 +783,8 @@ mod tests {
 
         assert_eq!(tree.num_leaves(), 4); // Padded to 4
         assert_eq!(tree.depth(), 2);
-        assert_eq!(tree.leaves()[3], Fp::zero()); // Last leaf should be zero
+        assert_eq!(tree.size(), 3);
+        assert_eq!(tree.node(0, 3), Fp::zero()); // Last leaf should be zero
     }
 
     #[test]
@@ -260,6 +797,112 @@ mod tests {
         assert_eq!(tree.depth(), 2);
     }
 
+    #[test]
+    fn test_add_to_empty_tree() {
+        let mut tree = MerkleTree::new(Vec::<Fp>::new());
+        assert_eq!(tree.num_leaves(), 1);
+
+        //the padding leaf of an empty tree is replaced, not kept as a member
+        tree.add(30u64);
+        assert_eq!(tree.leaves(), &[Fp::from(30u64)]);
+        assert_eq!(tree.depth(), 0);
+    }
+
+    #[test]
+    fn test_add_matches_building_from_scratch() {
+        let mut tree = MerkleTree::new(Vec::<Fp>::new());
+        for count in 1..=17u64 {
+            tree.add(count);
+            let rebuilt = MerkleTree::new((1..=count).collect());
+            assert_eq!(tree.levels(), rebuilt.levels(), "{} leaves", count);
+            assert_eq!(tree.depth(), rebuilt.depth());
+        }
+    }
+
+    #[test]
+    fn test_index_of_tracks_built_and_added_leaves() {
+        //lookups agree with and without the index
+        let built = MerkleTree::new(vec![10u64, 20, 30]);
+        for mut tree in [built.clone(), built.with_index()] {
+            assert_eq!(tree.index_of(&Fp::from(20u64)), Some(1));
+            assert!(!tree.contains(&Fp::from(40u64)));
+
+            //growing the tree keeps existing positions
+            tree.add(40u64);
+            tree.add(50u64);
+            assert_eq!(tree.index_of(&Fp::from(40u64)), Some(3));
+            assert_eq!(tree.index_of(&Fp::from(50u64)), Some(4));
+            assert_eq!(tree.index_of(&Fp::from(10u64)), Some(0));
+
+            //the zero padding is not a leaf
+            tree.add(Fp::zero());
+            assert!(!tree.contains(&Fp::zero()));
+        }
+
+        //a repeated leaf is found at its first position
+        let repeated = MerkleTree::new(vec![7u64, 8, 7]);
+        assert_eq!(repeated.index_of(&Fp::from(7u64)), Some(0));
+        assert_eq!(repeated.with_index().index_of(&Fp::from(7u64)), Some(0));
+    }
+
+    #[test]
+    fn test_levels_store_each_node_once() {
+        let tree = MerkleTree::new((1..=1000u64).collect());
+        let nodes: usize = tree.levels().iter().map(Vec::len).sum();
+        assert_eq!(nodes, 2001);
+        assert_eq!(tree.leaves(), tree.levels()[0].as_slice());
+    }
+
+    #[test]
+    fn test_padding_is_not_stored() {
+        //one leaf past a power of two doubles the width, but only the filled prefix is stored
+        let leaves: Vec<u64> = (1..=1025).collect();
+        let mut tree = MerkleTree::new(leaves[..1024].to_vec());
+        tree.add(1025u64);
+        assert_eq!(tree.num_leaves(), 2048);
+        assert_eq!(tree.size(), 1025);
+        let lengths: Vec<usize> = tree.levels().iter().map(Vec::len).collect();
+        assert_eq!(lengths, vec![1025, 513, 257, 129, 65, 33, 17, 9, 5, 3, 2, 1]);
+        assert_eq!(tree.levels(), MerkleTree::new(leaves).levels());
+
+        //nodes past the prefix are zero subtrees
+        let hasher = PoseidonHasher::new();
+        let zero_pair = hasher.hash_pair(Fp::zero(), Fp::zero());
+        assert_eq!(tree.node(0, 2047), Fp::zero());
+        assert_eq!(tree.node(1, 1023), zero_pair);
+        assert_eq!(tree.node(2, 511), hasher.hash_pair(zero_pair, zero_pair));
+        let proof = tree.generate_proof(1024).unwrap();
+        assert_eq!(proof.siblings[..2], [Fp::zero(), zero_pair]);
+        assert!(tree
+            .generate_multiproof(&[1024, 2047])
+            .unwrap()
+            .verify(&tree.root()));
+    }
+
+    #[test]
+    fn test_trees_with_another_hasher() {
+        let leaves: Vec<u64> = (1..=5).collect();
+        let mut tree = MerkleTree::with_hasher(leaves[..4].to_vec(), Sha256Hasher);
+        tree.add(5u64);
+        let rebuilt = MerkleTree::with_hasher(leaves.clone(), Sha256Hasher);
+        assert_eq!(tree.levels(), rebuilt.levels());
+        assert_ne!(tree.root(), MerkleTree::new(leaves).root());
+
+        let level_one = Sha256Hasher.hash_pair(Fp::from(1u64), Fp::from(2u64));
+        assert_eq!(tree.levels()[1][0], level_one);
+
+        //proofs verify only with the hasher the tree was built with
+        let multi = tree.generate_multiproof(&[0, 4]).unwrap();
+        assert!(multi.verify_with(&Sha256Hasher, &tree.root()));
+        assert!(!multi.verify(&tree.root()));
+
+        let old = MerkleTree::with_hasher(vec![1u64, 2, 3], Sha256Hasher);
+        let consistency = tree.consistency_proof(3).unwrap();
+        assert!(consistency.verify_with(&Sha256Hasher, &old.root(), &tree.root()));
+        assert_eq!(consistency.old_root_with(&Sha256Hasher), old.root());
+        assert!(!consistency.verify(&old.root(), &tree.root()));
+    }
+
     #[test]
     fn test_generate_proof() {
         let tree = MerkleTree::new(vec![10u64, 20, 30, 40]);
@@ -280,6 +923,24 @@ mod tests {
         assert!(proof.is_none());
     }
 
+    #[test]
+    fn test_proof_at_depth_matches_explicitly_padded_tree() {
+        let tree = MerkleTree::new(vec![10u64, 20, 30]);
+        let padded = MerkleTree::new(vec![10u64, 20, 30, 0, 0, 0, 0, 0]);
+        assert_eq!(tree.root_at_depth(2), Some(tree.root()));
+        assert_eq!(tree.root_at_depth(3), Some(padded.root()));
+        assert_eq!(tree.root_at_depth(1), None);
+
+        for index in 0..3 {
+            let proof = tree.generate_proof_at_depth(index, 3).unwrap();
+            let expected = padded.generate_proof(index).unwrap();
+            assert_eq!(proof.siblings, expected.siblings);
+            assert_eq!(proof.directions, expected.directions);
+            assert_eq!(proof.root, padded.root());
+        }
+        assert!(tree.generate_proof_at_depth(0, 1).is_none());
+    }
+
     #[test]
     fn test_mixed_leaf_types() {
         // Test using both unhashed and pre-hashed values
@@ -303,6 +964,137 @@ mod tests {
         assert_ne!(root1, root2);
     }
 
+    #[test]
+    fn test_sequential_and_default_builds_agree() {
+        //large enough for the parallel path to split the lowest levels
+        let leaves: Vec<u64> = (1..=3000).collect();
+        let tree = MerkleTree::new(leaves.clone());
+        let sequential = MerkleTree::new_sequential(leaves);
+        assert_eq!(tree.levels(), sequential.levels());
+        assert_eq!(tree.root(), sequential.root());
+    }
+
+    #[test]
+    fn test_multiproof_for_every_subset() {
+        let tree = MerkleTree::new((1..=8u64).collect());
+        for mask in 1u32..256 {
+            let indices: Vec<usize> = (0..8).filter(|i| mask & (1 << i) != 0).collect();
+            let proof = tree.generate_multiproof(&indices).unwrap();
+            assert!(proof.verify(&tree.root()), "{:?}", indices);
+            assert!(proof.hashes.len() <= indices.len() * tree.depth());
+        }
+
+        //all leaves need no hashes at all
+        let all = tree
+            .generate_multiproof(&[7, 0, 1, 2, 3, 4, 5, 6, 6])
+            .unwrap();
+        assert_eq!(all.indices, (0..8).collect::<Vec<_>>());
+        assert!(all.hashes.is_empty());
+    }
+
+    #[test]
+    fn test_multiproof_shares_siblings() {
+        let tree = MerkleTree::new((1..=1024u64).collect());
+        let indices: Vec<usize> = (0..100).map(|i| i * 7).collect();
+        let proof = tree.generate_multiproof(&indices).unwrap();
+        assert!(proof.verify(&tree.root()));
+        assert!(proof.hashes.len() < indices.len() * tree.depth() / 2);
+    }
+
+    #[test]
+    fn test_multiproof_rejects_tampering() {
+        let tree = MerkleTree::new((1..=8u64).collect());
+        let proof = tree.generate_multiproof(&[1, 4, 6]).unwrap();
+        assert!(!proof.verify(&MerkleTree::new((2..=9u64).collect()).root()));
+
+        let mut tampered = proof.clone();
+        tampered.leaves[1] = Fp::from(99u64);
+        assert!(!tampered.verify(&tree.root()));
+        tampered = proof.clone();
+        tampered.indices = vec![1, 5, 6];
+        assert!(!tampered.verify(&tree.root()));
+        tampered = proof.clone();
+        tampered.indices = vec![4, 1, 6];
+        assert!(!tampered.verify(&tree.root()), "indices must be sorted");
+        tampered = proof.clone();
+        tampered.hashes.push(Fp::zero());
+        assert!(!tampered.verify(&tree.root()), "extra hashes are rejected");
+        tampered = proof;
+        tampered.hashes.pop();
+        assert!(!tampered.verify(&tree.root()));
+
+        assert!(tree.generate_multiproof(&[]).is_none());
+        assert!(tree.generate_multiproof(&[8]).is_none());
+    }
+
+    #[test]
+    fn test_consistency_proofs_between_all_sizes() {
+        let leaves: Vec<u64> = (1..=9).collect();
+        for new_size in 0..=leaves.len() {
+            let new = MerkleTree::new(leaves[..new_size].to_vec());
+            for old_size in 0..=new_size {
+                let old = MerkleTree::new(leaves[..old_size].to_vec());
+                let proof = new.consistency_proof(old_size).unwrap();
+                assert!(proof.verify(&old.root(), &new.root()), "{} -> {}", old_size, new_size);
+                assert_eq!(proof.old_root(), old.root());
+                assert_eq!(new.root_at(old_size), Some(old.root()));
+            }
+            assert!(new.consistency_proof(new_size + 1).is_none());
+            assert!(new.root_at(new_size + 1).is_none());
+        }
+    }
+
+    #[test]
+    fn test_consistency_proof_between_earlier_sizes_matches_rebuilt_tree() {
+        let leaves: Vec<u64> = (1..=13).collect();
+        let tree = MerkleTree::new(leaves.clone());
+        for new_size in 0..=leaves.len() {
+            let rebuilt = MerkleTree::new(leaves[..new_size].to_vec());
+            for old_size in 0..=new_size {
+                assert_eq!(
+                    tree.consistency_proof_between(old_size, new_size),
+                    rebuilt.consistency_proof(old_size),
+                    "{} -> {}",
+                    old_size,
+                    new_size
+                );
+            }
+            assert!(tree
+                .consistency_proof_between(new_size + 1, new_size)
+                .is_none());
+        }
+        assert!(tree
+            .consistency_proof_between(0, leaves.len() + 1)
+            .is_none());
+    }
+
+    #[test]
+    fn test_consistency_proof_rejects_rewritten_history() {
+        let old = MerkleTree::new(vec![10u64, 20, 30]);
+        let new = MerkleTree::new(vec![10u64, 20, 30, 40, 50]);
+        let rewritten = MerkleTree::new(vec![10u64, 21, 30, 40, 50]);
+        let proof = new.consistency_proof(3).unwrap();
+
+        assert!(proof.verify(&old.root(), &new.root()));
+        assert!(!proof.verify(&old.root(), &rewritten.root()));
+        assert!(!rewritten
+            .consistency_proof(3)
+            .unwrap()
+            .verify(&old.root(), &rewritten.root()));
+
+        //the proof only holds for the sizes it was made for
+        let mut resized = proof.clone();
+        resized.old_size = 2;
+        assert!(!resized.verify(&old.root(), &new.root()));
+        resized = proof.clone();
+        resized.new_size = 9;
+        assert!(!resized.verify(&old.root(), &new.root()));
+
+        let mut tampered = proof;
+        tampered.siblings[0] += Fp::one();
+        assert!(!tampered.verify(&old.root(), &new.root()));
+    }
+
     #[test]
     fn test_proof_verification() {
         // Create a simple tree and verify the proof manually
@@ -1,72 +1,344 @@
-use crate::services::merkle_tree::MerkleTree;
-use halo2_gadgets::poseidon::primitives::{ConstantLength, Hash as PoseidonHash, P128Pow5T3};
+use crate::config::{Config, TreeConfig};
+use crate::error::ApiError;
+pub use crate::services::fp_hex::{fp_to_hex, parse_fp_hex, FpHex};
+use crate::services::merkle_tree::{
+    pad_root, zero_hashes, ConsistencyProof, LeafValue, MerkleProof, MerkleTree, MultiProof,
+    PoseidonHasher, TreeHasher,
+};
+use crate::services::tree_events::EVENT_BUFFER;
+pub use crate::services::tree_events::{FeedLagged, LeafEvent, LeafSubscription};
+pub use crate::services::tree_head::{PublicKeyResponse, SignedTreeHead};
+use ed25519_dalek::SigningKey;
+use ff::PrimeField;
 use halo2_proofs::pasta::Fp;
 use plotters::prelude::*;
+use rand_core::OsRng;
 use rust_api::prelude::*;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 
 /// Response type for the tree endpoint.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TreeResponse {
-    pub data: String,
+    /// Position of the new leaf, or of the existing one for an idempotent registration
+    pub index: usize,
+    /// Root of the tree after the change
+    pub data: FpHex,
+    /// Signed checkpoint of the tree after the change
+    pub head: SignedTreeHead,
 }
 
+/// Response type for the tree metadata endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeInfoResponse {
+    pub root: FpHex,
+    /// Number of levels above the leaves
+    pub depth: usize,
+    /// Number of registered leaves
+    pub num_leaves: usize,
+    /// Number of leaves including the zero padding up to the next power of two
+    pub padded_leaves: usize,
+    /// Maximum number of leaves the tree accepts (2^`tree.depth`)
+    pub capacity: usize,
+}
+
+/// Query parameters of the leaf listing endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LeavesQuery {
+    /// Index of the first leaf to return
+    #[serde(default)]
+    pub offset: usize,
+    /// Maximum number of leaves to return; defaults to and is capped at MAX_LEAVES_PAGE
+    pub limit: Option<usize>,
+}
+
+/// One page of the registered leaves, in tree order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeavesResponse {
+    /// Root of the tree the page was read from
+    pub root: FpHex,
+    pub offset: usize,
+    /// Total number of registered leaves
+    pub total: usize,
+    pub leaves: Vec<FpHex>,
+}
+
+/// Largest page returned by the leaf listing endpoint.
+pub const MAX_LEAVES_PAGE: usize = 1000;
+
 /// Response type for the tree visualization endpoint.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TreeVisualizationResponse {
     pub image_url: String,
 }
 
+/// Merkle path of one leaf, used by clients to build or check a membership proof offline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MerklePathResponse {
+    /// Position of the leaf in the tree
+    pub index: usize,
+    pub leaf: FpHex,
+    pub root: FpHex,
+    /// Sibling nodes from the leaf level up to the root
+    pub siblings: Vec<FpHex>,
+    /// 0 = current node is the left child, 1 = right child
+    pub directions: Vec<u8>,
+}
+
+impl MerklePathResponse {
+    /// Encodes a Merkle proof for the leaf at `index`.
+    pub fn from_proof(index: usize, proof: &MerkleProof) -> Self {
+        Self {
+            index,
+            leaf: FpHex(proof.leaf),
+            root: FpHex(proof.root),
+            siblings: proof.siblings.iter().copied().map(FpHex).collect(),
+            directions: proof
+                .directions
+                .iter()
+                .map(|d| u8::from(*d == Fp::one()))
+                .collect(),
+        }
+    }
+}
+
+/// Request body for the multiproof endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiProofRequest {
+    /// Indices of registered leaves, at most MAX_MULTIPROOF_LEAVES; duplicates are ignored
+    pub indices: Vec<usize>,
+}
+
+/// Largest number of leaves one multiproof may cover.
+pub const MAX_MULTIPROOF_LEAVES: usize = 1000;
+
+/// Inclusion proof for several leaves, with shared siblings sent once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiProofResponse {
+    pub root: FpHex,
+    pub depth: usize,
+    /// Positions of the proven leaves, sorted
+    pub indices: Vec<usize>,
+    pub leaves: Vec<FpHex>,
+    /// Nodes the verifier cannot compute from the leaves, level by level from the leaves up
+    pub hashes: Vec<FpHex>,
+}
+
+impl MultiProofResponse {
+    /// Encodes a multiproof against `root`.
+    pub fn from_proof(proof: &MultiProof, root: Fp) -> Self {
+        Self {
+            root: FpHex(root),
+            depth: proof.depth,
+            indices: proof.indices.clone(),
+            leaves: proof.leaves.iter().copied().map(FpHex).collect(),
+            hashes: proof.hashes.iter().copied().map(FpHex).collect(),
+        }
+    }
+
+    /// Decodes the proof; verify it with `proof().verify(&root)` against a trusted root.
+    pub fn proof(&self) -> MultiProof {
+        MultiProof {
+            depth: self.depth,
+            indices: self.indices.clone(),
+            leaves: self.leaves.iter().map(|l| l.0).collect(),
+            hashes: self.hashes.iter().map(|h| h.0).collect(),
+        }
+    }
+}
+
+/// Query parameters of the consistency proof endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsistencyQuery {
+    /// Size of the earlier tree
+    pub from: usize,
+    /// Size of the later tree; defaults to the current size
+    pub to: Option<usize>,
+}
+
+/// Proof that the tree of `from` leaves is a prefix of the tree of `to` leaves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyResponse {
+    pub from: usize,
+    pub to: usize,
+    /// Root of the first `from` leaves
+    pub from_root: FpHex,
+    /// Root of the first `to` leaves
+    pub to_root: FpHex,
+    /// Leaf at index `from - 1` (zero when `from` is 0)
+    pub leaf: FpHex,
+    /// Siblings of that leaf in the `to` tree, from the leaf level up to the root
+    pub siblings: Vec<FpHex>,
+}
+
+impl ConsistencyResponse {
+    /// Encodes a consistency proof together with the two roots it links.
+    pub fn from_proof(proof: &ConsistencyProof, to_root: Fp) -> Self {
+        Self {
+            from: proof.old_size,
+            to: proof.new_size,
+            from_root: FpHex(proof.old_root()),
+            to_root: FpHex(to_root),
+            leaf: FpHex(proof.leaf),
+            siblings: proof.siblings.iter().copied().map(FpHex).collect(),
+        }
+    }
+
+    /// Decodes the proof. Auditors verify it against roots they recorded themselves, not
+    /// against `from_root` and `to_root`.
+    pub fn proof(&self) -> ConsistencyProof {
+        ConsistencyProof {
+            old_size: self.from,
+            new_size: self.to,
+            leaf: self.leaf.0,
+            siblings: self.siblings.iter().map(|s| s.0).collect(),
+        }
+    }
+}
+
 /// Request body for registering a commitment in the tree.
 /// The client computes `commitment = Poseidon(secret)` locally and sends only this value.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterRequest {
-    /// Hex-encoded Fp field element representing Poseidon(secret)
-    pub commitment: String,
+    /// Poseidon(secret) as a hex field element; must not be zero
+    pub commitment: FpHex,
+    /// Return the existing leaf instead of 409 `duplicate_commitment` if the commitment is
+    /// already registered, so a client can safely retry
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+/// Request body for importing a set of members at once.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportRequest {
+    /// Commitments in the order they are appended to the tree
+    pub commitments: Vec<FpHex>,
+    /// Skip commitments that are already registered instead of rejecting the import with 409
+    /// `duplicate_commitment`, so a client can safely retry
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+/// Response type for the import endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResponse {
+    /// Number of commitments appended
+    pub imported: usize,
+    /// Number of members in the tree after the import
+    pub num_members: usize,
+    /// Root after the import
+    pub root: FpHex,
+    /// Signed checkpoint of the tree after the import
+    pub head: SignedTreeHead,
 }
 
 pub struct MerkleTreeService {
     tree: Mutex<MerkleTree>,
+    /// Most recent roots with the depth of the tree they are the root of, oldest first; the
+    /// last entry is the current root. Only updated while the tree lock is held.
+    root_history: Mutex<VecDeque<(Fp, usize)>>,
+    /// Leaf insertions, published while the tree lock is held so they arrive in index order
+    events: broadcast::Sender<LeafEvent>,
+    /// Key tree heads are signed with
+    signing_key: SigningKey,
+    config: TreeConfig,
+    /// Directory tree visualizations are written to, served at `/static`
+    storage_path: PathBuf,
 }
 
 impl Injectable for MerkleTreeService {}
 
 /// Computes Poseidon(secret) natively, returning the commitment as Fp.
 pub fn poseidon_commit(secret: u64) -> Fp {
-    PoseidonHash::<Fp, P128Pow5T3, ConstantLength<2>, 3, 2>::init()
-        .hash([Fp::from(secret), Fp::zero()])
+    poseidon_commit_attribute(secret, 0)
+}
+
+/// Computes Poseidon(secret, attribute) natively, returning a commitment that binds an
+/// attribute (age, balance, ...) to the member's secret. Used with the range-gated circuit;
+/// an attribute of 0 yields the same commitment as `poseidon_commit`.
+pub fn poseidon_commit_attribute(secret: u64, attribute: u64) -> Fp {
+    PoseidonHasher::new().hash_pair(Fp::from(secret), Fp::from(attribute))
+}
+
+/// 409 for a leaf that is already in the tree, so one member cannot hold two leaves.
+/// For a commitment repeated within an import, `index` is where its first copy is or goes.
+fn duplicate_commitment(index: usize) -> ApiError {
+    ApiError::new(
+        StatusCode::CONFLICT,
+        "duplicate_commitment",
+        format!("the commitment is already the leaf at index {}", index),
+    )
 }
 
-/// Parses a 0x-prefixed or raw hex string into an Fp field element.
-/// Expects 32 bytes (64 hex chars) in little-endian byte order.
-pub fn parse_fp_hex(hex: &str) -> Option<Fp> {
-    use ff::PrimeField;
-    let hex = hex.trim_start_matches("0x");
-    let bytes = (0..hex.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
-        .collect::<Option<Vec<u8>>>()?;
-    if bytes.len() != 32 {
-        return None;
-    }
-    let arr: [u8; 32] = bytes.try_into().ok()?;
-    Fp::from_repr(arr).into()
+/// LeafEvents for the leaves of `tree` in `indices`, each carrying the root the tree had
+/// right after that leaf was appended.
+fn leaf_events(
+    tree: &MerkleTree,
+    indices: std::ops::Range<usize>,
+) -> impl Iterator<Item = LeafEvent> + '_ {
+    indices.map(|index| LeafEvent {
+        index,
+        commitment: FpHex(tree.leaves()[index]),
+        root: FpHex(tree.root_at(index + 1).expect("the leaf is in the tree")),
+    })
+}
+
+/// Parses one hex commitment per line, skipping blank lines and `#` comments.
+/// This is the format of `tree.import_file` and of `zk-cli tree build --leaves`.
+///
+/// # Returns
+/// The commitments in file order, or the 1-based line number of the first invalid one
+pub fn parse_commitment_list(contents: &str) -> std::result::Result<Vec<Fp>, usize> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| parse_fp_hex(line).map_err(|_| i + 1))
+        .collect()
+}
+
+impl Default for MerkleTreeService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MerkleTreeService {
-    /// Creates a new MerkleTreeService seeded with pre-computed commitments.
-    /// Secrets 42, 99, 7, 13, 55, 77, 100, 200 are registered as Poseidon commitments.
-    /// The corresponding secrets can be used to generate valid ZK proofs.
+    /// Creates a new MerkleTreeService with an empty tree and the default configuration.
     pub fn new() -> Self {
-        let seed_secrets: Vec<u64> = vec![42, 99, 7, 13, 55, 77, 100, 200];
-        let commitments: Vec<Fp> = seed_secrets.iter().map(|&s| poseidon_commit(s)).collect();
-        let tree = MerkleTree::new(commitments);
+        Self::from_config(&Config::default())
+    }
+
+    /// Creates a MerkleTreeService with an empty tree, limited to `config.tree.depth`.
+    pub fn from_config(config: &Config) -> Self {
+        Self::with_members(config, Vec::new())
+    }
+
+    /// Creates a MerkleTreeService whose tree holds the given commitments, e.g. from
+    /// `Config::load_initial_members`. The caller ensures they fit in `config.tree.depth`.
+    pub fn with_members(config: &Config, members: Vec<Fp>) -> Self {
+        let tree = MerkleTree::new(members).with_index();
         Self {
+            root_history: Mutex::new(VecDeque::from([(tree.root(), tree.depth())])),
             tree: Mutex::new(tree),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            signing_key: SigningKey::generate(&mut OsRng),
+            config: config.tree.clone(),
+            storage_path: config.storage.path.clone(),
         }
     }
 
+    /// Signs tree heads with `key` instead of the key generated at construction, e.g. the one
+    /// from `Config::load_signing_key`.
+    pub fn with_signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = key;
+        self
+    }
+
     /// Registers a new commitment (Poseidon hash of a secret) in the tree.
     /// The caller computes `commitment = Poseidon(secret)` and sends only the commitment.
     /// Returns the new root hash after the tree is rebuilt.
@@ -75,14 +347,22 @@ impl MerkleTreeService {
     /// * `commitment` - The Fp commitment to register
     ///
     /// # Returns
-    /// TreeResponse containing the new root hash as a hex string
-    pub fn register_commitment(&self, commitment: Fp) -> TreeResponse {
-        self.with_tree_mut(|tree| {
-            tree.add(commitment);
-            TreeResponse {
-                data: format!("{:?}", tree.root()),
-            }
-        })
+    /// TreeResponse containing the leaf index and new root hash, 400 `zero_commitment` or
+    /// 409 `duplicate_commitment`
+    pub fn register_commitment(
+        &self,
+        commitment: Fp,
+    ) -> std::result::Result<TreeResponse, ApiError> {
+        self.insert(commitment, false)
+    }
+
+    /// Registers a commitment like `register_commitment`, but if it is already in the tree
+    /// returns its existing index with the current root and head instead of failing.
+    pub fn register_commitment_idempotent(
+        &self,
+        commitment: Fp,
+    ) -> std::result::Result<TreeResponse, ApiError> {
+        self.insert(commitment, true)
     }
 
     /// Adds a new leaf value to the Merkle tree and rebuilds it.
@@ -92,33 +372,393 @@ impl MerkleTreeService {
     /// * `value` - The u64 value to add as a leaf
     ///
     /// # Returns
-    /// TreeResponse containing the new root hash as a hex string
-    pub fn add_to_tree(&self, value: u64) -> TreeResponse {
+    /// TreeResponse containing the new root hash as a hex field element
+    pub fn add_to_tree(&self, value: u64) -> std::result::Result<TreeResponse, ApiError> {
+        self.insert(value, false)
+    }
+
+    /// Appends a set of commitments to the tree in one rebuild.
+    /// Either every commitment is added or, if they do not all fit or one is a duplicate,
+    /// none is.
+    ///
+    /// # Arguments
+    /// * `commitments` - The Fp commitments to append, in order
+    ///
+    /// # Returns
+    /// ImportResponse with the new member count and root, 400 `zero_commitment`, 409
+    /// `duplicate_commitment` if a commitment is already in the tree or repeated in the batch,
+    /// or 422 `tree_full`
+    pub fn import_commitments(
+        &self,
+        commitments: &[Fp],
+    ) -> std::result::Result<ImportResponse, ApiError> {
+        self.import(commitments, false)
+    }
+
+    /// Imports commitments like `import_commitments`, but skips those already in the tree
+    /// instead of failing, so an import can be retried after a lost response. `imported`
+    /// counts only the commitments that were appended.
+    pub fn import_commitments_idempotent(
+        &self,
+        commitments: &[Fp],
+    ) -> std::result::Result<ImportResponse, ApiError> {
+        self.import(commitments, true)
+    }
+
+    /// Appends the commitments that are not in the tree yet, or with `idempotent` unset
+    /// rejects the batch if any of them is.
+    fn import(
+        &self,
+        commitments: &[Fp],
+        idempotent: bool,
+    ) -> std::result::Result<ImportResponse, ApiError> {
+        if let Some(i) = commitments.iter().position(|c| *c == Fp::zero()) {
+            return Err(ApiError::bad_request(
+                "zero_commitment",
+                format!("commitment {} is zero, which is reserved for padding", i),
+            ));
+        }
+        let capacity = self.config.capacity();
+        self.with_tree_mut(|tree| {
+            let count = tree.size();
+            let mut appended = Vec::with_capacity(commitments.len());
+            let mut batch = HashMap::with_capacity(commitments.len());
+            for commitment in commitments {
+                let existing = tree.index_of(commitment);
+                if let (Some(index), false) = (existing, idempotent) {
+                    return Err(duplicate_commitment(index));
+                }
+                //a repeat is reported at the index its first copy has or is appended at
+                let index = existing.unwrap_or(count + appended.len());
+                if let Some(first) = batch.insert(commitment.to_repr(), index) {
+                    return Err(duplicate_commitment(first));
+                }
+                if existing.is_none() {
+                    appended.push(*commitment);
+                }
+            }
+
+            //checked after skipping known commitments so a retry succeeds on a full tree
+            if count + appended.len() > capacity {
+                return Err(ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "tree_full",
+                    format!(
+                        "{} commitments do not fit: the tree holds {} of {} leaves",
+                        appended.len(),
+                        count,
+                        capacity
+                    ),
+                ));
+            }
+
+            if !appended.is_empty() {
+                let mut members = tree.leaves()[..count].to_vec();
+                members.extend_from_slice(&appended);
+                *tree = MerkleTree::new(members).with_index();
+                self.publish(tree, count..count + appended.len());
+            }
+            Ok(ImportResponse {
+                imported: appended.len(),
+                num_members: count + appended.len(),
+                root: FpHex(tree.root()),
+                head: self.sign_head(tree),
+            })
+        })?
+    }
+
+    /// Appends a non-zero leaf unless the tree already holds 2^depth leaves.
+    /// A leaf that is already in the tree is rejected, or with `idempotent` returned as is.
+    fn insert<T: Into<LeafValue>>(
+        &self,
+        leaf: T,
+        idempotent: bool,
+    ) -> std::result::Result<TreeResponse, ApiError> {
+        let leaf = Fp::from(leaf.into());
+        //a zero leaf cannot be told apart from padding, so it would never be found again
+        if leaf == Fp::zero() {
+            return Err(ApiError::bad_request(
+                "zero_commitment",
+                "the zero commitment is reserved for padding",
+            ));
+        }
+
+        let capacity = self.config.capacity();
         self.with_tree_mut(|tree| {
-            tree.add(value);
-            TreeResponse {
-                data: format!("{:?}", tree.root()),
+            //checked before capacity so an idempotent retry succeeds on a full tree
+            if let Some(existing) = tree.index_of(&leaf) {
+                if !idempotent {
+                    return Err(duplicate_commitment(existing));
+                }
+                return Ok(TreeResponse {
+                    index: existing,
+                    data: FpHex(tree.root()),
+                    head: self.sign_head(tree),
+                });
+            }
+
+            let index = tree.size();
+            if index >= capacity {
+                return Err(ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "tree_full",
+                    format!("the tree is full ({} leaves)", capacity),
+                ));
             }
+            tree.add(leaf);
+            self.publish(tree, index..index + 1);
+            Ok(TreeResponse {
+                index,
+                data: FpHex(tree.root()),
+                head: self.sign_head(tree),
+            })
+        })?
+    }
+
+    /// Returns the current size and root, signed with the server's key.
+    pub fn signed_tree_head(&self) -> std::result::Result<SignedTreeHead, ApiError> {
+        self.with_tree(|tree| self.sign_head(tree))
+    }
+
+    /// Returns the public key that tree heads verify against.
+    pub fn public_key(&self) -> PublicKeyResponse {
+        PublicKeyResponse::new(&self.signing_key.verifying_key())
+    }
+
+    /// Signs the size and root of `tree`; called with the tree lock held so the two match.
+    fn sign_head(&self, tree: &MerkleTree) -> SignedTreeHead {
+        SignedTreeHead::sign(&self.signing_key, tree.size(), tree.root())
+    }
+
+    /// Sends a LeafEvent for each leaf in `indices`. Having no subscribers is not an error.
+    fn publish(&self, tree: &MerkleTree, indices: std::ops::Range<usize>) {
+        for event in leaf_events(tree, indices) {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Subscribes to leaf insertions starting at leaf `from`.
+    /// Leaves already in the tree are replayed first, each with the root right after it was
+    /// appended; every later insertion follows as it happens. Subscribing past the last leaf
+    /// waits for it.
+    ///
+    /// # Arguments
+    /// * `from` - Index of the first leaf to deliver, e.g. one past the last leaf a client saw
+    ///
+    /// # Returns
+    /// LeafSubscription yielding each leaf from `from` onwards exactly once, in index order
+    pub fn subscribe(
+        self: &Arc<Self>,
+        from: usize,
+    ) -> std::result::Result<LeafSubscription, ApiError> {
+        self.with_tree(|tree| {
+            //subscribing under the tree lock means no insertion falls between the backlog and
+            //the first live event; the backlog itself is read later, a page at a time
+            let receiver = self.events.subscribe();
+            LeafSubscription::new(Arc::clone(self), from, tree.size(), receiver)
+        })
+    }
+
+    /// Returns the LeafEvents for the leaves in `indices`, which must be in the tree.
+    /// Used by LeafSubscription to page through its backlog.
+    pub(crate) fn leaf_events(
+        &self,
+        indices: std::ops::Range<usize>,
+    ) -> std::result::Result<Vec<LeafEvent>, ApiError> {
+        self.with_tree(|tree| leaf_events(tree, indices).collect())
+    }
+
+    /// Returns the configured maximum depth (`tree.depth`); the tree never grows deeper.
+    pub fn max_depth(&self) -> usize {
+        self.config.depth
+    }
+
+    /// Describes the current tree: root, depth, and real vs padded leaf counts.
+    pub fn tree_info(&self) -> std::result::Result<TreeInfoResponse, ApiError> {
+        let capacity = self.config.capacity();
+        self.with_tree(|tree| TreeInfoResponse {
+            root: FpHex(tree.root()),
+            depth: tree.depth(),
+            num_leaves: tree.size(),
+            padded_leaves: tree.num_leaves(),
+            capacity,
+        })
+    }
+
+    /// Returns a page of registered leaves (padding excluded), so clients can mirror the
+    /// membership set.
+    ///
+    /// # Arguments
+    /// * `query` - Offset of the first leaf and page size (at most MAX_LEAVES_PAGE)
+    ///
+    /// # Returns
+    /// LeavesResponse with the leaves and the root they belong to; empty past the last leaf
+    pub fn leaves(&self, query: &LeavesQuery) -> std::result::Result<LeavesResponse, ApiError> {
+        let limit = query.limit.unwrap_or(MAX_LEAVES_PAGE).min(MAX_LEAVES_PAGE);
+        self.with_tree(|tree| {
+            let total = tree.size();
+            let start = query.offset.min(total);
+            let end = start.saturating_add(limit).min(total);
+            LeavesResponse {
+                root: FpHex(tree.root()),
+                offset: query.offset,
+                total,
+                leaves: tree.leaves()[start..end]
+                    .iter()
+                    .copied()
+                    .map(FpHex)
+                    .collect(),
+            }
+        })
+    }
+
+    /// Returns one inclusion proof for several registered leaves.
+    ///
+    /// # Arguments
+    /// * `request` - Indices of the leaves to prove
+    ///
+    /// # Returns
+    /// MultiProofResponse against the current root, or 400 `invalid_indices` if there are no
+    /// indices, more than MAX_MULTIPROOF_LEAVES, or one past the last registered leaf
+    pub fn multiproof(
+        &self,
+        request: &MultiProofRequest,
+    ) -> std::result::Result<MultiProofResponse, ApiError> {
+        if request.indices.is_empty() || request.indices.len() > MAX_MULTIPROOF_LEAVES {
+            return Err(ApiError::bad_request(
+                "invalid_indices",
+                format!(
+                    "expected 1 to {} indices, got {}",
+                    MAX_MULTIPROOF_LEAVES,
+                    request.indices.len()
+                ),
+            ));
+        }
+        self.with_tree(|tree| {
+            let size = tree.size();
+            if let Some(&index) = request.indices.iter().find(|&&i| i >= size) {
+                return Err(ApiError::bad_request(
+                    "invalid_indices",
+                    format!("index {} is past the last leaf (the tree has {})", index, size),
+                ));
+            }
+            let proof = tree
+                .generate_multiproof(&request.indices)
+                .expect("indices were checked");
+            Ok(MultiProofResponse::from_proof(&proof, tree.root()))
+        })?
+    }
+
+    /// Proves that the tree was only appended to between two sizes.
+    ///
+    /// # Arguments
+    /// * `query` - The earlier size and the later size (the current size if omitted)
+    ///
+    /// # Returns
+    /// ConsistencyResponse, or 400 `invalid_range` unless `from <= to <= current size`
+    pub fn consistency_proof(
+        &self,
+        query: &ConsistencyQuery,
+    ) -> std::result::Result<ConsistencyResponse, ApiError> {
+        self.with_tree(|tree| {
+            let size = tree.size();
+            let to = query.to.unwrap_or(size);
+            if query.from > to || to > size {
+                return Err(ApiError::bad_request(
+                    "invalid_range",
+                    format!(
+                        "expected from <= to <= {} (the current size), got from={} to={}",
+                        size, query.from, to
+                    ),
+                ));
+            }
+
+            //an earlier size is read from the stored levels rather than rebuilt, so the lock
+            //is held for O(depth) hashes
+            let proof = tree
+                .consistency_proof_between(query.from, to)
+                .expect("from <= to <= size was checked");
+            let root = tree.root_at(to).expect("to <= size was checked");
+            Ok(ConsistencyResponse::from_proof(&proof, root))
+        })?
+    }
+
+    /// Returns true if `root` is the current root or one of the `root_history_size` most
+    /// recent roots, so proofs made just before a registration still verify.
+    pub fn is_known_root(&self, root: &Fp) -> std::result::Result<bool, ApiError> {
+        Ok(self
+            .root_history
+            .lock()?
+            .iter()
+            .any(|(known, _)| known == root))
+    }
+
+    /// Like `is_known_root`, for a root padded with zero subtrees to `depth` levels as
+    /// circuits of that depth prove against (see `MerkleTree::root_at_depth`).
+    pub fn is_known_root_at_depth(
+        &self,
+        root: &Fp,
+        depth: usize,
+    ) -> std::result::Result<bool, ApiError> {
+        let hasher = PoseidonHasher::new();
+        let zeros = zero_hashes(&hasher, depth);
+        Ok(self
+            .root_history
+            .lock()?
+            .iter()
+            .any(|&(known, known_depth)| {
+                known_depth <= depth && pad_root(&hasher, &zeros, known, known_depth) == *root
+            }))
+    }
+
+    /// Returns the Merkle path of a registered commitment.
+    ///
+    /// # Arguments
+    /// * `commitment` - The Fp commitment whose path to return
+    ///
+    /// # Returns
+    /// MerklePathResponse against the current root, or 404 `commitment_not_in_tree`
+    pub fn proof_path(&self, commitment: Fp) -> std::result::Result<MerklePathResponse, ApiError> {
+        self.with_tree(|tree| {
+            let index = tree.index_of(&commitment)?;
+            let proof = tree.generate_proof(index)?;
+            Some(MerklePathResponse::from_proof(index, &proof))
+        })?
+        .ok_or_else(|| {
+            ApiError::not_found("commitment_not_in_tree", "commitment is not in the tree")
         })
     }
 
     /// Returns a read-only reference to the MerkleTree.
     /// Note: This requires locking the mutex. Use carefully to avoid deadlocks.
-    pub fn with_tree<F, R>(&self, f: F) -> R
+    /// Fails if a previous holder of the lock panicked, since the tree may be half-updated.
+    pub fn with_tree<F, R>(&self, f: F) -> std::result::Result<R, ApiError>
     where
         F: FnOnce(&MerkleTree) -> R,
     {
-        let tree = self.tree.lock().unwrap();
-        f(&tree)
+        let tree = self.tree.lock()?;
+        Ok(f(&tree))
     }
 
     /// Provides mutable access to the tree through a closure.
-    pub fn with_tree_mut<F, R>(&self, f: F) -> R
+    /// The resulting root is recorded in the root history if it changed.
+    /// Fails if a previous holder of the lock panicked, since the tree may be half-updated.
+    pub fn with_tree_mut<F, R>(&self, f: F) -> std::result::Result<R, ApiError>
     where
         F: FnOnce(&mut MerkleTree) -> R,
     {
-        let mut tree = self.tree.lock().unwrap();
-        f(&mut tree)
+        let mut tree = self.tree.lock()?;
+        let result = f(&mut tree);
+
+        let root = (tree.root(), tree.depth());
+        let mut history = self.root_history.lock()?;
+        if history.back() != Some(&root) {
+            history.push_back(root);
+            while history.len() > self.config.root_history_size {
+                history.pop_front();
+            }
+        }
+        Ok(result)
     }
 
     /// Visualizes the Merkle tree and saves it as an image.
@@ -126,18 +766,19 @@ impl MerkleTreeService {
     ///
     /// # Returns
     /// TreeVisualizationResponse containing the URL to the generated image
-    pub fn visualize_tree(&self) -> std::result::Result<TreeVisualizationResponse, String> {
+    pub fn visualize_tree(&self) -> std::result::Result<TreeVisualizationResponse, ApiError> {
         // Generate unique filename using timestamp
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .map_err(|e| format!("Time error: {}", e))?
+            .map_err(|e| ApiError::internal("visualization_failed", format!("Time error: {}", e)))?
             .as_millis();
 
         let filename = format!("tree_{}.png", timestamp);
-        let filepath = format!("static/{}", filename);
+        let filepath = self.storage_path.join(&filename);
 
         // Create the image
-        self.with_tree(|tree| Self::generate_tree_image(tree, &filepath))?;
+        self.with_tree(|tree| Self::generate_tree_image(tree, &filepath))?
+            .map_err(|e| ApiError::internal("visualization_failed", e))?;
 
         Ok(TreeVisualizationResponse {
             image_url: format!("/static/{}", filename),
@@ -145,7 +786,7 @@ impl MerkleTreeService {
     }
 
     /// Generates the actual tree visualization image.
-    fn generate_tree_image(tree: &MerkleTree, filepath: &str) -> std::result::Result<(), String> {
+    fn generate_tree_image(tree: &MerkleTree, filepath: &Path) -> std::result::Result<(), String> {
         let depth = tree.depth();
         let num_leaves = tree.num_leaves();
 
@@ -161,26 +802,22 @@ impl MerkleTreeService {
         // Draw the tree level by level
         let level_height = height / (depth + 2);
 
-        // Iterate through each level
-        for (level_idx, level_nodes) in tree.levels.iter().enumerate() {
+        // Iterate through each level, including the zero padding
+        for level_idx in 0..=depth {
             let y = level_height * (level_idx + 1);
-            let node_count = level_nodes.len();
+            let node_count = num_leaves >> level_idx;
             let spacing = width / (node_count + 1);
 
-            for (node_idx, node) in level_nodes.iter().enumerate() {
+            for node_idx in 0..node_count {
+                let node = &tree.node(level_idx, node_idx);
                 let x = spacing * (node_idx + 1);
 
                 // Draw node circle
                 root.draw(&Circle::new((x as i32, y as i32), 15, ShapeStyle::from(&BLUE).filled()))
                     .map_err(|e| format!("Draw error: {}", e))?;
 
-                // Draw hash text (truncated)
-                let hash_str = format!("{:?}", node);
-                let truncated = if hash_str.len() > 12 {
-                    format!("{}...", &hash_str[..12])
-                } else {
-                    hash_str
-                };
+                // Draw hash text (truncated), in the same hex encoding as the API
+                let truncated = format!("{}...", &fp_to_hex(node)[..12]);
 
                 root.draw(&Text::new(
                     truncated,
@@ -223,77 +860,99 @@ impl MerkleTreeService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::services::tree_events::BACKLOG_PAGE;
     use halo2_proofs::pasta::Fp;
 
+    /// A service whose tree holds more than the 8 leaves allowed by the default config.
+    fn large_service() -> MerkleTreeService {
+        let mut config = Config::default();
+        config.tree.depth = 16;
+        MerkleTreeService::from_config(&config)
+    }
+
     #[test]
     fn test_add_to_tree_and_verify() {
-        //create a new service with the default tree [10, 20, 30, 40, 50, 60, 70, 80]
+        //create a new service with an empty tree
         let service = MerkleTreeService::new();
 
         //verify initial state - value 90 should NOT be in the tree
-        let initial_contains_90 =
-            service.with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)));
+        let initial_contains_90 = service
+            .with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)))
+            .unwrap();
         assert!(!initial_contains_90, "Value 90 should not be in tree initially");
 
         //add value 90 to the tree
-        let response = service.add_to_tree(90);
+        let response = service.add_to_tree(90).unwrap();
 
-        //verify the response is not empty
-        assert!(!response.data.is_empty(), "Response should contain root hash");
+        //verify the response carries the new root
+        let root = service.with_tree(|tree| tree.root()).unwrap();
+        assert_eq!(response.data, FpHex(root), "Response should contain root hash");
 
         //verify the value was actually added to the tree
-        let now_contains_90 =
-            service.with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)));
+        let now_contains_90 = service
+            .with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)))
+            .unwrap();
         assert!(now_contains_90, "Value 90 should be in the tree after adding");
 
         //verify we can find the value at a specific position (should be at the end before padding)
-        let leaf_index = service.with_tree(|tree| {
-            tree.leaves()
-                .iter()
-                .position(|&leaf| leaf == Fp::from(90u64))
-        });
+        let leaf_index = service
+            .with_tree(|tree| {
+                tree.leaves()
+                    .iter()
+                    .position(|&leaf| leaf == Fp::from(90u64))
+            })
+            .unwrap();
         assert!(leaf_index.is_some(), "Should be able to find index of value 90");
 
         //verify we can generate a proof for the newly added value
-        let proof_result = service.with_tree(|tree| tree.generate_proof(leaf_index.unwrap()));
+        let proof_result = service
+            .with_tree(|tree| tree.generate_proof(leaf_index.unwrap()))
+            .unwrap();
         assert!(proof_result.is_some(), "Should be able to generate proof for value 90");
     }
 
     #[test]
     fn test_add_many_to_tree_and_verify() {
-        //create a new service with the default tree [10, 20, 30, 40, 50, 60, 70, 80]
+        //create a new service with an empty tree
         let service = MerkleTreeService::new();
 
         //verify initial state - value 90 should NOT be in the tree
-        let initial_contains_90 =
-            service.with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)));
+        let initial_contains_90 = service
+            .with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)))
+            .unwrap();
         assert!(!initial_contains_90, "Value 90 should not be in tree initially");
 
         //add several values to the tree
-        let response = service.add_to_tree(410);
-        let response = service.add_to_tree(190);
-        let response = service.add_to_tree(90);
-        let response = service.add_to_tree(290);
-        let response = service.add_to_tree(240);
+        service.add_to_tree(410).unwrap();
+        service.add_to_tree(190).unwrap();
+        service.add_to_tree(90).unwrap();
+        service.add_to_tree(290).unwrap();
+        let response = service.add_to_tree(240).unwrap();
 
-        //verify the response is not empty
-        assert!(!response.data.is_empty(), "Response should contain root hash");
+        //verify the response carries the new root
+        let root = service.with_tree(|tree| tree.root()).unwrap();
+        assert_eq!(response.data, FpHex(root), "Response should contain root hash");
 
         //verify the value was actually added to the tree
-        let now_contains_90 =
-            service.with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)));
+        let now_contains_90 = service
+            .with_tree(|tree| tree.leaves().iter().any(|&leaf| leaf == Fp::from(90u64)))
+            .unwrap();
         assert!(now_contains_90, "Value 90 should be in the tree after adding");
 
         //verify we can find the value at a specific position (should be at the end before padding)
-        let leaf_index = service.with_tree(|tree| {
-            tree.leaves()
-                .iter()
-                .position(|&leaf| leaf == Fp::from(90u64))
-        });
+        let leaf_index = service
+            .with_tree(|tree| {
+                tree.leaves()
+                    .iter()
+                    .position(|&leaf| leaf == Fp::from(90u64))
+            })
+            .unwrap();
         assert!(leaf_index.is_some(), "Should be able to find index of value 90");
 
         //verify we can generate a proof for the newly added value
-        let proof_result = service.with_tree(|tree| tree.generate_proof(leaf_index.unwrap()));
+        let proof_result = service
+            .with_tree(|tree| tree.generate_proof(leaf_index.unwrap()))
+            .unwrap();
         assert!(proof_result.is_some(), "Should be able to generate proof for value 90");
     }
 
@@ -302,20 +961,437 @@ mod tests {
         let service = MerkleTreeService::new();
 
         //add multiple values
-        service.add_to_tree(90);
-        service.add_to_tree(100);
-        service.add_to_tree(110);
+        service.add_to_tree(90).unwrap();
+        service.add_to_tree(100).unwrap();
+        service.add_to_tree(110).unwrap();
 
         //verify all values are present
-        service.with_tree(|tree| {
-            let leaves_contain_90 = tree.leaves().iter().any(|&l| l == Fp::from(90u64));
-            let leaves_contain_100 = tree.leaves().iter().any(|&l| l == Fp::from(100u64));
-            let leaves_contain_110 = tree.leaves().iter().any(|&l| l == Fp::from(110u64));
+        service
+            .with_tree(|tree| {
+                let leaves_contain_90 = tree.leaves().iter().any(|&l| l == Fp::from(90u64));
+                let leaves_contain_100 = tree.leaves().iter().any(|&l| l == Fp::from(100u64));
+                let leaves_contain_110 = tree.leaves().iter().any(|&l| l == Fp::from(110u64));
+
+                assert!(leaves_contain_90, "Tree should contain 90");
+                assert!(leaves_contain_100, "Tree should contain 100");
+                assert!(leaves_contain_110, "Tree should contain 110");
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_new_tree_is_empty() {
+        let service = MerkleTreeService::new();
+        let (leaves, padded) = service
+            .with_tree(|tree| (tree.leaves().to_vec(), tree.num_leaves()))
+            .unwrap();
+        assert!(leaves.is_empty());
+        assert_eq!(padded, 1, "an empty tree is a single padding leaf");
+        assert!(service.proof_path(poseidon_commit(42)).is_err());
+    }
+
+    #[test]
+    fn test_proof_path_for_registered_commitment() {
+        let service = MerkleTreeService::new();
+        let members: Vec<Fp> = [42u64, 99, 7, 13]
+            .iter()
+            .map(|&s| poseidon_commit(s))
+            .collect();
+        service.import_commitments(&members).unwrap();
+        let commitment = poseidon_commit(7);
+
+        let path = service.proof_path(commitment).unwrap();
+        assert_eq!(path.index, 2, "secret 7 is the third member");
+        assert_eq!(path.leaf, FpHex(commitment));
+        assert_eq!(path.root, FpHex(service.with_tree(|tree| tree.root()).unwrap()));
+        assert_eq!(path.siblings.len(), 2);
+        assert_eq!(path.directions, vec![0, 1]);
+
+        let err = service.proof_path(poseidon_commit(1)).unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_import_and_register_respect_capacity() {
+        let mut config = Config::default();
+        config.tree.depth = 2;
+        let members: Vec<Fp> = [1u64, 2, 3].iter().map(|&s| poseidon_commit(s)).collect();
+        let service = MerkleTreeService::with_members(&config, members.clone());
+        assert!(service.proof_path(poseidon_commit(3)).is_ok());
+
+        //a depth-2 tree holds 4 leaves, so importing two more is rejected as a whole
+        let err = service
+            .import_commitments(&[poseidon_commit(10), poseidon_commit(11)])
+            .unwrap_err();
+        assert_eq!(err.code, "tree_full");
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = service.import_commitments(&[poseidon_commit(4)]).unwrap();
+        assert_eq!(response.imported, 1);
+        assert_eq!(response.num_members, 4);
+        let err = service.register_commitment(poseidon_commit(5)).unwrap_err();
+        assert_eq!(err.code, "tree_full");
+    }
+
+    #[test]
+    fn test_duplicate_commitments_are_rejected() {
+        let service = MerkleTreeService::new();
+        let members: Vec<Fp> = (1..=3u64).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+        let root = service.with_tree(|tree| tree.root()).unwrap();
+
+        let err = service.register_commitment(members[1]).unwrap_err();
+        assert_eq!((err.status, err.code), (StatusCode::CONFLICT, "duplicate_commitment"));
+        assert!(err.message.contains("index 1"), "{}", err.message);
+
+        //a batch is rejected as a whole if it repeats a member or itself
+        let err = service
+            .import_commitments(&[poseidon_commit(4), members[0]])
+            .unwrap_err();
+        assert_eq!(err.code, "duplicate_commitment");
+        let err = service
+            .import_commitments(&[poseidon_commit(4), poseidon_commit(5), poseidon_commit(4)])
+            .unwrap_err();
+        assert_eq!((err.status, err.code), (StatusCode::CONFLICT, "duplicate_commitment"));
+        //the first copy would have been appended after the three members
+        assert!(err.message.contains("index 3"), "{}", err.message);
+
+        assert_eq!(service.with_tree(|tree| tree.root()).unwrap(), root);
+        assert_eq!(service.proof_path(members[2]).unwrap().index, 2);
+    }
+
+    #[test]
+    fn test_idempotent_register_returns_existing_leaf() {
+        let mut config = Config::default();
+        config.tree.depth = 1;
+        let service = MerkleTreeService::from_config(&config);
+
+        let first = service
+            .register_commitment_idempotent(poseidon_commit(1))
+            .unwrap();
+        assert_eq!(first.index, 0);
+        let second = service.register_commitment(poseidon_commit(2)).unwrap();
+        assert_eq!(second.index, 1);
+
+        //a retry on the now full tree returns the same leaf and leaves the tree unchanged
+        let retry = service
+            .register_commitment_idempotent(poseidon_commit(1))
+            .unwrap();
+        assert_eq!((retry.index, retry.data), (0, second.data));
+        assert_eq!(retry.head.size, 2);
+        let err = service.register_commitment(poseidon_commit(1)).unwrap_err();
+        assert_eq!(err.code, "duplicate_commitment");
+    }
+
+    #[test]
+    fn test_idempotent_import_skips_registered_commitments() {
+        let mut config = Config::default();
+        config.tree.depth = 2;
+        let service = MerkleTreeService::from_config(&config);
+        let members: Vec<Fp> = (1..=4u64).map(poseidon_commit).collect();
+        service.import_commitments(&members[..3]).unwrap();
+
+        //a retry of a larger batch appends only the new member, even on a nearly full tree
+        let response = service.import_commitments_idempotent(&members).unwrap();
+        assert_eq!((response.imported, response.num_members), (1, 4));
+        assert_eq!(service.proof_path(members[3]).unwrap().index, 3);
+
+        //once everything is in, a retry on the full tree changes nothing
+        let root = service.with_tree(|tree| tree.root()).unwrap();
+        let response = service.import_commitments_idempotent(&members).unwrap();
+        assert_eq!((response.imported, response.root), (0, FpHex(root)));
+        assert_eq!(response.head.size, 4);
+
+        //a repeat within the batch is still rejected, at the index of its first copy
+        let err = service
+            .import_commitments_idempotent(&[members[1], members[1]])
+            .unwrap_err();
+        assert_eq!(err.code, "duplicate_commitment");
+        assert!(err.message.contains("index 1"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_zero_commitment_is_rejected() {
+        let service = MerkleTreeService::new();
+        for err in [
+            service.register_commitment(Fp::zero()).unwrap_err(),
+            service
+                .register_commitment_idempotent(Fp::zero())
+                .unwrap_err(),
+            service.add_to_tree(0).unwrap_err(),
+            service
+                .import_commitments(&[poseidon_commit(1), Fp::zero()])
+                .unwrap_err(),
+        ] {
+            assert_eq!((err.status, err.code), (StatusCode::BAD_REQUEST, "zero_commitment"));
+        }
+        assert_eq!(service.tree_info().unwrap().num_leaves, 0);
+    }
+
+    #[test]
+    fn test_parse_commitment_list() {
+        let a = poseidon_commit(1);
+        let b = poseidon_commit(2);
+        let contents = format!("# members\n{}\n\n  0x{}  \n", fp_to_hex(&a), fp_to_hex(&b));
+        assert_eq!(parse_commitment_list(&contents).unwrap(), vec![a, b]);
+        assert_eq!(parse_commitment_list("# header\nnot-hex\n"), Err(2));
+    }
+
+    #[test]
+    fn test_root_history_keeps_recent_roots() {
+        let mut config = Config::default();
+        config.tree.root_history_size = 2;
+        let service = MerkleTreeService::from_config(&config);
+
+        let first = service.with_tree(|tree| tree.root()).unwrap();
+        service.add_to_tree(90).unwrap();
+        assert!(service.is_known_root(&first).unwrap(), "previous root is still recent");
+
+        service.add_to_tree(91).unwrap();
+        assert!(!service.is_known_root(&first).unwrap(), "oldest root was evicted");
+        let current = service.with_tree(|tree| tree.root()).unwrap();
+        assert!(service.is_known_root(&current).unwrap());
+    }
+
+    #[test]
+    fn test_tree_info_counts_real_and_padded_leaves() {
+        let service = MerkleTreeService::new();
+        let members: Vec<Fp> = (1..=5u64).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+
+        let info = service.tree_info().unwrap();
+        assert_eq!(info.num_leaves, 5);
+        assert_eq!(info.padded_leaves, 8);
+        assert_eq!(info.depth, 3);
+        assert_eq!(info.capacity, 1 << Config::default().tree.depth);
+        assert_eq!(info.root, FpHex(service.with_tree(|tree| tree.root()).unwrap()));
+    }
+
+    #[test]
+    fn test_leaves_pages_through_members() {
+        let service = MerkleTreeService::new();
+        let members: Vec<Fp> = (1..=5u64).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+
+        let page = |offset, limit| {
+            service
+                .leaves(&LeavesQuery {
+                    offset,
+                    limit: Some(limit),
+                })
+                .unwrap()
+        };
+        let first = page(0, 2);
+        assert_eq!(first.total, 5);
+        assert_eq!(first.leaves, vec![FpHex(members[0]), FpHex(members[1])]);
+
+        //the last page stops at the last real leaf, not the padding
+        let last = page(4, 10);
+        assert_eq!(last.leaves, vec![FpHex(members[4])]);
+        assert!(page(7, 10).leaves.is_empty());
+        assert!(page(usize::MAX, usize::MAX).leaves.is_empty());
+
+        let all = service.leaves(&LeavesQuery::default()).unwrap();
+        assert_eq!(all.leaves.len(), 5);
+    }
+
+    #[test]
+    fn test_subscribe_replays_backlog_then_live_events() {
+        use futures_util::FutureExt;
+
+        let service = Arc::new(MerkleTreeService::new());
+        let members: Vec<Fp> = (1..=3u64).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+
+        let mut feed = service.subscribe(1).unwrap();
+        let mut next = || {
+            feed.next()
+                .now_or_never()
+                .flatten()
+                .map(std::result::Result::unwrap)
+        };
+
+        //each replayed leaf carries the root right after it was appended
+        assert_eq!(
+            next(),
+            Some(LeafEvent {
+                index: 1,
+                commitment: FpHex(members[1]),
+                root: FpHex(MerkleTree::new(members[..2].to_vec()).root())
+            })
+        );
+        let root = FpHex(service.with_tree(|tree| tree.root()).unwrap());
+        assert_eq!(next().map(|e| (e.index, e.root)), Some((2, root)));
+        assert_eq!(next(), None, "caught up, waiting for insertions");
+
+        let joiner = poseidon_commit(4);
+        let response = service.register_commitment(joiner).unwrap();
+        assert_eq!(
+            next(),
+            Some(LeafEvent {
+                index: 3,
+                commitment: FpHex(joiner),
+                root: response.data
+            })
+        );
+    }
+
+    #[test]
+    fn test_subscribe_ahead_of_tree_skips_earlier_leaves() {
+        use futures_util::FutureExt;
+
+        let service = Arc::new(MerkleTreeService::new());
+        let mut feed = service.subscribe(2).unwrap();
+        let members: Vec<Fp> = (1..=3u64).map(poseidon_commit).collect();
+        let response = service.import_commitments(&members).unwrap();
+
+        let event = feed.next().now_or_never().flatten().unwrap().unwrap();
+        assert_eq!(event.index, 2);
+        assert_eq!(event.commitment, FpHex(members[2]));
+        assert_eq!(event.root, response.root);
+        assert!(feed.next().now_or_never().is_none());
+    }
+
+    #[test]
+    fn test_subscribe_pages_through_a_long_backlog() {
+        use futures_util::FutureExt;
+
+        let service = Arc::new(large_service());
+        let members: Vec<Fp> = (1..=BACKLOG_PAGE as u64 + 10)
+            .map(poseidon_commit)
+            .collect();
+        service.import_commitments(&members).unwrap();
+
+        let mut feed = service.subscribe(0).unwrap();
+        //leaves imported after subscribing follow the backlog, with their own roots
+        let joiner = poseidon_commit(0);
+        let response = service.register_commitment(joiner).unwrap();
+
+        let events: Vec<LeafEvent> = std::iter::from_fn(|| {
+            feed.next()
+                .now_or_never()
+                .flatten()
+                .map(std::result::Result::unwrap)
+        })
+        .collect();
+        assert_eq!(events.len(), members.len() + 1);
+        for (index, event) in events.iter().enumerate() {
+            assert_eq!(event.index, index);
+        }
+        let tree = MerkleTree::new(members.clone());
+        assert_eq!(events[BACKLOG_PAGE].root, FpHex(tree.root_at(BACKLOG_PAGE + 1).unwrap()));
+        assert_eq!(events[members.len() - 1].root, FpHex(tree.root()));
+        assert_eq!(events[members.len()].commitment, FpHex(joiner));
+        assert_eq!(events[members.len()].root, response.data);
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_told_where_to_resume() {
+        use futures_util::FutureExt;
+
+        let service = Arc::new(large_service());
+        let mut feed = service.subscribe(0).unwrap();
+        let members: Vec<Fp> = (0..EVENT_BUFFER as u64 + 1).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+
+        assert_eq!(feed.next().now_or_never().flatten(), Some(Err(FeedLagged { resume_from: 0 })));
+        assert_eq!(feed.next().now_or_never().flatten(), None, "the feed ends after a lag");
+    }
+
+    #[test]
+    fn test_multiproof_covers_requested_members() {
+        let service = large_service();
+        let members: Vec<Fp> = (1..=10u64).map(poseidon_commit).collect();
+        service.import_commitments(&members).unwrap();
+        let root = service.with_tree(|tree| tree.root()).unwrap();
+
+        let request = MultiProofRequest {
+            indices: vec![9, 0, 3, 3],
+        };
+        let response = service.multiproof(&request).unwrap();
+        assert_eq!(response.root, FpHex(root));
+        assert_eq!(response.indices, vec![0, 3, 9]);
+        assert_eq!(response.leaves, vec![FpHex(members[0]), FpHex(members[3]), FpHex(members[9])]);
+
+        //the proof survives a JSON round trip
+        let json = serde_json::to_string(&response).unwrap();
+        let decoded: MultiProofResponse = serde_json::from_str(&json).unwrap();
+        assert!(decoded.proof().verify(&root));
+
+        for indices in [vec![], vec![10], vec![0; MAX_MULTIPROOF_LEAVES + 1]] {
+            let err = service
+                .multiproof(&MultiProofRequest { indices })
+                .unwrap_err();
+            assert_eq!(err.code, "invalid_indices");
+        }
+    }
+
+    #[test]
+    fn test_consistency_proof_links_recorded_roots() {
+        let service = MerkleTreeService::new();
+        let mut roots = vec![service.with_tree(|tree| tree.root()).unwrap()];
+        for secret in 1..=6u64 {
+            roots.push(
+                service
+                    .register_commitment(poseidon_commit(secret))
+                    .unwrap()
+                    .data
+                    .0,
+            );
+        }
+
+        for (from, to) in [(0, 6), (2, 5), (3, 3), (5, 6), (1, 4)] {
+            let response = service
+                .consistency_proof(&ConsistencyQuery { from, to: Some(to) })
+                .unwrap();
+            assert_eq!((response.from, response.to), (from, to));
+            assert_eq!(response.from_root, FpHex(roots[from]));
+            assert_eq!(response.to_root, FpHex(roots[to]));
+            assert!(response.proof().verify(&roots[from], &roots[to]));
+        }
+
+        let latest = service
+            .consistency_proof(&ConsistencyQuery { from: 4, to: None })
+            .unwrap();
+        assert_eq!(latest.to, 6);
+    }
+
+    #[test]
+    fn test_consistency_proof_rejects_invalid_ranges() {
+        let service = MerkleTreeService::new();
+        service.add_to_tree(90).unwrap();
+        service.add_to_tree(91).unwrap();
+
+        for (from, to) in [(2, Some(1)), (0, Some(3)), (3, None)] {
+            let err = service
+                .consistency_proof(&ConsistencyQuery { from, to })
+                .unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST);
+            assert_eq!(err.code, "invalid_range");
+        }
+    }
+
+    #[test]
+    fn test_tree_changes_return_signed_heads() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let service = MerkleTreeService::new().with_signing_key(key.clone());
+        let public = service.public_key().verifying_key().unwrap();
+        assert_eq!(public, key.verifying_key());
+
+        let response = service.register_commitment(poseidon_commit(1)).unwrap();
+        assert_eq!((response.head.size, response.head.root), (1, response.data));
+        assert!(response.head.verify(&public));
+
+        let import = service
+            .import_commitments(&[poseidon_commit(2), poseidon_commit(3)])
+            .unwrap();
+        assert_eq!((import.head.size, import.head.root), (3, import.root));
+        assert!(import.head.verify(&public));
 
-            assert!(leaves_contain_90, "Tree should contain 90");
-            assert!(leaves_contain_100, "Tree should contain 100");
-            assert!(leaves_contain_110, "Tree should contain 110");
-        });
+        let head = service.signed_tree_head().unwrap();
+        assert_eq!((head.size, head.root), (3, import.root));
+        assert!(head.verify(&public));
+        assert!(!head.verify(&SigningKey::from_bytes(&[2u8; 32]).verifying_key()));
     }
 
     #[test]
@@ -323,13 +1399,13 @@ mod tests {
         let service = MerkleTreeService::new();
 
         //get initial root
-        let initial_root = service.with_tree(|tree| tree.root());
+        let initial_root = service.with_tree(|tree| tree.root()).unwrap();
 
         //add a value
-        service.add_to_tree(90);
+        service.add_to_tree(90).unwrap();
 
         //get new root
-        let new_root = service.with_tree(|tree| tree.root());
+        let new_root = service.with_tree(|tree| tree.root()).unwrap();
 
         //verify root changed
         assert_ne!(initial_root, new_root, "Root should change after adding a value");
@@ -2,5 +2,20 @@ pub mod health_service;
 pub mod merkle_tree_service;
 pub mod zk_service;
 
+pub mod batch;
+pub mod circuit_cost;
+pub mod circuit_gadgets;
+pub mod circuit_registry;
+pub mod fp_hex;
 pub mod merkle_circuit;
+pub mod merkle_range_circuit;
 pub mod merkle_tree;
+pub mod proof_envelope;
+pub mod prover;
+pub mod quaternary_circuit;
+pub mod quaternary_tree;
+pub mod range_check;
+pub mod tree_events;
+pub mod tree_hasher;
+pub mod tree_head;
+pub mod zk_error;
@@ -0,0 +1,353 @@
+use halo2_proofs::pasta::Fp;
+use rust_api::prelude::*;
+use std::fmt;
+
+use super::fp_hex::FpHex;
+
+/// Current envelope format version. Bump whenever the envelope layout changes.
+pub const ENVELOPE_VERSION: u16 = 1;
+
+/// A versioned, self-describing container for a serialized proof.
+///
+/// Everything a verifier needs besides the verifying key travels with the proof: which circuit
+/// produced it, the circuit parameters (depth, k) and the public inputs. Envelopes can be
+/// encoded as JSON (proof bytes as hex) or compact CBOR (proof bytes as a byte string).
+/// Both parsers are strict: unknown fields, unsupported versions and non-canonical public
+/// inputs are rejected.
+///
+/// Only the proof and its public inputs are checked by verification. `signal` and
+/// `nullifier` are unauthenticated metadata: no circuit constrains them, so anyone holding
+/// an envelope can change or strip them without invalidating the proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProofEnvelope {
+    /// Envelope format version (see ENVELOPE_VERSION)
+    pub version: u16,
+    /// Identifier of the circuit the proof was created for, e.g. "merkle-membership-v2"
+    pub circuit_id: String,
+    /// Merkle depth the circuit was instantiated with
+    pub depth: u32,
+    /// log2 of the number of circuit rows
+    pub k: u32,
+    /// Public inputs, encoded as canonical 64-char lowercase hex (`fp_to_hex`)
+    #[serde(deserialize_with = "canonical::vec")]
+    pub public_inputs: Vec<FpHex>,
+    /// Serialized proof bytes
+    #[serde(with = "proof_bytes")]
+    pub proof: Vec<u8>,
+    /// Optional application signal (canonical hex). Unauthenticated: it is not a public
+    /// input, so verification does not check it
+    #[serde(
+        default,
+        deserialize_with = "canonical::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub signal: Option<FpHex>,
+    /// Optional application nullifier (canonical hex). Unauthenticated and not tracked by the
+    /// server, so it does not prevent a proof from being reused
+    #[serde(
+        default,
+        deserialize_with = "canonical::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nullifier: Option<FpHex>,
+}
+
+/// Errors produced when decoding or checking a ProofEnvelope.
+#[derive(Debug)]
+pub enum EnvelopeError {
+    /// The input is not a well-formed JSON envelope
+    Json(serde_json::Error),
+    /// The input is not a well-formed CBOR envelope
+    Cbor(String),
+    /// Extra bytes follow the CBOR envelope
+    TrailingBytes,
+    /// The envelope was written by an unsupported format version
+    UnsupportedVersion(u16),
+    /// The envelope belongs to a different circuit or circuit parameters
+    CircuitMismatch { expected: String, found: String },
+    /// The proof bytes are empty
+    EmptyProof,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::Json(e) => write!(f, "invalid JSON envelope: {}", e),
+            EnvelopeError::Cbor(e) => write!(f, "invalid CBOR envelope: {}", e),
+            EnvelopeError::TrailingBytes => write!(f, "trailing bytes after CBOR envelope"),
+            EnvelopeError::UnsupportedVersion(v) => {
+                write!(f, "unsupported envelope version {} (expected {})", v, ENVELOPE_VERSION)
+            },
+            EnvelopeError::CircuitMismatch { expected, found } => {
+                write!(f, "circuit mismatch: expected {}, found {}", expected, found)
+            },
+            EnvelopeError::EmptyProof => write!(f, "proof bytes are empty"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+impl ProofEnvelope {
+    /// Creates an envelope at the current format version.
+    ///
+    /// # Arguments
+    /// * `circuit_id` - Identifier of the circuit that produced the proof
+    /// * `depth` - Merkle depth of the circuit
+    /// * `k` - log2 of the number of circuit rows
+    /// * `public_inputs` - The proof's public inputs, in circuit order
+    /// * `proof` - Serialized proof bytes
+    pub fn new(circuit_id: &str, depth: u32, k: u32, public_inputs: &[Fp], proof: Vec<u8>) -> Self {
+        Self {
+            version: ENVELOPE_VERSION,
+            circuit_id: circuit_id.to_string(),
+            depth,
+            k,
+            public_inputs: public_inputs.iter().copied().map(FpHex).collect(),
+            proof,
+            signal: None,
+            nullifier: None,
+        }
+    }
+
+    /// Encodes the envelope as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("ProofEnvelope is always serializable")
+    }
+
+    /// Strictly decodes and validates a JSON envelope.
+    pub fn from_json(json: &str) -> std::result::Result<Self, EnvelopeError> {
+        let envelope: Self = serde_json::from_str(json).map_err(EnvelopeError::Json)?;
+        envelope.validate()?;
+        Ok(envelope)
+    }
+
+    /// Encodes the envelope as compact CBOR.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Strictly decodes and validates a CBOR envelope. Trailing bytes are rejected.
+    pub fn from_cbor(bytes: &[u8]) -> std::result::Result<Self, EnvelopeError> {
+        let mut reader = bytes;
+        let envelope: Self =
+            ciborium::from_reader(&mut reader).map_err(|e| EnvelopeError::Cbor(e.to_string()))?;
+        if !reader.is_empty() {
+            return Err(EnvelopeError::TrailingBytes);
+        }
+        envelope.validate()?;
+        Ok(envelope)
+    }
+
+    /// Checks the format version and the proof bytes. Field elements are already checked to
+    /// be canonical hex when the envelope is decoded.
+    pub fn validate(&self) -> std::result::Result<(), EnvelopeError> {
+        if self.version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(self.version));
+        }
+        if self.proof.is_empty() {
+            return Err(EnvelopeError::EmptyProof);
+        }
+        Ok(())
+    }
+
+    /// Rejects envelopes produced for a different circuit, circuit version or parameters.
+    pub fn expect_circuit(
+        &self,
+        circuit_id: &str,
+        depth: u32,
+        k: u32,
+    ) -> std::result::Result<(), EnvelopeError> {
+        if self.circuit_id != circuit_id || self.depth != depth || self.k != k {
+            return Err(EnvelopeError::CircuitMismatch {
+                expected: format!("{} (depth={}, k={})", circuit_id, depth, k),
+                found: format!("{} (depth={}, k={})", self.circuit_id, self.depth, self.k),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the public inputs as field elements.
+    /// Every input was decoded as a field element, so none can be missing or invalid.
+    pub fn public_inputs_fp(&self) -> Vec<Fp> {
+        self.public_inputs.iter().map(|input| input.0).collect()
+    }
+}
+
+/// Deserializes field elements like FpHex, but accepts only the exact encoding produced by
+/// `fp_to_hex` (no `0x` prefix or uppercase digits), so an envelope has one valid encoding.
+mod canonical {
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    use crate::services::fp_hex::{fp_to_hex, parse_fp_hex, FpHex};
+
+    fn parse<E: Error>(hex: &str) -> Result<FpHex, E> {
+        match parse_fp_hex(hex) {
+            Ok(fp) if fp_to_hex(&fp) == hex => Ok(FpHex(fp)),
+            _ => Err(E::custom(format!("{:?} is not a canonical 64-char hex field element", hex))),
+        }
+    }
+
+    pub fn vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<FpHex>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| parse(hex))
+            .collect()
+    }
+
+    pub fn option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FpHex>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| parse(&hex))
+            .transpose()
+    }
+}
+
+/// Serializes proof bytes as a hex string in human-readable formats (JSON)
+/// and as a raw byte string in binary formats (CBOR).
+mod proof_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let hex_str = String::deserialize(deserializer)?;
+            hex::decode(hex_str).map_err(D::Error::custom)
+        } else {
+            let bytes: serde_bytes::ByteBuf = Deserialize::deserialize(deserializer)?;
+            Ok(bytes.into_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ProofEnvelope {
+        ProofEnvelope::new("merkle-membership-v1", 3, 9, &[Fp::from(42u64)], vec![1, 2, 3, 4])
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let envelope = sample();
+        let decoded = ProofEnvelope::from_json(&envelope.to_json()).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.public_inputs_fp(), vec![Fp::from(42u64)]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_public_input() {
+        let mut value: serde_json::Value = serde_json::from_str(&sample().to_json()).unwrap();
+        value["public_inputs"] = serde_json::json!(["zz"]);
+        assert!(matches!(
+            ProofEnvelope::from_json(&value.to_string()),
+            Err(EnvelopeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let envelope = sample();
+        let cbor = envelope.to_cbor();
+        assert!(cbor.len() < envelope.to_json().len(), "CBOR should be more compact than JSON");
+        assert_eq!(ProofEnvelope::from_cbor(&cbor).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        let mut value: serde_json::Value = serde_json::from_str(&sample().to_json()).unwrap();
+        value["extra"] = serde_json::json!(1);
+        assert!(matches!(
+            ProofEnvelope::from_json(&value.to_string()),
+            Err(EnvelopeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unsupported_version() {
+        let mut envelope = sample();
+        envelope.version = ENVELOPE_VERSION + 1;
+        assert!(matches!(
+            ProofEnvelope::from_json(&envelope.to_json()),
+            Err(EnvelopeError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_public_input() {
+        let mut value: serde_json::Value = serde_json::from_str(&sample().to_json()).unwrap();
+        let input = value["public_inputs"][0].as_str().unwrap().to_uppercase();
+        value["public_inputs"][0] = serde_json::json!(input);
+        assert!(matches!(
+            ProofEnvelope::from_json(&value.to_string()),
+            Err(EnvelopeError::Json(_))
+        ));
+
+        value["public_inputs"][0] = serde_json::json!(format!("0x{}", input.to_lowercase()));
+        assert!(matches!(
+            ProofEnvelope::from_json(&value.to_string()),
+            Err(EnvelopeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_cbor_rejects_non_canonical_signal() {
+        #[derive(Serialize)]
+        struct RawEnvelope<'a> {
+            version: u16,
+            circuit_id: &'a str,
+            depth: u32,
+            k: u32,
+            public_inputs: Vec<String>,
+            #[serde(with = "serde_bytes")]
+            proof: Vec<u8>,
+            signal: String,
+        }
+        let raw = RawEnvelope {
+            version: ENVELOPE_VERSION,
+            circuit_id: "merkle-membership-v1",
+            depth: 3,
+            k: 9,
+            public_inputs: vec![],
+            proof: vec![1],
+            signal: "0X01".to_string(),
+        };
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&raw, &mut cbor).unwrap();
+        assert!(matches!(ProofEnvelope::from_cbor(&cbor), Err(EnvelopeError::Cbor(_))));
+    }
+
+    #[test]
+    fn test_rejects_trailing_bytes() {
+        let mut cbor = sample().to_cbor();
+        cbor.push(0);
+        assert!(matches!(ProofEnvelope::from_cbor(&cbor), Err(EnvelopeError::TrailingBytes)));
+    }
+
+    #[test]
+    fn test_expect_circuit_mismatch() {
+        let envelope = sample();
+        assert!(envelope
+            .expect_circuit("merkle-membership-v1", 3, 9)
+            .is_ok());
+        assert!(envelope
+            .expect_circuit("merkle-membership-v2", 3, 9)
+            .is_err());
+        assert!(envelope
+            .expect_circuit("merkle-membership-v1", 4, 9)
+            .is_err());
+        assert!(envelope
+            .expect_circuit("merkle-membership-v1", 3, 10)
+            .is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::{
+        self, create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey,
+        SingleVerifier, VerifyingKey,
+    },
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255},
+};
+use rand_core::OsRng;
+use std::marker::PhantomData;
+
+use super::merkle_circuit::{MerkleCircuit, DEPTH};
+
+/// Rows budgeted for each Poseidon hash of the membership circuit. A width-3 Pow5
+/// permutation takes about 40 rows; the rest is headroom for the assignment regions and
+/// blinding rows.
+const ROWS_PER_HASH: usize = 64;
+
+/// Circuit size parameter MerkleCircuit is served with at `depth` levels: room for the
+/// commitment hash, one hash per level and one more hash's worth of headroom.
+/// See `GET /zk/circuit-info/cost` for the minimal k at each depth.
+pub const fn membership_k(depth: usize) -> u32 {
+    (ROWS_PER_HASH * (depth + 2))
+        .next_power_of_two()
+        .trailing_zeros()
+}
+
+/// Circuit size parameter used for MerkleCircuit at the default DEPTH.
+/// k=9 gives 2^9=512 rows for the commitment hash plus DEPTH path levels.
+pub const K: u32 = membership_k(DEPTH);
+
+/// Public parameters and keys for creating and verifying real (non-mock) proofs for circuit `C`.
+/// Uses the IPA commitment scheme over the Pasta curves, so no trusted setup is needed:
+/// `Params::new(k)` is deterministic and the keys are derived from the circuit shape alone.
+///
+/// Every supported circuit exposes a single instance column; the public inputs of one
+/// circuit instance are passed as a `Vec<Fp>` in row order.
+#[derive(Debug)]
+pub struct CircuitKeys<C> {
+    params: Params<EqAffine>,
+    pk: ProvingKey<EqAffine>,
+    _circuit: PhantomData<fn() -> C>,
+}
+
+/// Keys for the Merkle membership circuit.
+pub type MerkleKeys = CircuitKeys<MerkleCircuit>;
+
+impl<C: Circuit<Fp>> CircuitKeys<C> {
+    /// Generates the public parameters, verifying key and proving key for a circuit.
+    ///
+    /// # Arguments
+    /// * `k` - log2 of the number of rows in the circuit
+    /// * `circuit` - Any instance of the circuit; only its shape is used, never its witnesses
+    pub fn setup(k: u32, circuit: &C) -> std::result::Result<Self, plonk::Error> {
+        let params: Params<EqAffine> = Params::new(k);
+        let empty_circuit = circuit.without_witnesses();
+        let vk = keygen_vk(&params, &empty_circuit)?;
+        let pk = keygen_pk(&params, vk, &empty_circuit)?;
+        Ok(Self {
+            params,
+            pk,
+            _circuit: PhantomData,
+        })
+    }
+
+    /// Returns the public parameters the keys were generated with.
+    pub fn params(&self) -> &Params<EqAffine> {
+        &self.params
+    }
+
+    /// Returns the verifying key.
+    pub fn vk(&self) -> &VerifyingKey<EqAffine> {
+        self.pk.get_vk()
+    }
+
+    /// Returns a hex BLAKE2b-256 digest of the verifying key.
+    /// Keygen is deterministic, so two parties that derive keys for the same circuit and k get
+    /// the same fingerprint; comparing fingerprints checks that they verify against the same key.
+    pub fn vk_fingerprint(&self) -> String {
+        fingerprint(self.vk())
+    }
+
+    /// Creates a single proof covering one or more circuit instances.
+    ///
+    /// # Arguments
+    /// * `circuits` - The circuits to prove, each with its private witnesses
+    /// * `instances` - The public inputs of each circuit, in the same order
+    ///
+    /// # Returns
+    /// The serialized proof bytes
+    pub fn prove(
+        &self,
+        circuits: &[C],
+        instances: &[Vec<Fp>],
+    ) -> std::result::Result<Vec<u8>, plonk::Error> {
+        if circuits.len() != instances.len() {
+            return Err(plonk::Error::InvalidInstances);
+        }
+
+        let columns: Vec<[&[Fp]; 1]> = instances.iter().map(|i| [&i[..]]).collect();
+        let per_circuit: Vec<&[&[Fp]]> = columns.iter().map(|c| &c[..]).collect();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof(&self.params, &self.pk, circuits, &per_circuit, OsRng, &mut transcript)?;
+        Ok(transcript.finalize())
+    }
+
+    /// Verifies a proof created by [`CircuitKeys::prove`] against the given public inputs.
+    ///
+    /// # Returns
+    /// true if the proof is valid for exactly these public inputs, in this order
+    pub fn verify(&self, proof: &[u8], instances: &[Vec<Fp>]) -> bool {
+        verify(&self.params, self.vk(), proof, instances)
+    }
+}
+
+/// The verifying key for circuit `C` without its proving key, with the public parameters it
+/// verifies under. Verifies the same proofs as the CircuitKeys for the same circuit and k.
+///
+/// halo2_proofs 0.3 cannot serialize a VerifyingKey, so a verifier derives the key itself with
+/// `keygen_vk`, which skips the proving key, and compares `vk_fingerprint` with the one the
+/// prover published to check that both sides use the same key.
+#[derive(Debug)]
+pub struct CircuitVerifier<C> {
+    params: Params<EqAffine>,
+    vk: VerifyingKey<EqAffine>,
+    _circuit: PhantomData<fn() -> C>,
+}
+
+impl<C: Circuit<Fp>> CircuitVerifier<C> {
+    /// Generates the public parameters and verifying key for a circuit.
+    ///
+    /// # Arguments
+    /// * `k` - log2 of the number of rows in the circuit
+    /// * `circuit` - Any instance of the circuit; only its shape is used, never its witnesses
+    pub fn setup(k: u32, circuit: &C) -> std::result::Result<Self, plonk::Error> {
+        let params: Params<EqAffine> = Params::new(k);
+        let vk = keygen_vk(&params, &circuit.without_witnesses())?;
+        Ok(Self {
+            params,
+            vk,
+            _circuit: PhantomData,
+        })
+    }
+
+    /// Returns the verifying key.
+    pub fn vk(&self) -> &VerifyingKey<EqAffine> {
+        &self.vk
+    }
+
+    /// Returns the same digest as `CircuitKeys::vk_fingerprint` for the key's CircuitKeys.
+    pub fn vk_fingerprint(&self) -> String {
+        fingerprint(&self.vk)
+    }
+
+    /// Verifies a proof created by [`CircuitKeys::prove`] against the given public inputs.
+    pub fn verify(&self, proof: &[u8], instances: &[Vec<Fp>]) -> bool {
+        verify(&self.params, &self.vk, proof, instances)
+    }
+}
+
+/// Hex BLAKE2b-256 of the verifying key's pinned (Debug) form.
+fn fingerprint(vk: &VerifyingKey<EqAffine>) -> String {
+    let pinned = format!("{:?}", vk.pinned());
+    let digest = blake2b_simd::Params::new()
+        .hash_length(32)
+        .hash(pinned.as_bytes());
+    hex::encode(digest.as_bytes())
+}
+
+/// Verifies a proof with one instance column per circuit.
+fn verify(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+) -> bool {
+    let columns: Vec<[&[Fp]; 1]> = instances.iter().map(|i| [&i[..]]).collect();
+    let per_circuit: Vec<&[&[Fp]]> = columns.iter().map(|c| &c[..]).collect();
+
+    let strategy = SingleVerifier::new(params);
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+    verify_proof(params, vk, strategy, &per_circuit, &mut transcript).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::merkle_range_circuit::{MerkleRangeCircuit, MERKLE_RANGE_K};
+
+    #[test]
+    fn test_vk_fingerprint_is_deterministic() {
+        let a = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let b = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        assert_eq!(a.vk_fingerprint(), b.vk_fingerprint());
+        assert_eq!(a.vk_fingerprint().len(), 64);
+
+        let other = CircuitKeys::setup(MERKLE_RANGE_K, &MerkleRangeCircuit::default()).unwrap();
+        assert_ne!(a.vk_fingerprint(), other.vk_fingerprint());
+    }
+
+    #[test]
+    fn test_verifier_verifies_the_same_proofs() {
+        use crate::services::merkle_circuit::merkle_path_witness;
+        use crate::services::merkle_tree::MerkleTree;
+        use crate::services::merkle_tree_service::poseidon_commit;
+        use halo2_proofs::circuit::Value;
+
+        let keys = MerkleKeys::setup(K, &MerkleCircuit::default()).unwrap();
+        let verifier = CircuitVerifier::setup(K, &MerkleCircuit::default()).unwrap();
+        assert_eq!(verifier.vk_fingerprint(), keys.vk_fingerprint());
+
+        let tree = MerkleTree::new((1..=8u64).map(poseidon_commit).collect());
+        let (siblings, directions, root) =
+            merkle_path_witness(&tree, poseidon_commit(3), DEPTH).unwrap();
+        let circuit = MerkleCircuit {
+            secret: Value::known(Fp::from(3)),
+            siblings,
+            directions,
+        };
+        let proof = keys.prove(&[circuit], &[vec![root]]).unwrap();
+        assert!(verifier.verify(&proof, &[vec![root]]));
+        assert!(!verifier.verify(&proof, &[vec![root + Fp::one()]]));
+    }
+}
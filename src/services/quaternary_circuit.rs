@@ -0,0 +1,413 @@
+use halo2_gadgets::poseidon::{
+    primitives::{ConstantLength, P128Pow5T3},
+    Hash, Pow5Chip, Pow5Config,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    pasta::Fp,
+    plonk::{self, Advice, Circuit, Column, ConstraintSystem, Expression, Instance, Selector},
+    poly::Rotation,
+};
+
+use super::circuit_gadgets::{self, PoseidonColumns};
+use super::merkle_tree_service::poseidon_commit;
+use super::quaternary_tree::{place_children, P128Pow5T5, QuaternaryTree, ARITY};
+use super::zk_error::ZkProofError;
+
+/// Depth of the quaternary tree the circuit proves: 4^2 = 16 leaves, twice the capacity of
+/// MerkleCircuit's depth-3 binary tree with one fewer path hash.
+pub const QUATERNARY_DEPTH: usize = 2;
+
+/// Stable identifier for this circuit, recorded in proof envelopes.
+/// Bump the suffix whenever the constraint system changes in a way that invalidates old keys.
+pub const QUATERNARY_CIRCUIT_ID: &str = "merkle-membership-quaternary-v1";
+
+/// Membership of `Poseidon(secret, 0)` in a QuaternaryTree, with the root as public input.
+///
+/// The arity-4 counterpart of MerkleCircuit: each level hashes four children with one width-5
+/// Poseidon permutation, and a 4-way position selector gate replaces the binary conditional
+/// swap.
+#[derive(Clone, Debug)]
+pub struct QuaternaryMerkleCircuit {
+    /// Private secret witness — never revealed; the circuit proves Poseidon(secret) == commitment
+    pub secret: Value<Fp>,
+
+    /// The three siblings at each level, in child order.
+    pub siblings: [[Value<Fp>; ARITY - 1]; QUATERNARY_DEPTH],
+
+    /// Position (0..=3) of the current node among its parent's children at each level.
+    /// The circuit witnesses it as two bits and enforces:
+    /// 1. Each bit is binary
+    /// 2. The current node is the child at the position and the siblings fill the other slots
+    ///    in order
+    pub positions: [Value<usize>; QUATERNARY_DEPTH],
+}
+
+impl Default for QuaternaryMerkleCircuit {
+    /// A circuit with all witnesses unknown, suitable for key generation.
+    fn default() -> Self {
+        Self {
+            secret: Value::unknown(),
+            siblings: [[Value::unknown(); ARITY - 1]; QUATERNARY_DEPTH],
+            positions: [Value::unknown(); QUATERNARY_DEPTH],
+        }
+    }
+}
+
+impl QuaternaryMerkleCircuit {
+    /// Builds a fully-witnessed circuit for the member holding `secret`, and its public inputs.
+    /// Fails if the commitment is not in the tree or the tree depth is not QUATERNARY_DEPTH.
+    pub fn build(
+        tree: &QuaternaryTree,
+        secret: u64,
+    ) -> std::result::Result<(Self, Vec<Fp>), ZkProofError> {
+        let leaf = poseidon_commit(secret);
+        let proof = tree
+            .index_of(&leaf)
+            .and_then(|idx| tree.generate_proof(idx))
+            .ok_or(ZkProofError::CommitmentNotInTree)?;
+
+        let depth_error = || ZkProofError::DepthUnsupported {
+            tree_depth: tree.depth(),
+            circuit_depth: QUATERNARY_DEPTH,
+        };
+        let siblings: [[Fp; ARITY - 1]; QUATERNARY_DEPTH] =
+            proof.siblings.try_into().map_err(|_| depth_error())?;
+        let positions: [usize; QUATERNARY_DEPTH] =
+            proof.positions.try_into().map_err(|_| depth_error())?;
+
+        let circuit = QuaternaryMerkleCircuit {
+            secret: Value::known(Fp::from(secret)),
+            siblings: siblings.map(|level| level.map(Value::known)),
+            positions: positions.map(Value::known),
+        };
+        Ok((circuit, vec![tree.root()]))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuaternaryConfig {
+    advice: Column<Advice>,
+    pub instance: Column<Instance>,
+    //width-3 Poseidon for the commitment, width-5 Poseidon for the nodes
+    commit_poseidon: Pow5Config<Fp, 3, 2>,
+    poseidon: Pow5Config<Fp, 5, 4>,
+
+    //columns for the position selector: the current node, its siblings, the two position
+    //bits and the four children in hashing order
+    select_current: Column<Advice>,
+    select_siblings: [Column<Advice>; ARITY - 1],
+    select_bits: [Column<Advice>; 2],
+    select_children: [Column<Advice>; ARITY],
+
+    //selector to enable the position constraints
+    select_selector: Selector,
+}
+
+impl QuaternaryConfig {
+    /// Allocates the columns, both Poseidon chips and the position selector gate.
+    pub fn configure(meta: &mut ConstraintSystem<Fp>) -> Self {
+        let advice = meta.advice_column();
+        let instance = meta.instance_column();
+
+        //allow equality constraints / copying between cells
+        meta.enable_equality(advice);
+        meta.enable_equality(instance);
+
+        //poseidon columns for the wider permutation; the width-3 chip uses the first three of
+        //each, and the chips have their own selectors, so their gates never apply to each
+        //other's rows
+        let columns = PoseidonColumns::<5>::configure(meta);
+        let commit_poseidon = Pow5Chip::<Fp, 3, 2>::configure::<P128Pow5T3>(
+            meta,
+            [columns.state[0], columns.state[1], columns.state[2]],
+            columns.partial_sbox,
+            [columns.rc_a[0], columns.rc_a[1], columns.rc_a[2]],
+            [columns.rc_b[0], columns.rc_b[1], columns.rc_b[2]],
+        );
+        let poseidon = Pow5Chip::<Fp, 5, 4>::configure::<P128Pow5T5>(
+            meta,
+            columns.state,
+            columns.partial_sbox,
+            columns.rc_a,
+            columns.rc_b,
+        );
+
+        let select_current = meta.advice_column();
+        let select_siblings: [_; ARITY - 1] = std::array::from_fn(|_| meta.advice_column());
+        let select_bits: [_; 2] = std::array::from_fn(|_| meta.advice_column());
+        let select_children: [_; ARITY] = std::array::from_fn(|_| meta.advice_column());
+
+        //enable equality on the current node and children so they can be copied to and from
+        //the hashes
+        meta.enable_equality(select_current);
+        for col in &select_children {
+            meta.enable_equality(*col);
+        }
+
+        let select_selector = meta.selector();
+
+        //create custom gate for the position selector
+        //with position = b0 + 2 * b1, the indicator of each position is
+        //  e0 = (1 - b0)(1 - b1), e1 = b0(1 - b1), e2 = (1 - b0)b1, e3 = b0 * b1
+        //and child j is cur if the position is j, s_j if it is after j and s_(j-1) if before:
+        //  c0 = e0 * cur + (1 - e0) * s0
+        //  c1 = e1 * cur + e0 * s0 + (e2 + e3) * s1
+        //  c2 = e2 * cur + (e0 + e1) * s1 + e3 * s2
+        //  c3 = e3 * cur + (1 - e3) * s2
+        meta.create_gate("position selector", |meta| {
+            let s = meta.query_selector(select_selector);
+            let cur = meta.query_advice(select_current, Rotation::cur());
+            let [s0, s1, s2] = select_siblings.map(|col| meta.query_advice(col, Rotation::cur()));
+            let [b0, b1] = select_bits.map(|col| meta.query_advice(col, Rotation::cur()));
+            let [c0, c1, c2, c3] =
+                select_children.map(|col| meta.query_advice(col, Rotation::cur()));
+
+            let one = || Expression::Constant(Fp::one());
+            let e0 = (one() - b0.clone()) * (one() - b1.clone());
+            let e1 = b0.clone() * (one() - b1.clone());
+            let e2 = (one() - b0.clone()) * b1.clone();
+            let e3 = b0.clone() * b1.clone();
+
+            vec![
+                //constraint 1: both position bits are binary
+                s.clone() * b0.clone() * (one() - b0),
+                s.clone() * b1.clone() * (one() - b1),
+                //constraint 2: each child is cur or the sibling for its slot
+                s.clone() * (c0 - (e0.clone() * cur.clone() + (one() - e0.clone()) * s0.clone())),
+                s.clone()
+                    * (c1
+                        - (e1.clone() * cur.clone()
+                            + e0.clone() * s0
+                            + (e2.clone() + e3.clone()) * s1.clone())),
+                s.clone() * (c2 - (e2 * cur.clone() + (e0 + e1) * s1 + e3.clone() * s2.clone())),
+                s * (c3 - (e3.clone() * cur + (one() - e3) * s2)),
+            ]
+        });
+
+        QuaternaryConfig {
+            advice,
+            instance,
+            commit_poseidon,
+            poseidon,
+            select_current,
+            select_siblings,
+            select_bits,
+            select_children,
+            select_selector,
+        }
+    }
+
+    /// Computes the width-5 Poseidon of four children inside the circuit.
+    pub fn hash_children(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        name: &str,
+        children: [AssignedCell<Fp, Fp>; ARITY],
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        let hasher = Hash::<_, _, P128Pow5T5, ConstantLength<4>, 5, 4>::init(
+            Pow5Chip::<Fp, 5, 4>::construct(self.poseidon.clone()),
+            layouter.namespace(|| format!("init hasher {}", name)),
+        )?;
+        hasher.hash(layouter.namespace(|| format!("hash {}", name)), children)
+    }
+
+    /// Walks the quaternary path from `leaf` up to the root and returns the root cell.
+    /// The path may have any length; the number of levels is the tree depth.
+    ///
+    /// # Arguments
+    /// * `leaf` - The assigned leaf (commitment) cell
+    /// * `siblings` - The three siblings at each level from leaf to root, in child order
+    /// * `positions` - Position (0..=3) of the current node at each level
+    pub fn merkle_root(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        leaf: AssignedCell<Fp, Fp>,
+        siblings: &[[Value<Fp>; ARITY - 1]],
+        positions: &[Value<usize>],
+    ) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+        assert_eq!(siblings.len(), positions.len(), "one position per level");
+        let mut cur_cell = leaf;
+
+        //iterate through each level of the tree, from leaf to root
+        for i in 0..siblings.len() {
+            let children_cells = layouter.assign_region(
+                || format!("position selector level {}", i),
+                |mut region| {
+                    self.select_selector.enable(&mut region, 0)?;
+
+                    //the copy constraint ties this level to the hash computed at the level below
+                    let cur_copy = cur_cell.copy_advice(
+                        || format!("cur {}", i),
+                        &mut region,
+                        self.select_current,
+                        0,
+                    )?;
+
+                    for (j, (&column, &sibling)) in
+                        self.select_siblings.iter().zip(&siblings[i]).enumerate()
+                    {
+                        region.assign_advice(
+                            || format!("sibling {} {}", i, j),
+                            column,
+                            0,
+                            || sibling,
+                        )?;
+                    }
+
+                    //the position as two bits; like the children below, only its low two
+                    //bits are used
+                    for (bit, &column) in self.select_bits.iter().enumerate() {
+                        region.assign_advice(
+                            || format!("position bit {} {}", i, bit),
+                            column,
+                            0,
+                            || positions[i].map(|p| Fp::from(((p >> bit) & 1) as u64)),
+                        )?;
+                    }
+
+                    //compute the children natively; the gate checks them against the inputs
+                    let children = cur_copy
+                        .value()
+                        .copied()
+                        .zip(siblings[i][0])
+                        .zip(siblings[i][1])
+                        .zip(siblings[i][2])
+                        .zip(positions[i])
+                        .map(|((((cur, s0), s1), s2), position)| {
+                            place_children(cur, [s0, s1, s2], position % ARITY)
+                        });
+
+                    let mut cells = Vec::with_capacity(ARITY);
+                    for (j, &column) in self.select_children.iter().enumerate() {
+                        cells.push(region.assign_advice(
+                            || format!("child {} {}", i, j),
+                            column,
+                            0,
+                            || children.map(|c| c[j]),
+                        )?);
+                    }
+                    Ok(cells)
+                },
+            )?;
+
+            let children_cells: [AssignedCell<Fp, Fp>; ARITY] =
+                children_cells.try_into().expect("one cell per child");
+            cur_cell = self.hash_children(layouter, &format!("level {}", i), children_cells)?;
+        }
+
+        Ok(cur_cell)
+    }
+
+    /// Lays out the full membership statement: Poseidon(secret, 0) is a leaf on the given
+    /// quaternary path and the path hashes to the public root in instance row 0.
+    pub fn synthesize_membership(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        secret: Value<Fp>,
+        siblings: &[[Value<Fp>; ARITY - 1]],
+        positions: &[Value<usize>],
+    ) -> std::result::Result<(), plonk::Error> {
+        //derive the commitment Poseidon(secret, 0) exactly as MerkleCircuit does
+        let commitment =
+            circuit_gadgets::commitment(layouter, self.advice, &self.commit_poseidon, secret)?;
+
+        //walk the path from the commitment up to the root
+        let root = self.merkle_root(layouter, commitment, siblings, positions)?;
+
+        //constrain the final hash (root) to equal the public input
+        layouter.constrain_instance(root.cell(), self.instance, 0)
+    }
+}
+
+impl Circuit<Fp> for QuaternaryMerkleCircuit {
+    type Config = QuaternaryConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        QuaternaryConfig::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        config.synthesize_membership(&mut layouter, self.secret, &self.siblings, &self.positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::circuit_cost::measure;
+    use crate::services::prover::K;
+    use halo2_proofs::dev::MockProver;
+
+    /// A full depth-2 tree whose member at index 6 has secret 7.
+    fn member_tree() -> QuaternaryTree {
+        let commitments = (0..16u64)
+            .map(|i| poseidon_commit(if i == 6 { 7 } else { i + 100 }))
+            .collect();
+        QuaternaryTree::new(commitments)
+    }
+
+    fn verifies(circuit: &QuaternaryMerkleCircuit, root: Fp) -> bool {
+        MockProver::run(K, circuit, vec![vec![root]])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_member_proves_at_every_position() {
+        let tree = member_tree();
+        for secret in [100u64, 105, 7, 115] {
+            let (circuit, public_inputs) = QuaternaryMerkleCircuit::build(&tree, secret).unwrap();
+            assert!(verifies(&circuit, public_inputs[0]), "secret {}", secret);
+        }
+    }
+
+    #[test]
+    fn test_wrong_position_fails() {
+        let tree = member_tree();
+        let (mut circuit, public_inputs) = QuaternaryMerkleCircuit::build(&tree, 7).unwrap();
+        circuit.positions[0] = Value::known(3);
+        assert!(!verifies(&circuit, public_inputs[0]));
+    }
+
+    #[test]
+    fn test_wrong_root_fails() {
+        let (circuit, _) = QuaternaryMerkleCircuit::build(&member_tree(), 7).unwrap();
+        assert!(!verifies(&circuit, Fp::from(1u64)));
+    }
+
+    #[test]
+    fn test_build_rejects_non_member_and_other_depths() {
+        assert_eq!(
+            QuaternaryMerkleCircuit::build(&member_tree(), 8).unwrap_err(),
+            ZkProofError::CommitmentNotInTree
+        );
+        let deeper = QuaternaryTree::new((0..17u64).map(poseidon_commit).collect());
+        assert!(matches!(
+            QuaternaryMerkleCircuit::build(&deeper, 3),
+            Err(ZkProofError::DepthUnsupported { tree_depth: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_fits_in_served_k() {
+        let report = measure(
+            QUATERNARY_CIRCUIT_ID,
+            QUATERNARY_DEPTH as u32,
+            &QuaternaryMerkleCircuit::default(),
+            1,
+        )
+        .unwrap();
+        assert!(report.min_k <= K, "K={} but the circuit needs k={}", K, report.min_k);
+    }
+}
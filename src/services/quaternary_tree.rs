@@ -0,0 +1,467 @@
+use ff::{Field, PrimeField};
+use halo2_gadgets::poseidon::primitives::{
+    generate_constants, ConstantLength, Hash as PoseidonHash, Mds, Spec,
+};
+use halo2_proofs::pasta::Fp;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::merkle_tree::LeafValue;
+
+/// Number of children of every internal node.
+pub const ARITY: usize = 4;
+
+/// Poseidon with width 5 and rate 4 over Pallas' base field, so one permutation absorbs all
+/// four children of a node.
+///
+/// Uses the x^5 S-box with R_F = 8 full rounds and R_P = 60 partial rounds. These are the
+/// numbers in the Poseidon paper's table for t = 5 and 128-bit security over a 255-bit prime
+/// field (the `poseidonperm_x5_255_5` instance of the reference implementation). halo2's
+/// `P128Pow5T3` runs the reference `calc_round_numbers.py` for Pallas and gets R_P = 56 at
+/// t = 3; the script asks for no more at t = 5, so 60 keeps the paper's extra margin.
+///
+/// halo2_gadgets only ships the width-3 constants, so these are generated with its Grain LFSR
+/// on first use, which follows the reference `generate_parameters_grain.sage`.
+#[derive(Clone, Copy, Debug)]
+pub struct P128Pow5T5;
+
+/// Round constants and the MDS matrix with its inverse, as returned by `Spec::constants`.
+type Constants = (Vec<[Fp; 5]>, Mds<Fp, 5>, Mds<Fp, 5>);
+
+impl Spec<Fp, 5, 4> for P128Pow5T5 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        60
+    }
+
+    fn sbox(val: Fp) -> Fp {
+        val.pow_vartime([5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+
+    fn constants() -> Constants {
+        //generating the constants costs far more than a hash, and Hash::init asks for them
+        //on every hash
+        static CONSTANTS: OnceLock<Constants> = OnceLock::new();
+        CONSTANTS
+            .get_or_init(generate_constants::<Fp, Self, 5, 4>)
+            .clone()
+    }
+}
+
+/// Hashes the four children of a node, in order, into their parent.
+pub fn hash_children(children: [Fp; ARITY]) -> Fp {
+    PoseidonHash::<Fp, P128Pow5T5, ConstantLength<4>, 5, 4>::init().hash(children)
+}
+
+/// Places `current` at `position` among its three siblings, which keep their order.
+///
+/// # Panics
+/// If `position` is not in 0..ARITY
+pub fn place_children(current: Fp, siblings: [Fp; ARITY - 1], position: usize) -> [Fp; ARITY] {
+    assert!(position < ARITY, "position {} out of range", position);
+    let mut siblings = siblings.into_iter();
+    std::array::from_fn(|slot| {
+        if slot == position {
+            current
+        } else {
+            siblings.next().unwrap()
+        }
+    })
+}
+
+/// Represents a Merkle proof for a leaf of a QuaternaryTree.
+/// Each level holds the node's three siblings and its position among the four children.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuaternaryProof {
+    /// The leaf value being proven
+    pub leaf: Fp,
+    /// The three siblings at each level from leaf to root, in child order
+    pub siblings: Vec<[Fp; ARITY - 1]>,
+    /// Position (0..=3) of the leaf/current node among its parent's children at each level
+    pub positions: Vec<usize>,
+    /// The root hash
+    pub root: Fp,
+}
+
+impl QuaternaryProof {
+    /// Folds the path from the leaf up and returns the root it leads to.
+    /// The leaf is in the tree with a trusted root iff this equals that root.
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::QuaternaryTree;
+    ///
+    /// let tree = QuaternaryTree::new(vec![10u64, 20, 30, 40, 50]);
+    /// let proof = tree.generate_proof(4).unwrap();
+    /// assert_eq!(proof.compute_root(), tree.root());
+    /// ```
+    pub fn compute_root(&self) -> Fp {
+        self.siblings.iter().zip(&self.positions).fold(
+            self.leaf,
+            |current, (&siblings, &position)| {
+                hash_children(place_children(current, siblings, position))
+            },
+        )
+    }
+}
+
+/// Depth of the tree holding `size` leaves, which is padded to the next power of four.
+fn depth_for(size: usize) -> usize {
+    (size.next_power_of_two().trailing_zeros() as usize + 1) / 2
+}
+
+/// A Merkle tree in which every node has four children, hashed with Poseidon of width 5.
+///
+/// It holds as many leaves as a binary tree of twice its depth, so a membership proof takes
+/// half as many hashes (each over a wider state). Leaves are the same commitments as in
+/// `MerkleTree`, but the roots of the two trees differ.
+/// Pads with zeros if the number of leaves is not a power of 4; since zero is the padding
+/// value, it cannot be a leaf.
+#[derive(Clone, Debug)]
+pub struct QuaternaryTree {
+    /// All nodes, organized by levels (0 = leaves including zero-padding, last = root).
+    /// Each level is a quarter of the length of the one below.
+    levels: Vec<Vec<Fp>>,
+    /// The depth of the tree (number of levels from leaf to root, not including leaf level)
+    depth: usize,
+    /// Number of leaves, not counting the zero padding after them
+    size: usize,
+    /// Position of each leaf, keyed by its canonical representation (Fp is not
+    /// Hash). A leaf that occurs more than once maps to its first position.
+    positions: HashMap<[u8; 32], usize>,
+}
+
+impl QuaternaryTree {
+    /// Creates a new quaternary tree from a list of leaf values.
+    /// Leaf values can be either unhashed (u64) or pre-hashed (Fp).
+    /// If the number of leaves is not a power of 4, pads with zeros.
+    ///
+    /// # Arguments
+    /// * `leaves` - Vector of leaf values (can be mixed unhashed and hashed)
+    ///
+    /// # Panics
+    /// If a leaf is zero, the padding value
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::QuaternaryTree;
+    ///
+    /// let tree = QuaternaryTree::new((1..=16u64).collect());
+    /// assert_eq!(tree.depth(), 2);
+    /// ```
+    pub fn new<T: Into<LeafValue>>(leaves: Vec<T>) -> Self {
+        let mut converted_leaves: Vec<Fp> = leaves
+            .into_iter()
+            .map(|leaf| match leaf.into() {
+                LeafValue::Unhashed(val) => Fp::from(val),
+                LeafValue::Hashed(fp) => fp,
+            })
+            .collect();
+        assert!(
+            converted_leaves.iter().all(|leaf| *leaf != Fp::zero()),
+            "zero is the padding value and cannot be a leaf"
+        );
+
+        let size = converted_leaves.len();
+        let depth = depth_for(size);
+        converted_leaves.resize(ARITY.pow(depth as u32), Fp::zero());
+
+        let mut levels = vec![converted_leaves];
+        for level in 0..depth {
+            let next_level = levels[level]
+                .chunks(ARITY)
+                .map(|children| hash_children(children.try_into().unwrap()))
+                .collect();
+            levels.push(next_level);
+        }
+
+        let mut positions = HashMap::new();
+        for (position, leaf) in levels[0][..size].iter().enumerate() {
+            positions.entry(leaf.to_repr()).or_insert(position);
+        }
+
+        QuaternaryTree {
+            levels,
+            depth,
+            size,
+            positions,
+        }
+    }
+
+    /// Adds a new leaf after the last leaf and updates the hashes on its path.
+    /// When the tree is full it grows to four times the width first; the new leaves are all
+    /// zero subtrees, so no existing hash is recomputed.
+    ///
+    /// # Panics
+    /// If the leaf is zero, the padding value
+    ///
+    /// # Example
+    /// ```
+    /// use zk_rust_api::QuaternaryTree;
+    ///
+    /// let mut tree = QuaternaryTree::new(vec![10u64, 20, 30, 40]);
+    /// tree.add(50u64);
+    /// assert_eq!(tree.num_leaves(), 16);
+    /// assert_eq!(tree.root(), QuaternaryTree::new(vec![10u64, 20, 30, 40, 50]).root());
+    /// ```
+    pub fn add<T: Into<LeafValue>>(&mut self, leaf: T) {
+        let fp_leaf = match leaf.into() {
+            LeafValue::Unhashed(val) => Fp::from(val),
+            LeafValue::Hashed(fp) => fp,
+        };
+
+        assert!(fp_leaf != Fp::zero(), "zero is the padding value and cannot be a leaf");
+
+        // The new leaf replaces the first padding leaf (an empty tree is a single zero leaf)
+        let index = self.size;
+        if index == self.num_leaves() {
+            self.grow();
+        }
+        self.levels[0][index] = fp_leaf;
+        self.size += 1;
+        self.positions.entry(fp_leaf.to_repr()).or_insert(index);
+        self.update_path(index);
+    }
+
+    /// Multiplies the number of leaves by four by appending three zero subtrees.
+    fn grow(&mut self) {
+        let mut zero = Fp::zero();
+        for level in &mut self.levels {
+            level.resize(level.len() * ARITY, zero);
+            zero = hash_children([zero; ARITY]);
+        }
+        let top = &self.levels[self.depth];
+        let root = hash_children([top[0], top[1], top[2], top[3]]);
+        self.levels.push(vec![root]);
+        self.depth += 1;
+    }
+
+    /// Recomputes the ancestors of the leaf at `index`.
+    fn update_path(&mut self, mut index: usize) {
+        for level in 0..self.depth {
+            let first = index - index % ARITY;
+            let children = self.levels[level][first..first + ARITY].try_into().unwrap();
+            index /= ARITY;
+            self.levels[level + 1][index] = hash_children(children);
+        }
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root(&self) -> Fp {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Generates a Merkle proof for the leaf at the given index.
+    ///
+    /// # Arguments
+    /// * `leaf_index` - The index of the leaf (0-based, before padding)
+    ///
+    /// # Returns
+    /// * `Some(QuaternaryProof)` if the index is valid
+    /// * `None` if the index is out of bounds
+    pub fn generate_proof(&self, leaf_index: usize) -> Option<QuaternaryProof> {
+        if leaf_index >= self.num_leaves() {
+            return None;
+        }
+
+        let mut siblings = Vec::with_capacity(self.depth);
+        let mut positions = Vec::with_capacity(self.depth);
+        let mut current_index = leaf_index;
+
+        for level in 0..self.depth {
+            let position = current_index % ARITY;
+            let first = current_index - position;
+            let mut others = (first..first + ARITY)
+                .filter(|&i| i != current_index)
+                .map(|i| self.levels[level][i]);
+            siblings.push(std::array::from_fn(|_| others.next().unwrap()));
+            positions.push(position);
+
+            current_index /= ARITY;
+        }
+
+        Some(QuaternaryProof {
+            leaf: self.levels[0][leaf_index],
+            siblings,
+            positions,
+            root: self.root(),
+        })
+    }
+
+    /// Returns the position of `leaf` in O(1), or None if it is not in the tree.
+    /// Zero is the padding value and is never found. A leaf that occurs more than once is
+    /// found at its first position.
+    pub fn index_of(&self, leaf: &Fp) -> Option<usize> {
+        self.positions.get(&leaf.to_repr()).copied()
+    }
+
+    /// Returns the depth of the tree (number of levels from leaf to root, not including the
+    /// leaf level).
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of leaves in the tree (including zero-padding).
+    pub fn num_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns the number of leaves, not counting the zero padding after the last one.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns a reference to the leaves (including zero-padding).
+    pub fn leaves(&self) -> &[Fp] {
+        &self.levels[0]
+    }
+
+    /// Returns every level of the tree, from the leaves (level 0) up to the root.
+    pub fn levels(&self) -> &[Vec<Fp>] {
+        &self.levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_hash_children_matches_reference_vector() {
+        //Poseidon([1, 2, 3, 4]) with t = 5, R_F = 8, R_P = 60 over Pallas, computed with an
+        //independent port of the reference Grain/Cauchy parameter generator and permutation
+        //(the same port reproduces halo2's width-3 round constants); halo2_gadgets publishes
+        //no width-5 vector, so this pins the constants, MDS matrix and round schedule
+        let expected = Fp::from_raw([
+            0x1d9b_252c_183b_538d,
+            0xf4ab_8623_818c_ffb7,
+            0x6ab7_0f7e_e579_e34c,
+            0x11db_25f8_eeff_5b04,
+        ]);
+        assert_eq!(hash_children([1u64, 2, 3, 4].map(Fp::from)), expected);
+
+        let (round_constants, _, _) = P128Pow5T5::constants();
+        assert_eq!(round_constants.len(), 8 + 60);
+    }
+
+    #[test]
+    fn test_tree_pads_to_power_of_four() {
+        let tree = QuaternaryTree::new(vec![10u64, 20, 30, 40, 50]);
+        assert_eq!(tree.num_leaves(), 16);
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.size(), 5);
+        assert_eq!(tree.levels()[1].len(), 4);
+
+        let empty = QuaternaryTree::new(Vec::<Fp>::new());
+        assert_eq!(empty.num_leaves(), 1);
+        assert_eq!(empty.depth(), 0);
+    }
+
+    #[test]
+    fn test_depth_is_half_the_binary_depth() {
+        for count in [4u64, 16, 64, 256, 1000] {
+            let leaves: Vec<u64> = (1..=count).collect();
+            let binary = MerkleTree::new(leaves.clone());
+            let quaternary = QuaternaryTree::new(leaves);
+            assert_eq!(quaternary.depth(), binary.depth().div_ceil(2), "{} leaves", count);
+        }
+    }
+
+    #[test]
+    fn test_root_hashes_children_in_order() {
+        let tree = QuaternaryTree::new(vec![1u64, 2, 3, 4]);
+        let children = [1u64, 2, 3, 4].map(Fp::from);
+        assert_eq!(tree.root(), hash_children(children));
+        assert_ne!(tree.root(), QuaternaryTree::new(vec![2u64, 1, 3, 4]).root());
+    }
+
+    #[test]
+    fn test_add_matches_building_from_scratch() {
+        let mut tree = QuaternaryTree::new(Vec::<Fp>::new());
+        for count in 1..=70u64 {
+            tree.add(count);
+            let rebuilt = QuaternaryTree::new((1..=count).collect());
+            assert_eq!(tree.levels(), rebuilt.levels(), "{} leaves", count);
+            assert_eq!(tree.depth(), rebuilt.depth());
+        }
+    }
+
+    #[test]
+    fn test_size_counts_added_leaves() {
+        let mut tree = QuaternaryTree::new(Vec::<Fp>::new());
+        assert_eq!(tree.size(), 0);
+        for count in 1..=17u64 {
+            tree.add(count);
+            assert_eq!(tree.size(), count as usize);
+        }
+        assert_eq!(tree.num_leaves(), 64);
+    }
+
+    #[test]
+    #[should_panic(expected = "zero is the padding value")]
+    fn test_add_rejects_zero_leaf() {
+        QuaternaryTree::new(vec![10u64, 20]).add(0u64);
+    }
+
+    #[test]
+    #[should_panic(expected = "zero is the padding value")]
+    fn test_new_rejects_zero_leaf() {
+        QuaternaryTree::new(vec![10u64, 0, 20]);
+    }
+
+    #[test]
+    fn test_index_of_tracks_built_and_added_leaves() {
+        let mut tree = QuaternaryTree::new(vec![10u64, 20, 30, 40]);
+        assert_eq!(tree.index_of(&Fp::from(30u64)), Some(2));
+
+        //growing the tree keeps existing positions
+        tree.add(50u64);
+        assert_eq!(tree.index_of(&Fp::from(50u64)), Some(4));
+        assert_eq!(tree.index_of(&Fp::from(10u64)), Some(0));
+
+        //the zero padding is not a leaf, and a repeated leaf is found at its first position
+        assert_eq!(tree.index_of(&Fp::zero()), None);
+        let repeated = QuaternaryTree::new(vec![7u64, 8, 7]);
+        assert_eq!(repeated.index_of(&Fp::from(7u64)), Some(0));
+    }
+
+    #[test]
+    fn test_every_proof_leads_to_the_root() {
+        let tree = QuaternaryTree::new((1..=50u64).collect());
+        for index in 0..tree.num_leaves() {
+            let proof = tree.generate_proof(index).unwrap();
+            assert_eq!(proof.siblings.len(), tree.depth());
+            assert_eq!(proof.positions[0], index % 4);
+            assert_eq!(proof.compute_root(), tree.root(), "leaf {}", index);
+        }
+        assert!(tree.generate_proof(tree.num_leaves()).is_none());
+    }
+
+    #[test]
+    fn test_proof_with_wrong_position_fails() {
+        let tree = QuaternaryTree::new((1..=16u64).collect());
+        let mut proof = tree.generate_proof(6).unwrap();
+        proof.positions[0] = 1;
+        assert_ne!(proof.compute_root(), tree.root());
+    }
+
+    #[test]
+    fn test_place_children_keeps_sibling_order() {
+        let siblings = [1u64, 2, 3].map(Fp::from);
+        let current = Fp::from(9u64);
+        for position in 0..ARITY {
+            let children = place_children(current, siblings, position);
+            assert_eq!(children[position], current);
+            let rest: Vec<Fp> = children.into_iter().filter(|&c| c != current).collect();
+            assert_eq!(rest, siblings);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use ff::{Field, PrimeField};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    pasta::Fp,
+    plonk::{
+        self, Advice, Circuit, Column, ConstraintSystem, Expression, Instance, Selector,
+        TableColumn,
+    },
+    poly::Rotation,
+};
+use rust_api::prelude::*;
+
+use super::circuit_registry::CircuitDefinition;
+use super::merkle_tree::MerkleTree;
+use super::zk_error::ZkProofError;
+
+/// Number of bits checked per lookup.
+pub const LIMB_BITS: usize = 8;
+
+/// Number of limbs per range check; values are checked to lie in [0, 2^(LIMB_BITS * NUM_LIMBS)).
+pub const NUM_LIMBS: usize = 8;
+
+/// Stable identifier for the standalone range circuit, recorded in proof envelopes.
+pub const RANGE_CIRCUIT_ID: &str = "range-v1";
+
+/// k=9 fits the 256-row lookup table plus blinding rows.
+pub const RANGE_K: u32 = 9;
+
+/// Lookup-based range check gadget.
+///
+/// Proves `0 <= lhs - rhs < 2^64` by decomposing the difference into 8-bit limbs with a
+/// running sum: `z_0 = lhs - rhs`, `z_{i+1} = (z_i - limb_i) / 2^8`. Each limb
+/// `z_i - 2^8 * z_{i+1}` is looked up in a 256-row table and the final `z_8` is constrained
+/// to zero. Checking `value - min` and `max - value` this way proves `min <= value <= max`
+/// for 64-bit bounds.
+#[derive(Clone, Debug)]
+pub struct RangeCheckConfig {
+    //operands of the subtraction whose result is range checked
+    lhs: Column<Advice>,
+    rhs: Column<Advice>,
+    //running sum z_i of the limb decomposition
+    running_sum: Column<Advice>,
+
+    //enforces z_0 = lhs - rhs on the first row of a range check region
+    q_diff: Selector,
+    //enables the limb lookup on each decomposition row
+    q_lookup: Selector,
+    //table of all LIMB_BITS-bit values
+    table: TableColumn,
+}
+
+impl RangeCheckConfig {
+    /// Allocates the columns, subtraction gate and limb lookup for the gadget.
+    /// The circuit must also enable at least one constant column, which is used to
+    /// constrain the final running sum to zero.
+    pub fn configure(meta: &mut ConstraintSystem<Fp>) -> Self {
+        let lhs = meta.advice_column();
+        let rhs = meta.advice_column();
+        let running_sum = meta.advice_column();
+        meta.enable_equality(lhs);
+        meta.enable_equality(rhs);
+        meta.enable_equality(running_sum);
+
+        let q_diff = meta.selector();
+        let q_lookup = meta.complex_selector();
+        let table = meta.lookup_table_column();
+
+        //z_0 = lhs - rhs
+        meta.create_gate("range difference", |meta| {
+            let q = meta.query_selector(q_diff);
+            let lhs = meta.query_advice(lhs, Rotation::cur());
+            let rhs = meta.query_advice(rhs, Rotation::cur());
+            let z = meta.query_advice(running_sum, Rotation::cur());
+            vec![q * (z - (lhs - rhs))]
+        });
+
+        //limb_i = z_i - 2^LIMB_BITS * z_{i+1} must be in the table
+        meta.lookup(|meta| {
+            let q = meta.query_selector(q_lookup);
+            let z_cur = meta.query_advice(running_sum, Rotation::cur());
+            let z_next = meta.query_advice(running_sum, Rotation::next());
+            let limb = z_cur - z_next * Expression::Constant(Fp::from(1u64 << LIMB_BITS));
+            vec![(q * limb, table)]
+        });
+
+        Self {
+            lhs,
+            rhs,
+            running_sum,
+            q_diff,
+            q_lookup,
+            table,
+        }
+    }
+
+    /// Loads the limb lookup table. Must be called once per synthesis.
+    pub fn load_table(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        layouter.assign_table(
+            || "range check table",
+            |mut table| {
+                for i in 0..(1 << LIMB_BITS) {
+                    table.assign_cell(
+                        || format!("limb {}", i),
+                        self.table,
+                        i,
+                        || Value::known(Fp::from(i as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Constrains `0 <= lhs - rhs < 2^(LIMB_BITS * NUM_LIMBS)`.
+    pub fn assert_difference_in_range(
+        &self,
+        layouter: &mut impl Layouter<Fp>,
+        name: &str,
+        lhs: &AssignedCell<Fp, Fp>,
+        rhs: &AssignedCell<Fp, Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        layouter.assign_region(
+            || format!("range check {}", name),
+            |mut region| {
+                self.q_diff.enable(&mut region, 0)?;
+                let lhs = lhs.copy_advice(|| "lhs", &mut region, self.lhs, 0)?;
+                let rhs = rhs.copy_advice(|| "rhs", &mut region, self.rhs, 0)?;
+
+                let mut z_val = lhs.value().zip(rhs.value()).map(|(l, r)| *l - *r);
+                let mut z = region.assign_advice(|| "z_0", self.running_sum, 0, || z_val)?;
+
+                let shift_inv = Fp::from(1u64 << LIMB_BITS).invert().unwrap();
+                for i in 0..NUM_LIMBS {
+                    self.q_lookup.enable(&mut region, i)?;
+                    //z_{i+1} = (z_i - low limb of z_i) / 2^LIMB_BITS
+                    z_val = z_val.map(|v| (v - low_limb(&v)) * shift_inv);
+                    z = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        self.running_sum,
+                        i + 1,
+                        || z_val,
+                    )?;
+                }
+
+                //all bits consumed: anything left over means the difference was out of range
+                region.constrain_constant(z.cell(), Fp::zero())
+            },
+        )
+    }
+}
+
+/// Returns the lowest LIMB_BITS bits of a field element.
+fn low_limb(value: &Fp) -> Fp {
+    Fp::from(value.to_repr()[0] as u64)
+}
+
+/// Standalone circuit proving that a private value lies in the public range [min, max].
+///
+/// Public inputs (instance column rows): 0 = min, 1 = max.
+#[derive(Clone, Debug)]
+pub struct RangeCircuit {
+    /// Private value whose range is proven
+    pub value: Value<Fp>,
+}
+
+impl Default for RangeCircuit {
+    /// A circuit with an unknown value, suitable for key generation.
+    fn default() -> Self {
+        Self {
+            value: Value::unknown(),
+        }
+    }
+}
+
+/// Private value and public bounds for a standalone range proof request.
+#[derive(Debug, Deserialize)]
+pub struct ValueRangeWitness {
+    pub value: u64,
+    /// Inclusive lower bound (public)
+    pub min: u64,
+    /// Inclusive upper bound (public)
+    pub max: u64,
+}
+
+impl CircuitDefinition for RangeCircuit {
+    type Witness = ValueRangeWitness;
+
+    const ID: &'static str = RANGE_CIRCUIT_ID;
+    const PUBLIC_INPUTS: &'static [&'static str] = &["min", "max"];
+
+    fn k(&self) -> u32 {
+        RANGE_K
+    }
+
+    //the circuit does not involve the tree
+    fn depth(&self) -> u32 {
+        0
+    }
+
+    fn build(
+        &self,
+        _tree: &MerkleTree,
+        witness: Self::Witness,
+    ) -> std::result::Result<(Self, Vec<Fp>), ZkProofError> {
+        if !(witness.min..=witness.max).contains(&witness.value) {
+            return Err(ZkProofError::InvalidWitness {
+                reason: format!("value is not within [{}, {}]", witness.min, witness.max),
+            });
+        }
+
+        let circuit = RangeCircuit {
+            value: Value::known(Fp::from(witness.value)),
+        };
+        Ok((circuit, vec![Fp::from(witness.min), Fp::from(witness.max)]))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RangeCircuitConfig {
+    advice: Column<Advice>,
+    instance: Column<Instance>,
+    range: RangeCheckConfig,
+}
+
+impl Circuit<Fp> for RangeCircuit {
+    type Config = RangeCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let advice = meta.advice_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(advice);
+        meta.enable_equality(instance);
+
+        //needed to constrain the final running sum to zero
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+
+        RangeCircuitConfig {
+            advice,
+            instance,
+            range: RangeCheckConfig::configure(meta),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> std::result::Result<(), plonk::Error> {
+        config.range.load_table(&mut layouter)?;
+
+        let (value, min, max) = layouter.assign_region(
+            || "assign value and bounds",
+            |mut region| {
+                let value = region.assign_advice(|| "value", config.advice, 0, || self.value)?;
+                let min = region.assign_advice_from_instance(
+                    || "min",
+                    config.instance,
+                    0,
+                    config.advice,
+                    1,
+                )?;
+                let max = region.assign_advice_from_instance(
+                    || "max",
+                    config.instance,
+                    1,
+                    config.advice,
+                    2,
+                )?;
+                Ok((value, min, max))
+            },
+        )?;
+
+        config
+            .range
+            .assert_difference_in_range(&mut layouter, "value - min", &value, &min)?;
+        config
+            .range
+            .assert_difference_in_range(&mut layouter, "max - value", &max, &value)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    fn run(value: u64, min: u64, max: u64) -> bool {
+        let circuit = RangeCircuit {
+            value: Value::known(Fp::from(value)),
+        };
+        let prover =
+            MockProver::run(RANGE_K, &circuit, vec![vec![Fp::from(min), Fp::from(max)]]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    #[test]
+    fn test_value_in_range() {
+        assert!(run(30, 18, 65));
+    }
+
+    #[test]
+    fn test_value_on_bounds() {
+        assert!(run(18, 18, 65), "lower bound is inclusive");
+        assert!(run(65, 18, 65), "upper bound is inclusive");
+        assert!(run(0, 0, u64::MAX), "full 64-bit range");
+        assert!(run(u64::MAX, 0, u64::MAX), "full 64-bit range");
+    }
+
+    #[test]
+    fn test_value_below_range() {
+        assert!(!run(17, 18, 65));
+    }
+
+    #[test]
+    fn test_value_above_range() {
+        assert!(!run(66, 18, 65));
+    }
+
+    #[test]
+    fn test_empty_range() {
+        assert!(!run(10, 20, 5), "min > max admits no value");
+    }
+
+    #[test]
+    fn test_build_rejects_value_outside_bounds() {
+        let tree = MerkleTree::new(Vec::<u64>::new());
+        let witness = ValueRangeWitness {
+            value: 30,
+            min: 18,
+            max: 65,
+        };
+        let (_, public_inputs) = RangeCircuit::default().build(&tree, witness).unwrap();
+        assert_eq!(public_inputs, vec![Fp::from(18u64), Fp::from(65u64)]);
+
+        let witness = ValueRangeWitness {
+            value: 10,
+            min: 18,
+            max: 65,
+        };
+        assert!(RangeCircuit::default().build(&tree, witness).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use rust_api::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::fp_hex::FpHex;
+use super::merkle_tree_service::MerkleTreeService;
+
+/// Number of leaf events buffered per subscriber before it is considered lagged.
+pub const EVENT_BUFFER: usize = 1024;
+
+/// Number of existing leaves a subscription reads from the tree at a time while replaying.
+pub const BACKLOG_PAGE: usize = 256;
+
+/// A leaf was appended to the tree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeafEvent {
+    /// Position of the new leaf
+    pub index: usize,
+    pub commitment: FpHex,
+    /// Root right after this leaf was appended, also for leaves imported in one batch
+    pub root: FpHex,
+}
+
+/// The subscriber fell more than EVENT_BUFFER events behind and missed some.
+/// It should resubscribe from `resume_from` (or refetch the leaves it lacks).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedLagged {
+    pub resume_from: usize,
+}
+
+/// Leaf events from a given index onwards: first the leaves that were already in the tree,
+/// then every insertion as it happens. Each index is delivered exactly once, in order.
+pub struct LeafSubscription {
+    service: Arc<MerkleTreeService>,
+    /// Leaves below this index were in the tree when the subscription started and are read
+    /// from it in pages of BACKLOG_PAGE
+    backlog_end: usize,
+    /// Backlog events read from the tree but not yet delivered
+    page: VecDeque<LeafEvent>,
+    receiver: broadcast::Receiver<LeafEvent>,
+    /// Index of the next leaf the subscriber has not seen
+    next_index: usize,
+    lagged: bool,
+}
+
+impl LeafSubscription {
+    /// Creates a subscription from leaf `from` for a tree of `size` leaves, with a receiver
+    /// subscribed while the same tree lock was held, so no insertion falls between the two.
+    pub(crate) fn new(
+        service: Arc<MerkleTreeService>,
+        from: usize,
+        size: usize,
+        receiver: broadcast::Receiver<LeafEvent>,
+    ) -> Self {
+        Self {
+            service,
+            backlog_end: size,
+            page: VecDeque::new(),
+            receiver,
+            next_index: from,
+            lagged: false,
+        }
+    }
+
+    /// Waits for the next leaf event.
+    ///
+    /// # Returns
+    /// * `Some(Ok(event))` for each leaf in index order
+    /// * `Some(Err(FeedLagged))` once if events were dropped, after which the feed ends
+    /// * `None` when the feed has ended, or the tree could not be read
+    pub async fn next(&mut self) -> Option<std::result::Result<LeafEvent, FeedLagged>> {
+        if self.lagged {
+            return None;
+        }
+        if self.page.is_empty() && self.next_index < self.backlog_end {
+            let end = self.backlog_end.min(self.next_index + BACKLOG_PAGE);
+            //the feed ends if the tree can no longer be read
+            self.page = self.service.leaf_events(self.next_index..end).ok()?.into();
+        }
+        if let Some(event) = self.page.pop_front() {
+            self.next_index = event.index + 1;
+            return Some(Ok(event));
+        }
+
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.index < self.next_index => continue,
+                Ok(event) => {
+                    self.next_index = event.index + 1;
+                    return Some(Ok(event));
+                },
+                Err(RecvError::Lagged(_)) => {
+                    self.lagged = true;
+                    return Some(Err(FeedLagged {
+                        resume_from: self.next_index,
+                    }));
+                },
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
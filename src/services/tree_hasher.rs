@@ -0,0 +1,105 @@
+use ff::{FromUniformBytes, PrimeField};
+use halo2_gadgets::poseidon::primitives::{ConstantLength, Hash as PoseidonHash, P128Pow5T3, Spec};
+use halo2_proofs::pasta::Fp;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Two-to-one hash that combines sibling nodes into their parent.
+///
+/// `MerkleTree` is generic over it so a tree can use another Poseidon spec or a non-algebraic
+/// hash. Only `PoseidonHasher` with the default spec is provable by the membership circuits;
+/// other hashers are for trees that are checked natively.
+pub trait TreeHasher: Clone + fmt::Debug + Send + Sync {
+    /// Hashes a left and right child into their parent node.
+    fn hash_pair(&self, left: Fp, right: Fp) -> Fp;
+}
+
+/// Poseidon with width 3 and rate 2 over Pallas' base field, as used by the circuits.
+/// `S` selects the round constants and number of rounds; the default is `P128Pow5T3`.
+pub struct PoseidonHasher<S = P128Pow5T3> {
+    spec: PhantomData<fn() -> S>,
+}
+
+impl PoseidonHasher {
+    /// The hasher of the membership circuits. Other specs are created with
+    /// `PoseidonHasher::<S>::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+//implemented by hand so S itself does not have to be Clone, Debug or Default
+impl<S> Default for PoseidonHasher<S> {
+    fn default() -> Self {
+        Self { spec: PhantomData }
+    }
+}
+
+impl<S> Clone for PoseidonHasher<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for PoseidonHasher<S> {}
+
+impl<S> fmt::Debug for PoseidonHasher<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PoseidonHasher<{}>", std::any::type_name::<S>())
+    }
+}
+
+impl<S: Spec<Fp, 3, 2>> TreeHasher for PoseidonHasher<S> {
+    fn hash_pair(&self, left: Fp, right: Fp) -> Fp {
+        PoseidonHash::<Fp, S, ConstantLength<2>, 3, 2>::init().hash([left, right])
+    }
+}
+
+/// SHA-256 over the two children's 32-byte little-endian representations, for trees that
+/// are never proven in a circuit.
+///
+/// The 256-bit digest is reduced modulo the field order so every node stays an `Fp`; a
+/// digest at or above the modulus therefore differs from the raw SHA-256 output. This is not
+/// the RFC 6962 (Certificate Transparency) tree hash: there is no 0x00/0x01 leaf and node
+/// prefix, and trees are zero-padded to a power of two, so roots do not match an RFC 6962
+/// log over the same entries.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256Hasher;
+
+impl TreeHasher for Sha256Hasher {
+    fn hash_pair(&self, left: Fp, right: Fp) -> Fp {
+        let digest = Sha256::new()
+            .chain_update(left.to_repr())
+            .chain_update(right.to_repr())
+            .finalize();
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&digest);
+        Fp::from_uniform_bytes(&wide)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::fp_hex::parse_fp_hex;
+
+    #[test]
+    fn test_poseidon_matches_the_primitive() {
+        let (left, right) = (Fp::from(1u64), Fp::from(2u64));
+        let expected =
+            PoseidonHash::<Fp, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash([left, right]);
+        assert_eq!(PoseidonHasher::<P128Pow5T3>::new().hash_pair(left, right), expected);
+    }
+
+    #[test]
+    fn test_sha256_reduces_the_digest() {
+        let (left, right) = (Fp::from(1u64), Fp::from(2u64));
+        //SHA-256(le(1) || le(2)) = ff55c979...0f70a194 (little-endian) is above the modulus,
+        //so the node is the digest reduced modulo p
+        let expected =
+            parse_fp_hex("fd55c9799c46e68197e7cada51b1ec0094ba3f675238b5fd25d282b60f70a114");
+        assert_eq!(Sha256Hasher.hash_pair(left, right), expected.unwrap());
+        assert_ne!(Sha256Hasher.hash_pair(left, right), Sha256Hasher.hash_pair(right, left));
+    }
+}
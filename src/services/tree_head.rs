@@ -0,0 +1,174 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ff::PrimeField;
+use halo2_proofs::pasta::Fp;
+use rust_api::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::fp_hex::FpHex;
+
+/// Prefix of every signed tree head message, so the signature cannot be replayed as a
+/// signature over anything else the key signs.
+pub const TREE_HEAD_DOMAIN: &[u8] = b"zk-rust-api/tree-head/v1";
+
+/// A checkpoint of the tree signed by the server: the server committed to `root` being the
+/// root of its first `size` leaves at `timestamp`.
+///
+/// Anyone holding the server's public key can check it, so a client can prove to a third
+/// party which root the server published. Together with a consistency proof between two heads
+/// it shows the server never rewrote history in between.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    /// Number of leaves
+    pub size: usize,
+    pub root: FpHex,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// ed25519 signature over `message(size, root, timestamp)`, 128 hex chars
+    pub signature: String,
+}
+
+impl SignedTreeHead {
+    /// Signs a tree head with the current time.
+    pub fn sign(key: &SigningKey, size: usize, root: Fp) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self::sign_at(key, size, root, timestamp)
+    }
+
+    /// Signs a tree head with the given timestamp.
+    pub fn sign_at(key: &SigningKey, size: usize, root: Fp, timestamp: u64) -> Self {
+        let signature = key.sign(&Self::message(size, &root, timestamp));
+        Self {
+            size,
+            root: FpHex(root),
+            timestamp,
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Returns true if the head was signed by the holder of `key`.
+    /// A malformed signature is treated as invalid.
+    ///
+    /// # Example
+    /// ```
+    /// use ed25519_dalek::SigningKey;
+    /// use zk_rust_api::{services::tree_head::SignedTreeHead, Fp};
+    ///
+    /// let key = SigningKey::from_bytes(&[7u8; 32]);
+    /// let head = SignedTreeHead::sign(&key, 4, Fp::from(42u64));
+    /// assert!(head.verify(&key.verifying_key()));
+    /// ```
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        let mut bytes = [0u8; 64];
+        if hex::decode_to_slice(&self.signature, &mut bytes).is_err() {
+            return false;
+        }
+        let message = Self::message(self.size, &self.root.0, self.timestamp);
+        key.verify(&message, &Signature::from_bytes(&bytes)).is_ok()
+    }
+
+    /// The signed bytes: the domain, then size and timestamp as little-endian u64 around the
+    /// 32-byte root representation.
+    pub fn message(size: usize, root: &Fp, timestamp: u64) -> Vec<u8> {
+        let mut message = TREE_HEAD_DOMAIN.to_vec();
+        message.extend_from_slice(&(size as u64).to_le_bytes());
+        message.extend_from_slice(&root.to_repr());
+        message.extend_from_slice(&timestamp.to_le_bytes());
+        message
+    }
+}
+
+/// The key tree heads are verified with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKeyResponse {
+    /// Always `ed25519`
+    pub algorithm: String,
+    /// 64 hex chars
+    pub public_key: String,
+}
+
+impl PublicKeyResponse {
+    /// Describes an ed25519 public key.
+    pub fn new(key: &VerifyingKey) -> Self {
+        Self {
+            algorithm: "ed25519".to_string(),
+            public_key: hex::encode(key.as_bytes()),
+        }
+    }
+
+    /// Decodes the public key, or None if it is not a valid ed25519 key.
+    pub fn verifying_key(&self) -> Option<VerifyingKey> {
+        parse_public_key(&self.public_key)
+    }
+}
+
+/// Decodes a hex ed25519 public key, e.g. one pinned in a client's configuration.
+pub fn parse_public_key(hex_key: &str) -> Option<VerifyingKey> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hex_key.trim(), &mut bytes).ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Decodes a signing key from its 32-byte seed as 64 hex chars (the `signing.key_file`
+/// format). Surrounding whitespace is ignored.
+pub fn parse_signing_key(hex_seed: &str) -> Option<SigningKey> {
+    let mut seed = [0u8; 32];
+    hex::decode_to_slice(hex_seed.trim(), &mut seed).ok()?;
+    Some(SigningKey::from_bytes(&seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::merkle_tree_service::poseidon_commit;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_signed_head_verifies_with_signer_key_only() {
+        let head = SignedTreeHead::sign_at(&key(1), 5, poseidon_commit(42), 1_700_000_000_000);
+        assert!(head.verify(&key(1).verifying_key()));
+        assert!(!head.verify(&key(2).verifying_key()));
+    }
+
+    #[test]
+    fn test_any_changed_field_breaks_the_signature() {
+        let head = SignedTreeHead::sign_at(&key(1), 5, poseidon_commit(42), 1_700_000_000_000);
+        let public = key(1).verifying_key();
+
+        let mut changed = head.clone();
+        changed.size = 6;
+        assert!(!changed.verify(&public));
+        changed = head.clone();
+        changed.root = FpHex(poseidon_commit(43));
+        assert!(!changed.verify(&public));
+        changed = head.clone();
+        changed.timestamp += 1;
+        assert!(!changed.verify(&public));
+        changed = head;
+        changed.signature.truncate(10);
+        assert!(!changed.verify(&public));
+    }
+
+    #[test]
+    fn test_keys_round_trip_through_hex() {
+        let signing = parse_signing_key(&format!("{}\n", hex::encode([9u8; 32]))).unwrap();
+        assert_eq!(signing, key(9));
+        assert!(parse_signing_key("abcd").is_none());
+
+        let response = PublicKeyResponse::new(&signing.verifying_key());
+        assert_eq!(response.verifying_key(), Some(signing.verifying_key()));
+        assert!(parse_public_key("zz").is_none());
+    }
+
+    #[test]
+    fn test_head_json_round_trip() {
+        let head = SignedTreeHead::sign(&key(3), 1, poseidon_commit(7));
+        let json = serde_json::to_string(&head).unwrap();
+        let decoded: SignedTreeHead = serde_json::from_str(&json).unwrap();
+        assert!(decoded.verify(&key(3).verifying_key()));
+    }
+}
@@ -0,0 +1,90 @@
+use halo2_proofs::{dev::VerifyFailure, plonk};
+use rust_api::prelude::*;
+use std::fmt;
+
+/// Why a membership proof could not be produced or did not verify.
+/// Converted into an ApiError whose `code` lets clients tell the cases apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZkProofError {
+    /// The prover's commitment is not a leaf of the current tree
+    CommitmentNotInTree,
+    /// The tree has grown to a depth the circuit was not built for
+    DepthUnsupported {
+        tree_depth: usize,
+        circuit_depth: usize,
+    },
+    /// The witness is well-formed but cannot satisfy the circuit (e.g. attribute out of range)
+    InvalidWitness { reason: String },
+    /// Circuit synthesis failed before any constraint could be checked
+    Synthesis { reason: String },
+    /// The circuit was synthesized but constraints are not satisfied
+    VerificationFailed {
+        /// One entry per failure, naming the gate/constraint or the violated copy constraint
+        failures: Vec<String>,
+    },
+}
+
+impl ZkProofError {
+    /// Stable, machine-readable name of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ZkProofError::CommitmentNotInTree => "commitment_not_in_tree",
+            ZkProofError::DepthUnsupported { .. } => "depth_unsupported",
+            ZkProofError::InvalidWitness { .. } => "invalid_witness",
+            ZkProofError::Synthesis { .. } => "synthesis",
+            ZkProofError::VerificationFailed { .. } => "verification_failed",
+        }
+    }
+
+    /// HTTP status code for this error.
+    /// Client-side problems (unknown commitment, bad witness) are 4xx; failures inside the
+    /// prover for a witness that should have been valid are 5xx.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ZkProofError::CommitmentNotInTree => StatusCode::NOT_FOUND,
+            ZkProofError::DepthUnsupported { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ZkProofError::InvalidWitness { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ZkProofError::Synthesis { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ZkProofError::VerificationFailed { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ZkProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZkProofError::CommitmentNotInTree => write!(f, "commitment is not in the tree"),
+            ZkProofError::DepthUnsupported {
+                tree_depth,
+                circuit_depth,
+            } => write!(
+                f,
+                "tree depth {} does not match circuit depth {}",
+                tree_depth, circuit_depth
+            ),
+            ZkProofError::InvalidWitness { reason } => write!(f, "invalid witness: {}", reason),
+            ZkProofError::Synthesis { reason } => write!(f, "circuit synthesis failed: {}", reason),
+            ZkProofError::VerificationFailed { failures } => {
+                write!(f, "constraints not satisfied: {}", failures.join("; "))
+            },
+        }
+    }
+}
+
+impl std::error::Error for ZkProofError {}
+
+impl From<plonk::Error> for ZkProofError {
+    fn from(e: plonk::Error) -> Self {
+        ZkProofError::Synthesis {
+            reason: format!("{:?}", e),
+        }
+    }
+}
+
+impl From<Vec<VerifyFailure>> for ZkProofError {
+    fn from(failures: Vec<VerifyFailure>) -> Self {
+        ZkProofError::VerificationFailed {
+            failures: failures.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+}
@@ -1,10 +1,22 @@
-use halo2_proofs::dev::MockProver;
-use halo2_proofs::{circuit::Value, pasta::Fp};
+use halo2_proofs::{dev::MockProver, pasta::Fp};
 use rust_api::prelude::*;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
-use super::merkle_circuit::{MerkleCircuit, DEPTH};
-use super::merkle_tree_service::{poseidon_commit, MerkleTreeService};
+use crate::config::ProvingConfig;
+use crate::error::ApiError;
+
+use super::batch::{prove_batch, verify_batch, BatchProof};
+use super::circuit_cost::{membership_cost_by_depth, CostReport, MAX_REPORT_DEPTH};
+use super::circuit_registry::{
+    CircuitDefinition, CircuitEntry, CircuitInfo, CircuitRegistry, ServedCircuit,
+};
+use super::merkle_circuit::{MembershipWitness, MerkleCircuit, CIRCUIT_ID};
+use super::merkle_range_circuit::MerkleRangeCircuit;
+use super::merkle_tree_service::{fp_to_hex, FpHex, MerkleTreeService};
+use super::proof_envelope::ProofEnvelope;
+use super::prover::MerkleKeys;
+use super::range_check::RangeCircuit;
+use super::zk_error::ZkProofError;
 
 /// Response type for zero-knowledge proof verification.
 /// Contains a boolean indicating whether the proof is valid.
@@ -13,10 +25,36 @@ pub struct ZKProofResponse {
     pub proof: bool,
 }
 
+/// Response type for proof envelope and batch proof verification.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZKVerifyResponse {
+    pub valid: bool,
+}
+
+/// Response type for the batch proof endpoint.
+/// Carries one proof covering `count` membership proofs against `root`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchProofResponse {
+    /// Root every membership proof in the batch is against
+    pub root: FpHex,
+    /// Number of membership proofs in the batch
+    pub count: usize,
+    /// Hex-encoded proof bytes
+    pub proof: String,
+}
+
 /// Service for generating and verifying zero-knowledge proofs using Merkle trees.
 /// Uses MerkleTreeService to access the shared default Merkle tree.
+/// Circuits are served through a CircuitRegistry, so new circuits only need to be registered here.
 pub struct ZKService {
     tree_service: Arc<MerkleTreeService>,
+    /// Every circuit that can be proven and verified by id
+    registry: CircuitRegistry,
+    /// Typed handle to the membership circuit entry, whose keys are also used for batch proofs
+    membership: Arc<CircuitEntry<MerkleCircuit>>,
+    /// Circuit cost report, computed on first request since it runs trial keygens
+    cost_report: OnceLock<CostReport>,
+    limits: ProvingConfig,
 }
 
 impl Injectable for ZKService {}
@@ -25,7 +63,36 @@ impl ZKService {
     /// Creates a new ZKService with a reference to the MerkleTreeService.
     /// The tree is accessed from MerkleTreeService, which maintains the shared default tree.
     pub fn new(tree_service: Arc<MerkleTreeService>) -> Self {
-        Self { tree_service }
+        Self::with_config(tree_service, &ProvingConfig::default())
+    }
+
+    /// Creates a ZKService that enforces the given proving limits.
+    /// The Merkle circuits are built for the tree service's maximum depth, so every tree it
+    /// can hold is provable.
+    pub fn with_config(tree_service: Arc<MerkleTreeService>, limits: &ProvingConfig) -> Self {
+        let depth = tree_service.max_depth();
+        let mut registry = CircuitRegistry::new();
+        let membership = registry.register(MerkleCircuit::new(depth));
+        registry.register(MerkleRangeCircuit::new(depth));
+        registry.register(RangeCircuit::default());
+
+        Self {
+            tree_service,
+            registry,
+            membership,
+            cost_report: OnceLock::new(),
+            limits: limits.clone(),
+        }
+    }
+
+    /// Returns the MerkleCircuit keys, generating them on first use.
+    fn keys(&self) -> std::result::Result<&MerkleKeys, ApiError> {
+        self.membership.keys()
+    }
+
+    /// Depth the membership circuit is served with.
+    fn depth(&self) -> usize {
+        self.membership.circuit().depth() as usize
     }
 
     /// Generates a zero-knowledge proof that the caller knows a secret whose Poseidon commitment
@@ -35,117 +102,533 @@ impl ZKService {
     /// * `secret` - The private secret value known by the prover
     ///
     /// # Returns
-    /// ZKProofResponse with proof=true if Poseidon(secret) is in the tree and verification passes.
-    pub fn zk_proof(&self, secret: u64) -> ZKProofResponse {
-        self.tree_service.with_tree(|tree| {
-            let commitment = poseidon_commit(secret);
-            let circuit = self.build_circuit(secret, commitment, tree);
-            let root = tree.root();
-
-            //k=9 gives 2^9=512 rows; needed for two Poseidon hashes (commitment + path levels)
-            let prover = MockProver::run(9, &circuit, vec![vec![root]]).unwrap();
-            ZKProofResponse {
-                proof: prover.verify().is_ok(),
-            }
+    /// ZKProofResponse with proof=true if Poseidon(secret) is in the tree and every constraint
+    /// is satisfied, or an error (converted from ZkProofError) explaining why the proof could
+    /// not be produced
+    pub fn zk_proof(&self, secret: u64) -> std::result::Result<ZKProofResponse, ApiError> {
+        let membership = self.membership.circuit();
+        let (circuit, public_inputs) = self
+            .tree_service
+            .with_tree(|tree| membership.build(tree, MembershipWitness { secret }))??;
+
+        let prover = MockProver::run(membership.k(), &circuit, vec![public_inputs])
+            .map_err(ZkProofError::from)?;
+        prover.verify().map_err(ZkProofError::from)?;
+        Ok(ZKProofResponse { proof: true })
+    }
+
+    /// Describes every circuit this service can prove and verify.
+    pub fn circuits(&self) -> Vec<CircuitInfo> {
+        self.registry.list()
+    }
+
+    /// Reports the minimal k, columns, degree and proof size of every served circuit, and of
+    /// the membership circuit at depths 1..=MAX_REPORT_DEPTH for capacity planning.
+    /// The report only depends on the circuits, so it is computed once and cached.
+    pub fn cost_report(&self) -> std::result::Result<CostReport, ApiError> {
+        if let Some(report) = self.cost_report.get() {
+            return Ok(report.clone());
+        }
+
+        let report = CostReport {
+            registered: self.registry.costs()?,
+            membership_by_depth: membership_cost_by_depth(1..=MAX_REPORT_DEPTH).map_err(|e| {
+                ApiError::internal("cost_measurement_failed", format!("{}: {:?}", CIRCUIT_ID, e))
+            })?,
+        };
+        Ok(self.cost_report.get_or_init(|| report).clone())
+    }
+
+    /// Looks up a registered circuit, failing with 404 if the id is unknown.
+    fn circuit(&self, circuit_id: &str) -> std::result::Result<&Arc<dyn ServedCircuit>, ApiError> {
+        self.registry.get(circuit_id).ok_or_else(|| {
+            ApiError::not_found("unknown_circuit", format!("unknown circuit: {}", circuit_id))
         })
     }
 
-    /// Builds the MerkleCircuit for the given secret, looking up its commitment in the tree.
-    /// Returns a circuit with dummy witnesses if the commitment is not found or the tree
-    /// depth does not match the circuit's fixed DEPTH (causing verification to fail gracefully).
-    fn build_circuit(
+    /// Fails with 422 `unknown_root` unless the root is current or in the tree's root history,
+    /// once padded to the proving circuit's `depth`.
+    /// Proofs against roots that were never (or are no longer) this tree's are not accepted,
+    /// even if they are otherwise valid.
+    fn expect_known_root(&self, root: &Fp, depth: usize) -> std::result::Result<(), ApiError> {
+        if self.tree_service.is_known_root_at_depth(root, depth)? {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unknown_root",
+                format!("root {} is not a current or recent root of the tree", fp_to_hex(root)),
+            ))
+        }
+    }
+
+    /// Creates a real proof for the given circuit and wraps it in a ProofEnvelope.
+    /// The witness is used privately and is never stored or logged.
+    ///
+    /// # Arguments
+    /// * `circuit_id` - Id of a registered circuit
+    /// * `witness` - The circuit's witness as JSON (e.g. `{ "secret": 42 }` for membership)
+    ///
+    /// # Returns
+    /// A ProofEnvelope carrying the circuit's public inputs, or an error if the circuit is
+    /// unknown, the witness is malformed or not provable against the current tree, or
+    /// proving fails
+    pub fn prove_circuit(
+        &self,
+        circuit_id: &str,
+        witness: serde_json::Value,
+    ) -> std::result::Result<ProofEnvelope, ApiError> {
+        self.circuit(circuit_id)?.prove(&self.tree_service, witness)
+    }
+
+    /// Verifies a proof envelope for the given circuit.
+    /// Envelopes for another circuit, circuit version, depth or k are rejected as errors rather
+    /// than reported as invalid proofs.
+    ///
+    /// # Arguments
+    /// * `circuit_id` - Id of a registered circuit
+    /// * `envelope` - The envelope returned by `prove_circuit`
+    /// * `require_known_root` - Also reject proofs whose root input is outside the tree's
+    ///   root history; otherwise only the proof itself is checked
+    ///
+    /// # Returns
+    /// ZKVerifyResponse with valid=true if the proof verifies against its recorded public inputs
+    pub fn verify_circuit(
         &self,
-        secret: u64,
-        commitment: Fp,
-        tree: &super::merkle_tree::MerkleTree,
-    ) -> MerkleCircuit {
-        let leaf_index = tree.leaves().iter().position(|&l| l == commitment);
-
-        let (siblings, directions) = leaf_index
-            .and_then(|idx| self.extract_proof_arrays(tree, idx))
-            .unwrap_or(([Fp::zero(); DEPTH], [Fp::zero(); DEPTH]));
-
-        MerkleCircuit {
-            secret: Value::known(Fp::from(secret)),
-            siblings: siblings.map(Value::known),
-            directions: directions.map(Value::known),
+        circuit_id: &str,
+        envelope: &ProofEnvelope,
+        require_known_root: bool,
+    ) -> std::result::Result<ZKVerifyResponse, ApiError> {
+        let circuit = self.circuit(circuit_id)?;
+        let public_inputs = circuit.public_inputs(envelope)?;
+        if require_known_root {
+            if let Some(root) = circuit.root_input().and_then(|i| public_inputs.get(i)) {
+                self.expect_known_root(root, circuit.info().depth as usize)?;
+            }
+        }
+        let valid = circuit.verify(envelope, public_inputs)?;
+        Ok(ZKVerifyResponse { valid })
+    }
+
+    /// Creates a real membership proof for a secret and wraps it in a ProofEnvelope.
+    /// The secret is used as a private witness and is never stored or logged.
+    ///
+    /// # Arguments
+    /// * `secret` - The private secret value known by the prover
+    ///
+    /// # Returns
+    /// A ProofEnvelope whose single public input is the current root, or an error if the
+    /// secret is not a provable member or proving fails
+    pub fn prove(&self, secret: u64) -> std::result::Result<ProofEnvelope, ApiError> {
+        self.prove_circuit(CIRCUIT_ID, serde_json::json!({ "secret": secret }))
+    }
+
+    /// Verifies a membership proof envelope produced by `prove`.
+    /// See `verify_circuit` for `require_known_root`.
+    pub fn verify(
+        &self,
+        envelope: &ProofEnvelope,
+        require_known_root: bool,
+    ) -> std::result::Result<ZKVerifyResponse, ApiError> {
+        self.verify_circuit(CIRCUIT_ID, envelope, require_known_root)
+    }
+
+    /// Proves membership for many secrets in a single batch proof against the current root.
+    /// Each secret is used as a private witness and is never stored or logged.
+    ///
+    /// # Arguments
+    /// * `secrets` - The private secrets whose Poseidon commitments must all be in the tree
+    ///
+    /// # Returns
+    /// BatchProofResponse with the single batch proof, or an error if any secret is not a
+    /// provable member or proving fails
+    pub fn prove_batch(
+        &self,
+        secrets: &[u64],
+    ) -> std::result::Result<BatchProofResponse, ApiError> {
+        if secrets.is_empty() {
+            return Err(ApiError::bad_request("empty_batch", "at least one secret is required"));
         }
+        if secrets.len() > self.limits.max_batch_size {
+            return Err(ApiError::bad_request(
+                "batch_too_large",
+                format!(
+                    "at most {} secrets can be proven in one batch, found {}",
+                    self.limits.max_batch_size,
+                    secrets.len()
+                ),
+            ));
+        }
+
+        let (root, circuits) = self.tree_service.with_tree(|tree| {
+            let circuits = secrets
+                .iter()
+                .enumerate()
+                .map(|(i, &secret)| {
+                    self.membership
+                        .circuit()
+                        .build(tree, MembershipWitness { secret })
+                        .map(|(circuit, _)| circuit)
+                        .map_err(|e| {
+                            let mut err = ApiError::from(e);
+                            err.message = format!("secret at position {}: {}", i, err.message);
+                            err
+                        })
+                })
+                .collect::<std::result::Result<Vec<_>, ApiError>>()?;
+            let root = tree
+                .root_at_depth(self.depth())
+                .expect("every circuit was built, so the tree fits the circuit");
+            Ok::<_, ApiError>((root, circuits))
+        })??;
+
+        let batch = prove_batch(self.keys()?, &circuits, root).map_err(ZkProofError::from)?;
+
+        Ok(BatchProofResponse {
+            root: FpHex(batch.root),
+            count: batch.count,
+            proof: hex::encode(&batch.proof),
+        })
     }
 
-    /// Extracts fixed-size sibling and direction arrays from a Merkle proof at a given index.
-    /// Returns None if the tree depth does not match the circuit's fixed DEPTH.
-    fn extract_proof_arrays(
+    /// Verifies a batch proof previously returned by `prove_batch`.
+    /// The root is taken from the proof itself; see `verify_circuit` for `require_known_root`.
+    ///
+    /// # Returns
+    /// ZKVerifyResponse with valid=true if the batch proof verifies, or an error if the
+    /// proof encoding is malformed or a required root check fails
+    pub fn verify_batch(
         &self,
-        tree: &super::merkle_tree::MerkleTree,
-        idx: usize,
-    ) -> Option<([Fp; DEPTH], [Fp; DEPTH])> {
-        let proof = tree.generate_proof(idx)?;
-        let siblings: [Fp; DEPTH] = proof.siblings.try_into().ok()?;
-        let directions: [Fp; DEPTH] = proof.directions.try_into().ok()?;
-        Some((siblings, directions))
+        proof: &BatchProofResponse,
+        require_known_root: bool,
+    ) -> std::result::Result<ZKVerifyResponse, ApiError> {
+        let root = proof.root.0;
+        let bytes = hex::decode(&proof.proof).map_err(|e| {
+            ApiError::bad_request("invalid_proof", format!("invalid proof hex: {}", e))
+        })?;
+        if require_known_root {
+            self.expect_known_root(&root, self.depth())?;
+        }
+
+        let batch = BatchProof {
+            root,
+            count: proof.count,
+            proof: bytes,
+        };
+        Ok(ZKVerifyResponse {
+            valid: verify_batch(self.keys()?, &batch),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::merkle_tree_service::poseidon_commit;
+    use super::super::merkle_range_circuit::MERKLE_RANGE_CIRCUIT_ID;
+    use super::super::merkle_tree::MerkleTree;
+    use super::super::merkle_tree_service::{poseidon_commit, poseidon_commit_attribute};
+    use super::super::prover::{membership_k, K};
+    use super::super::range_check::RANGE_CIRCUIT_ID;
     use super::*;
+    use halo2_proofs::pasta::Fp;
 
-    //seed secrets used in MerkleTreeService::new(): [42, 99, 7, 13, 55, 77, 100, 200]
+    /// Secrets of the 8 members (a full tree at the default depth 3) the tests prove with.
+    const SECRETS: [u64; 8] = [42, 99, 7, 13, 55, 77, 100, 200];
+
+    /// Tree service whose tree holds the commitments of SECRETS.
+    fn member_tree_service() -> Arc<MerkleTreeService> {
+        let commitments: Vec<Fp> = SECRETS.iter().map(|&s| poseidon_commit(s)).collect();
+        let service = MerkleTreeService::new();
+        service.import_commitments(&commitments).unwrap();
+        Arc::new(service)
+    }
 
     #[test]
     fn test_zk_proof_with_valid_secret() {
-        let tree_service = Arc::new(MerkleTreeService::new());
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        //secret 42 is a seed secret — its commitment is in the tree
-        assert!(service.zk_proof(42).proof, "proof should succeed for seed secret 42");
+        //secret 42 is a member secret — its commitment is in the tree
+        assert!(service.zk_proof(42).unwrap().proof, "proof should succeed for member secret 42");
     }
 
     #[test]
     fn test_zk_proof_with_another_valid_secret() {
-        let tree_service = Arc::new(MerkleTreeService::new());
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        //secret 99 is also a seed secret
-        assert!(service.zk_proof(99).proof, "proof should succeed for seed secret 99");
+        //secret 99 is also a member secret
+        assert!(service.zk_proof(99).unwrap().proof, "proof should succeed for member secret 99");
     }
 
     #[test]
     fn test_zk_proof_with_invalid_secret() {
-        let tree_service = Arc::new(MerkleTreeService::new());
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        //secret 1 is not a seed secret
-        assert!(!service.zk_proof(1).proof, "proof should fail for unknown secret 1");
+        //secret 1 is not a member secret
+        assert_eq!(
+            service.zk_proof(1).unwrap_err().code,
+            "commitment_not_in_tree",
+            "proof should fail for unknown secret 1"
+        );
     }
 
     #[test]
     fn test_zk_proof_with_zero_secret() {
-        let tree_service = Arc::new(MerkleTreeService::new());
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        assert!(!service.zk_proof(0).proof, "proof should fail for secret 0");
+        assert_eq!(
+            service.zk_proof(0).unwrap_err().code,
+            "commitment_not_in_tree",
+            "proof should fail for secret 0"
+        );
     }
 
     #[test]
-    fn test_zk_proof_last_seed_secret() {
-        let tree_service = Arc::new(MerkleTreeService::new());
+    fn test_zk_proof_last_member_secret() {
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        //secret 200 is the last seed secret (index 7)
-        assert!(service.zk_proof(200).proof, "proof should succeed for seed secret 200");
+        //secret 200 is the last member secret (index 7)
+        assert!(
+            service.zk_proof(200).unwrap().proof,
+            "proof should succeed for member secret 200"
+        );
+    }
+
+    #[test]
+    fn test_prove_and_verify_envelope() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+
+        let envelope = service.prove(42).unwrap();
+        assert_eq!(envelope.circuit_id, CIRCUIT_ID);
+
+        //the envelope survives a round trip through both wire formats
+        let from_json = ProofEnvelope::from_json(&envelope.to_json()).unwrap();
+        let from_cbor = ProofEnvelope::from_cbor(&envelope.to_cbor()).unwrap();
+        assert!(service.verify(&from_json, true).unwrap().valid);
+        assert!(service.verify(&from_cbor, true).unwrap().valid);
     }
 
     #[test]
-    fn test_zk_proof_after_register() {
-        // The circuit is fixed at DEPTH=3 (8-leaf tree).
-        // Registering a 9th leaf grows the tree to depth 4; the circuit cannot
-        // accommodate the longer proof path, so verification fails gracefully
-        // (returns false) rather than panicking.
+    fn test_prove_with_fewer_members_than_the_circuit_depth() {
+        //trees of 1 to 4 members are shallower than the default depth 3; their paths are padded with zero
+        //subtrees and the proofs are against the padded roots
         let tree_service = Arc::new(MerkleTreeService::new());
-        let commitment = poseidon_commit(999);
-        tree_service.register_commitment(commitment);
+        let service = ZKService::new(tree_service.clone());
+        let mut envelopes = Vec::new();
+        for &secret in &SECRETS[..4] {
+            tree_service
+                .import_commitments(&[poseidon_commit(secret)])
+                .unwrap();
+            let envelope = service.prove(secret).unwrap();
+            assert!(service.verify(&envelope, true).unwrap().valid, "secret {}", secret);
+            envelopes.push(envelope);
+        }
+
+        //the padded roots of earlier, shallower trees are still in the root history
+        for envelope in &envelopes {
+            assert!(service.verify(envelope, true).unwrap().valid);
+        }
+    }
 
+    #[test]
+    fn test_verify_rejects_mismatched_circuit() {
+        let tree_service = member_tree_service();
         let service = ZKService::new(tree_service);
-        assert!(
-            !service.zk_proof(999).proof,
-            "proof should fail gracefully when tree depth exceeds circuit DEPTH=3"
-        );
+
+        let mut envelope = service.prove(42).unwrap();
+        envelope.depth += 1;
+        assert!(service.verify(&envelope, false).is_err(), "depth mismatch must be rejected");
+    }
+
+    #[test]
+    fn test_prove_range_for_attribute_commitment() {
+        //replace the tree with attribute commitments (secret s has attribute 20 + s)
+        let tree_service = member_tree_service();
+        tree_service
+            .with_tree_mut(|tree| {
+                *tree = MerkleTree::new(
+                    (0..8u64)
+                        .map(|s| poseidon_commit_attribute(s, 20 + s))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .unwrap();
+        let service = ZKService::new(tree_service);
+
+        let witness = serde_json::json!({ "secret": 3, "attribute": 23, "min": 18, "max": 65 });
+        let envelope = service
+            .prove_circuit(MERKLE_RANGE_CIRCUIT_ID, witness)
+            .unwrap();
+        assert_eq!(envelope.public_inputs.len(), 3);
+        let verified = service
+            .verify_circuit(MERKLE_RANGE_CIRCUIT_ID, &envelope, true)
+            .unwrap();
+        assert!(verified.valid);
+
+        //the envelope is bound to its circuit
+        assert!(service.verify(&envelope, false).is_err());
+    }
+
+    #[test]
+    fn test_prove_range_rejects_out_of_range_attribute() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+        let witness = serde_json::json!({ "secret": 42, "attribute": 10, "min": 18, "max": 65 });
+        let err = service
+            .prove_circuit(MERKLE_RANGE_CIRCUIT_ID, witness)
+            .unwrap_err();
+        assert_eq!(err.code, "invalid_witness");
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn test_prove_standalone_range() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+
+        let witness = serde_json::json!({ "value": 30, "min": 18, "max": 65 });
+        let envelope = service.prove_circuit(RANGE_CIRCUIT_ID, witness).unwrap();
+        assert_eq!(envelope.public_inputs.len(), 2);
+
+        //the circuit has no root input, so the known-root check does not apply
+        let verified = service
+            .verify_circuit(RANGE_CIRCUIT_ID, &envelope, true)
+            .unwrap();
+        assert!(verified.valid);
+    }
+
+    #[test]
+    fn test_registry_lists_and_rejects_unknown_circuits() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+
+        let ids: Vec<String> = service.circuits().into_iter().map(|c| c.id).collect();
+        assert!(ids.contains(&CIRCUIT_ID.to_string()));
+        assert!(ids.contains(&MERKLE_RANGE_CIRCUIT_ID.to_string()));
+        assert!(ids.contains(&RANGE_CIRCUIT_ID.to_string()));
+
+        let witness = serde_json::json!({ "secret": 42 });
+        let err = service
+            .prove_circuit("no-such-circuit", witness)
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_prove_circuit_rejects_malformed_witness() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+        let witness = serde_json::json!({ "not_a_secret": 42 });
+        let err = service.prove_circuit(CIRCUIT_ID, witness).unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_prove_batch_round_trip() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+
+        let response = service.prove_batch(&[42, 99, 200]).unwrap();
+        assert_eq!(response.count, 3);
+
+        let verified = service.verify_batch(&response, true).unwrap();
+        assert!(verified.valid, "batch proof for member secrets should verify");
+    }
+
+    #[test]
+    fn test_prove_batch_rejects_non_member() {
+        let tree_service = member_tree_service();
+        let service = ZKService::new(tree_service);
+
+        assert!(service.prove_batch(&[42, 1]).is_err(), "secret 1 is not in the tree");
+        assert!(service.prove_batch(&[]).is_err(), "empty batch should be rejected");
+    }
+
+    #[test]
+    fn test_prove_batch_enforces_batch_limit() {
+        let tree_service = member_tree_service();
+        let limits = ProvingConfig { max_batch_size: 2 };
+        let service = ZKService::with_config(tree_service, &limits);
+
+        let err = service.prove_batch(&[42, 99, 7]).unwrap_err();
+        assert_eq!(err.code, "batch_too_large");
+    }
+
+    #[test]
+    fn test_verify_rejects_root_outside_history() {
+        let mut config = crate::config::Config::default();
+        config.tree.root_history_size = 1;
+        config.tree.depth = 4;
+        let commitments: Vec<Fp> = SECRETS.iter().map(|&s| poseidon_commit(s)).collect();
+        let tree_service = Arc::new(MerkleTreeService::with_members(&config, commitments));
+        let service = ZKService::new(tree_service.clone());
+
+        let envelope = service.prove(42).unwrap();
+        assert!(service.verify(&envelope, true).unwrap().valid);
+
+        //with a history of one, any registration makes the proof's root stale
+        tree_service.add_to_tree(1000).unwrap();
+        let err = service.verify(&envelope, true).unwrap_err();
+        assert_eq!(err.code, "unknown_root");
+
+        //the root check is opt-in; the proof itself is still valid for its root
+        assert!(service.verify(&envelope, false).unwrap().valid);
+    }
+
+    #[test]
+    fn test_zk_proof_at_configured_depth() {
+        //with tree.depth = 4 the circuits are built for 16 leaves, so a 9th member that grows
+        //the tree to depth 4 can still prove
+        let mut config = crate::config::Config::default();
+        config.tree.depth = 4;
+        let commitments: Vec<Fp> = SECRETS.iter().map(|&s| poseidon_commit(s)).collect();
+        let tree_service = Arc::new(MerkleTreeService::with_members(&config, commitments));
+        tree_service
+            .register_commitment(poseidon_commit(999))
+            .unwrap();
+
+        let service = ZKService::new(tree_service);
+        assert!(service.zk_proof(999).unwrap().proof);
+        let info = service
+            .circuits()
+            .into_iter()
+            .find(|c| c.id == CIRCUIT_ID)
+            .unwrap();
+        assert_eq!((info.depth, info.k), (4, membership_k(4)));
+
+        let envelope = service.prove(999).unwrap();
+        assert_eq!(envelope.depth, 4);
+        assert!(service.verify(&envelope, true).unwrap().valid);
+    }
+
+    #[test]
+    fn test_tree_deeper_than_circuit_is_rejected() {
+        //a tree of 9 leaves has depth 4, which a depth-3 circuit cannot accommodate; proving
+        //fails with DepthUnsupported rather than panicking
+        let tree = MerkleTree::new((1..=9u64).map(poseidon_commit).collect::<Vec<_>>());
+        let err = MerkleCircuit::new(3)
+            .build(&tree, MembershipWitness { secret: 9 })
+            .unwrap_err();
+        assert_eq!(err.code(), "depth_unsupported");
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn test_unsatisfied_constraints_are_named() {
+        //an honest witness checked against the wrong root violates the root copy constraint
+        let tree_service = member_tree_service();
+        let (circuit, _) = tree_service
+            .with_tree(|tree| {
+                MerkleCircuit::default().build(tree, MembershipWitness { secret: 42 })
+            })
+            .unwrap()
+            .unwrap();
+        let prover = MockProver::run(K, &circuit, vec![vec![Fp::from(1u64)]]).unwrap();
+
+        let err = ZkProofError::from(prover.verify().unwrap_err());
+        match &err {
+            ZkProofError::VerificationFailed { failures } => assert!(!failures.is_empty()),
+            other => panic!("expected VerificationFailed, got {:?}", other),
+        }
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 }
@@ -0,0 +1,135 @@
+//! End-to-end tests of the `zk-cli` binary: commit, tree build, prove, vk and verify.
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::PathBuf;
+use zk_rust_api::{fp_to_hex, poseidon_commit, Fp};
+
+/// A fresh scratch directory for one test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn zk_cli() -> Command {
+    Command::cargo_bin("zk-cli").unwrap()
+}
+
+/// Writes a leaves file with the commitments of secrets 1 to 8, as printed by `commit`.
+fn write_leaves(dir: &std::path::Path) -> PathBuf {
+    let mut leaves = String::from("# members\n");
+    for secret in 1..=8u64 {
+        let output = zk_cli()
+            .arg("commit")
+            .arg(secret.to_string())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        leaves.push_str(&String::from_utf8(output.stdout).unwrap());
+    }
+    let path = dir.join("leaves.txt");
+    fs::write(&path, leaves).unwrap();
+    path
+}
+
+#[test]
+fn test_commit_prints_the_poseidon_commitment() {
+    zk_cli()
+        .args(["commit", "42"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", fp_to_hex(&poseidon_commit(42))));
+}
+
+#[test]
+fn test_prove_then_verify_round_trip() {
+    let dir = scratch_dir("round-trip");
+    let leaves = write_leaves(&dir);
+    let path = dir.join("path.json");
+    let proof = dir.join("proof.json");
+    let vk = dir.join("vk.json");
+
+    zk_cli()
+        .args(["tree", "build", "--index", "2", "--leaves"])
+        .arg(&leaves)
+        .arg("--out")
+        .arg(&path)
+        .assert()
+        .success();
+    zk_cli()
+        .args(["prove", "--secret", "3", "--path"])
+        .arg(&path)
+        .arg("--out")
+        .arg(&proof)
+        .assert()
+        .success();
+    zk_cli().arg("vk").arg("--out").arg(&vk).assert().success();
+    zk_cli()
+        .arg("verify")
+        .arg("--proof")
+        .arg(&proof)
+        .arg("--vk")
+        .arg(&vk)
+        .assert()
+        .success()
+        .stdout("valid\n");
+
+    //the same proof against another root is rejected
+    let mut envelope: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&proof).unwrap()).unwrap();
+    envelope["public_inputs"][0] = fp_to_hex(&Fp::from(1)).into();
+    let forged = dir.join("forged.json");
+    fs::write(&forged, envelope.to_string()).unwrap();
+    zk_cli()
+        .arg("verify")
+        .arg("--proof")
+        .arg(&forged)
+        .arg("--vk")
+        .arg(&vk)
+        .assert()
+        .failure()
+        .stderr("error: invalid proof\n");
+
+    //and so is a key file whose key does not match its fingerprint
+    let mut key: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&vk).unwrap()).unwrap();
+    key["fingerprint"] = "00".repeat(32).into();
+    let tampered = dir.join("tampered.json");
+    fs::write(&tampered, key.to_string()).unwrap();
+    zk_cli()
+        .arg("verify")
+        .arg("--proof")
+        .arg(&proof)
+        .arg("--vk")
+        .arg(&tampered)
+        .assert()
+        .failure()
+        .stderr("error: verifying key fingerprint does not match\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_prove_rejects_a_secret_that_is_not_the_leaf() {
+    let dir = scratch_dir("wrong-secret");
+    let leaves = write_leaves(&dir);
+    let path = dir.join("path.json");
+
+    zk_cli()
+        .args(["tree", "build", "--index", "0", "--leaves"])
+        .arg(&leaves)
+        .arg("--out")
+        .arg(&path)
+        .assert()
+        .success();
+    zk_cli()
+        .args(["prove", "--secret", "2", "--path"])
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr("error: Poseidon(secret) does not match the leaf in the path file\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -73,7 +73,6 @@ function RegisterPanel() {
 
 /**
  * Panel for generating a ZK proof that the user knows a registered secret.
- * Seed secrets pre-loaded in the tree: 42, 99, 7, 13, 55, 77, 100, 200.
  */
 function ProvePanel() {
     const [secret, setSecret] = useState('');
@@ -101,7 +100,7 @@ function ProvePanel() {
             <p className="text-sm text-gray-500 mb-4">
                 Enter your secret. The server proves <code>Poseidon(secret)</code> is in the tree
                 without revealing which commitment matches.
-                Try one of the seed secrets: <code>42, 99, 7, 13, 55, 77, 100, 200</code>.
+                The secret must have been registered first.
             </p>
 
             <form onSubmit={handleSubmit} className="flex gap-3 mb-3">
@@ -2,7 +2,19 @@
  * Response from adding a value to the tree or registering a commitment
  */
 export interface TreeResponse {
-    data: string;
+    index: number; // position of the new leaf, or of the existing one for an idempotent register
+    data: string; // new root, 64-char little-endian hex Fp field element
+    head: SignedTreeHead;
+}
+
+/**
+ * Tree checkpoint signed with the server's ed25519 key
+ */
+export interface SignedTreeHead {
+    size: number;
+    root: string;
+    timestamp: number; // milliseconds since the Unix epoch
+    signature: string; // 128 hex chars
 }
 
 /**
@@ -24,7 +36,8 @@ export interface AddToTreeRequest {
  * The client computes commitment = Poseidon(secret) locally and sends only the commitment.
  */
 export interface RegisterRequest {
-    commitment: string; // 64-char hex Fp field element
+    commitment: string; // 64-char hex Fp field element, must not be zero
+    idempotent?: boolean; // return the existing leaf instead of 409 if already registered
 }
 
 /**